@@ -1,25 +1,65 @@
 // mod avl;
-use std::cell::RefCell;
+use std::borrow;
+use std::cmp::Ordering;
 use std::fmt::Display;
-use std::rc::Rc;
-
-type Tree<T> = Rc<RefCell<AVLNode<T>>>;
-type AVLTree<T> = Option<Tree<T>>;
+use std::io::{Read, Write};
+use std::iter::FromIterator;
+use std::ops::RangeBounds;
+use tree_core::arena::{Arena, Link, NodeId};
+use tree_core::augment::{self, Aggregate, Augmented};
+use tree_core::merge::{Merge, MergeItem};
+use tree_core::snapshot::{self, Codec, Header, NodeTag, SnapshotError, TreeKind};
+use tree_core::traverse::{
+    Entries, InOrder, Keys, LevelOrder, NodeHandle, PostOrder, PreOrder, Range,
+};
+use tree_core::validate::{self, Problem, Report, Side, Violation};
+use tree_core::{build, order, pretty, Comparator, DuplicatePolicy, Natural, SortedTree};
 
+// a node of AVL and AVLMap. it lives in the arena of its tree and links to its
+// children by their index in that arena
 #[derive(Clone, Debug)]
-pub struct AVLNode<T> {
+struct AVLNode<K, V = (), A = ()> {
     // actual node
-    pub key: T,
+    key: K,
+    value: V,
     height: i32,
-    left: AVLTree<T>,
-    right: AVLTree<T>,
+    // number of nodes in the subtree rooted here
+    size: usize,
+    // aggregate of the entries in the subtree rooted here, see tree_core::augment
+    aggregate: A,
+    left: Link,
+    right: Link,
+}
+
+// borrowed handle to a node in the arena of a tree. the crate does not export
+// it, it is only reachable as the item type of the traversals, which read the
+// node through NodeHandle
+pub struct NodeRef<'a, K, V = (), A = ()> {
+    nodes: &'a Arena<AVLNode<K, V, A>>,
+    id: NodeId,
 }
 
 // tree object that allows calls to self
 // easier usage for user and abstracts the nodes away from tree
-#[derive(Clone, Debug)]
-pub struct AVL<T: Ord + Copy + Display> {
-    root: AVLTree<T>,
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug)]
+pub struct AVL<T, A = (), C = Natural> {
+    // the keys, each with an empty value
+    map: AVLMap<T, (), A>,
+    // what insert does with a key that is already in the tree
+    duplicates: DuplicatePolicy,
+    // decides the order of the keys, see tree_core::compare
+    cmp: C,
+}
+
+// same tree as AVL but every key carries a value, used as an ordered dictionary.
+// all nodes are kept in one arena, see tree_core::arena, so the tree needs no
+// reference counting or runtime borrow checks and the slot of a removed node
+// goes to the next insert
+#[derive(Debug)]
+pub struct AVLMap<K, V, A = ()> {
+    nodes: Arena<AVLNode<K, V, A>>,
+    root: Link,
 }
 
 // a enum that holds the rotation cases for clarity
@@ -32,466 +72,2009 @@ enum RotationCase {
     RL,
 }
 
-impl<T: Ord + Copy + Display> AVLNode<T> {
-    // creates a new avl node with data
-    pub fn new(data: T) -> AVLTree<T> {
-        Some(Rc::new(RefCell::new(Self {
+// the node algorithms, they work on any order of the keys given as a comparator
+impl<K, V, A: Aggregate<K, V>> AVLMap<K, V, A> {
+    // stores a new leaf node with data in the arena
+    fn new_node(&mut self, data: K, value: V) -> NodeId {
+        let aggregate = A::from_entry(&data, &value);
+        self.nodes.alloc(AVLNode {
             key: data,
+            value,
             height: 1,
+            size: 1,
+            aggregate,
             left: None,
             right: None,
-        })))
+        })
+    }
+
+    // borrowed handle to the node id, what the traversals walk
+    fn node_ref(&self, id: NodeId) -> NodeRef<'_, K, V, A> {
+        NodeRef {
+            nodes: &self.nodes,
+            id,
+        }
+    }
+
+    fn root_ref(&self) -> Option<NodeRef<'_, K, V, A>> {
+        self.root.map(|id| self.node_ref(id))
     }
 
     // gets height of tree, 0 if node doesn't exist
-    pub fn height(node: AVLTree<T>) -> i32 {
-        return match node {
+    fn height_of(&self, node: Link) -> i32 {
+        match node {
             None => 0,
-            Some(cur) => cur.borrow().height,
-        };
+            Some(id) => self.nodes[id].height,
+        }
     }
 
-    // gets the min key of tree rooted at node given
-    pub fn getMinVal(node: AVLTree<T>) -> T {
-        let n_unwrap = node.unwrap();
-        let n_bor = n_unwrap.borrow();
-        return if n_bor.right.is_some() {
-            Self::getMinVal(n_bor.right.clone())
-        } else {
-            n_bor.key
-        };
+    // gets number of nodes in tree, 0 if node doesn't exist
+    fn size_of(&self, node: Link) -> usize {
+        match node {
+            None => 0,
+            Some(id) => self.nodes[id].size,
+        }
+    }
+
+    // recomputes height, size and aggregate of id from its children
+    fn update(&mut self, id: NodeId) {
+        let n = &self.nodes[id];
+        let left = n.left.map(|l| &self.nodes[l]);
+        let right = n.right.map(|r| &self.nodes[r]);
+        let height = 1 + self.height_of(n.left).max(self.height_of(n.right));
+        let size = 1 + self.size_of(n.left) + self.size_of(n.right);
+        let aggregate = augment::summarize(
+            &n.key,
+            &n.value,
+            left.map(|l| &l.aggregate),
+            right.map(|r| &r.aggregate),
+        );
+        let n = &mut self.nodes[id];
+        n.height = height;
+        n.size = size;
+        n.aggregate = aggregate;
     }
 
-    // calculates the avl balance algo. returns the new root
-    pub fn getBalance(node: AVLTree<T>) -> i32 {
+    // calculates the avl balance of node, left height minus right height
+    fn get_balance(&self, node: Link) -> i32 {
         match node {
             None => 0,
-            Some(n) => {
-                let n_b = n.borrow();
-                Self::height(n_b.left.clone()) - Self::height(n_b.right.clone())
+            Some(id) => {
+                let n = &self.nodes[id];
+                self.height_of(n.left) - self.height_of(n.right)
             }
         }
     }
 
-    // insert treating self as the root
-    pub fn insert(node: AVLTree<T>, data: T) -> AVLTree<T> {
-        if node.is_none() {
+    // inserts the entry below node, returns the new root and the entry that is
+    // not in the tree afterwards if the key was there already: the new one if
+    // duplicates reject it, the old one if they replace it. keeping duplicates
+    // puts an equal key right of the ones before it. keys are ordered by cmp
+    fn insert_entry<C: Comparator<K>>(
+        &mut self,
+        node: Link,
+        data: K,
+        value: V,
+        duplicates: DuplicatePolicy,
+        cmp: &C,
+    ) -> (NodeId, Option<(K, V)>) {
+        let id = match node {
             // no node exist thus insert one here
-            return Self::new(data);
-        }
-
-        let rc_node = node.clone().unwrap();
-        let bal;
-        let rcase: RotationCase;
-        {
-            // mutable block for changing height + bst insert
-            let mut cur = rc_node.borrow_mut();
-
-            // recursive stanadard binary tree insertion
-            if data < cur.key {
-                cur.left = Self::insert(cur.left.clone(), data);
-            } else if data > cur.key {
-                cur.right = Self::insert(cur.right.clone(), data);
-            } else {
-                return node;
-            }
+            None => return (self.new_node(data, value), None),
+            Some(id) => id,
+        };
 
-            // calculate properties
-            cur.height = 1 + Self::height(cur.left.clone()).max(Self::height(cur.right.clone()));
-            bal = Self::height(cur.left.clone()) - Self::height(cur.right.clone());
-
-            let l_key;
-            let r_key;
-            match cur.left.clone() {
-                None => {
-                    l_key = data;
-                }
-                Some(l_node) => l_key = l_node.borrow().key,
+        // recursive standard binary tree insertion
+        let displaced = match cmp.compare(&data, &self.nodes[id].key) {
+            Ordering::Less => {
+                let (left, displaced) =
+                    self.insert_entry(self.nodes[id].left, data, value, duplicates, cmp);
+                self.nodes[id].left = Some(left);
+                displaced
             }
-            match cur.right.clone() {
-                None => {
-                    r_key = data;
-                }
-                Some(r_node) => r_key = r_node.borrow().key,
+            Ordering::Equal if duplicates == DuplicatePolicy::Reject => {
+                return (id, Some((data, value)));
             }
-
-            // compute rotation cases
-            if (bal > 1) && (data < l_key) {
-                rcase = RotationCase::LL;
-            } else if (bal < -1) && (data > r_key) {
-                rcase = RotationCase::RR;
-            } else if (bal > 1) && (data > l_key) {
-                rcase = RotationCase::LR;
-            } else if (bal < -1) && (data < r_key) {
-                rcase = RotationCase::RL;
-            } else {
-                rcase = RotationCase::Nil;
+            Ordering::Equal if duplicates == DuplicatePolicy::Replace => {
+                // key already present, keep the node and swap in the new entry
+                let n = &mut self.nodes[id];
+                let key = std::mem::replace(&mut n.key, data);
+                let value = std::mem::replace(&mut n.value, value);
+                Some((key, value))
             }
-        }
-
-        // return the node after rotation cases run
-        return match rcase {
-            RotationCase::LL => Self::rightRotate(rc_node),
-            RotationCase::RR => Self::leftRotate(rc_node),
-            RotationCase::LR => {
-                let tmp = Self::leftRotate(rc_node.borrow().left.clone().unwrap());
-                rc_node.borrow_mut().left = tmp;
-                Self::rightRotate(rc_node)
+            Ordering::Greater | Ordering::Equal => {
+                let (right, displaced) =
+                    self.insert_entry(self.nodes[id].right, data, value, duplicates, cmp);
+                self.nodes[id].right = Some(right);
+                displaced
             }
-            RotationCase::RL => {
-                let tmp = Self::rightRotate(rc_node.borrow().right.clone().unwrap());
-                rc_node.borrow_mut().right = tmp;
-                Self::leftRotate(rc_node)
-            }
-            RotationCase::Nil => node,
         };
-    }
 
-    // algo for right rotations
-    pub fn rightRotate(node: Tree<T>) -> AVLTree<T> {
-        let mut cur = node.borrow_mut();
-        let l = cur.left.clone();
-        let l_unwrap = l.clone().unwrap();
-        let mut l_mut = l_unwrap.borrow_mut();
+        if displaced.is_some() {
+            // no new node, but an entry under this node may have changed
+            self.update(id);
+            return (id, displaced);
+        }
+        (self.rebalance(id), None)
+    }
 
-        let lr = l_mut.right.clone();
+    // algo for right rotations, returns the new root of the subtree
+    fn right_rotate(&mut self, id: NodeId) -> NodeId {
+        let l = self.nodes[id]
+            .left
+            .expect("right rotation needs a left child");
+        let lr = self.nodes[l].right;
 
         // rotate
-        l_mut.right = Some(node.clone());
-        cur.left = lr;
+        self.nodes[l].right = Some(id);
+        self.nodes[id].left = lr;
+
+        // update height, size and aggregate, node first as it is now below l
+        self.update(id);
+        self.update(l);
+        l
+    }
 
-        // update height
-        cur.height = 1 + Self::height(cur.left.clone()).max(Self::height(cur.right.clone()));
-        l_mut.height = 1 + Self::height(l_mut.left.clone()).max(cur.height);
+    // algo for left rotatons, returns the new root of the subtree
+    fn left_rotate(&mut self, id: NodeId) -> NodeId {
+        let r = self.nodes[id]
+            .right
+            .expect("left rotation needs a right child"); // x .right (y)
+        let rl = self.nodes[r].left; // t2
 
-        return l;
+        // rotate
+        self.nodes[r].left = Some(id);
+        self.nodes[id].right = rl;
+
+        // update height, size and aggregate, node first as it is now below r
+        self.update(id);
+        self.update(r);
+        r
     }
 
-    // algo for left rotatons
-    pub fn leftRotate(node: Tree<T>) -> AVLTree<T> {
-        let mut cur = node.borrow_mut(); // x
-        let r = cur.right.clone(); // x .right (y)
-        let r_unwrap = r.clone().unwrap();
-        let mut r_mut = r_unwrap.borrow_mut(); // y
+    // restores the balance of id after one of its subtrees grew or shrank in
+    // height by one, returns the new root of the subtree
+    fn rebalance(&mut self, id: NodeId) -> NodeId {
+        self.update(id);
+        let bal = self.get_balance(Some(id));
+        let (left, right) = (self.nodes[id].left, self.nodes[id].right);
+        let lbal = self.get_balance(left);
+        let rbal = self.get_balance(right);
 
-        let rl = r_mut.left.clone(); // t2
+        let rcase = if (bal > 1) && (lbal >= 0) {
+            RotationCase::LL
+        } else if (bal < -1) && (rbal <= 0) {
+            RotationCase::RR
+        } else if bal > 1 {
+            RotationCase::LR
+        } else if bal < -1 {
+            RotationCase::RL
+        } else {
+            RotationCase::Nil
+        };
 
-        // rotate
-        r_mut.left = Some(node.clone());
-        cur.right = rl;
-
-        // update height
-        cur.height = 1 + Self::height(cur.left.clone()).max(Self::height(cur.right.clone()));
-        r_mut.height = 1 + cur.height.max(Self::height(r_mut.right.clone()));
-
-        return r;
-    }
-
-    // returns bool if node exists
-    pub fn search(root: AVLTree<T>, data: T) -> bool {
-        return match root {
-            None => false,
-            Some(node) => {
-                let n = node.borrow();
-                if data < n.key {
-                    return Self::search(n.left.clone(), data);
-                } else if data > n.key {
-                    return Self::search(n.right.clone(), data);
-                } else {
-                    true
-                }
+        match rcase {
+            RotationCase::LL => self.right_rotate(id),
+            RotationCase::RR => self.left_rotate(id),
+            RotationCase::LR => {
+                let tmp = self.left_rotate(left.unwrap());
+                self.nodes[id].left = Some(tmp);
+                self.right_rotate(id)
             }
-        };
+            RotationCase::RL => {
+                let tmp = self.right_rotate(right.unwrap());
+                self.nodes[id].right = Some(tmp);
+                self.left_rotate(id)
+            }
+            RotationCase::Nil => id,
+        }
+    }
+
+    // finds the node holding data in the order of cmp
+    fn find<Q, C>(&self, data: &Q, cmp: &C) -> Link
+    where
+        K: borrow::Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut cur = self.root;
+        while let Some(id) = cur {
+            let n = &self.nodes[id];
+            match cmp.compare(data, n.key.borrow()) {
+                Ordering::Less => cur = n.left,
+                Ordering::Greater => cur = n.right,
+                Ordering::Equal => return Some(id),
+            }
+        }
+        None
     }
 
-    // deletes starting at root returns new root
-    pub fn delete(root: AVLTree<T>, data: T) -> AVLTree<T> {
-        if root.is_none() {
+    // deletes data from the subtree under node with the keys ordered by cmp,
+    // returns the new root and the entry that was taken out
+    fn delete_by<Q, C>(&mut self, node: Link, data: &Q, cmp: &C) -> (Link, Option<(K, V)>)
+    where
+        K: borrow::Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let id = match node {
             // no deletion cases
-            return root;
-        }
-
-        let rc_node = root.clone().unwrap();
-        let rcase: RotationCase;
-        let mut rem_node: AVLTree<T> = None;
-        {
-            // mutability block
-            let mut cur = rc_node.borrow_mut();
-            let rem_unwrap;
-            let mut rem_mut;
-
-            // standard bst recurse deletion
-            if data < cur.key {
-                cur.left = Self::delete(cur.left.clone(), data);
-                rem_node = root;
-                rem_mut = cur;
-            } else if data > cur.key {
-                cur.right = Self::delete(cur.right.clone(), data);
-                rem_node = root;
-                rem_mut = cur;
-            } else {
+            None => return (None, None),
+            Some(id) => id,
+        };
+
+        // standard bst recurse deletion
+        let removed = match cmp.compare(data, self.nodes[id].key.borrow()) {
+            Ordering::Less => {
+                let (left, removed) = self.delete_by(self.nodes[id].left, data, cmp);
+                self.nodes[id].left = left;
+                removed
+            }
+            Ordering::Greater => {
+                let (right, removed) = self.delete_by(self.nodes[id].right, data, cmp);
+                self.nodes[id].right = right;
+                removed
+            }
+            Ordering::Equal => {
                 // delete here
-                if cur.left.is_none() && cur.right.is_none() {
-                    // no child
-                    return None;
-                } else if cur.left.is_some() && cur.right.is_some() {
-                    // get inorder, copy keys, delete lowest
-                    let tmp = Self::getMinVal(cur.right.clone());
-                    cur.key = tmp;
-                    cur.right = Self::delete(cur.right.clone(), tmp);
-                    rem_node = root.clone();
-                    rem_mut = cur;
-                } else if cur.left.is_some() {
-                    rem_node = cur.left.clone();
-                    rem_unwrap = rem_node.clone().unwrap();
-                    rem_mut = rem_unwrap.borrow_mut();
-                } else if cur.right.is_some() {
-                    rem_node = cur.right.clone();
-                    rem_unwrap = rem_node.clone().unwrap();
-                    rem_mut = rem_unwrap.borrow_mut();
-                } else {
-                    panic!("This cond doesn't exist");
-                }
+                let (left, right) = (self.nodes[id].left, self.nodes[id].right);
+                let replacement = match (left, right) {
+                    // no child or one child, which takes the place of this node
+                    (None, child) | (child, None) => child,
+                    // the inorder successor is taken out of the right subtree
+                    // and takes the place of this node
+                    (Some(_), Some(right)) => {
+                        let (rest, succ) = self.delete_min(right);
+                        self.nodes[succ].left = left;
+                        self.nodes[succ].right = rest;
+                        Some(self.rebalance(succ))
+                    }
+                };
+                let n = self.nodes.free(id);
+                return (replacement, Some((n.key, n.value)));
             }
+        };
 
-            // rem_node shouldn't be none but if it is just return no root
-            if rem_node.is_none() {
-                println!("the impossible happened");
-                return rem_node;
-            }
+        match removed {
+            None => (Some(id), None),
+            Some(_) => (Some(self.rebalance(id)), removed),
+        }
+    }
 
-            rem_mut.height =
-                1 + Self::height(rem_mut.left.clone()).max(Self::height(rem_mut.right.clone()));
-            // get balancing conditions
-            let bal = Self::height(rem_mut.left.clone()) - Self::height(rem_mut.right.clone());
-            let rbal = Self::getBalance(rem_mut.right.clone());
-            let lbal = Self::getBalance(rem_mut.left.clone());
-
-            if (bal > 1) && (lbal >= 0) {
-                // ll
-                rcase = RotationCase::LL;
-            } else if (bal < -1) && (rbal <= 0) {
-                // rr
-                rcase = RotationCase::RR;
-            } else if (bal > 1) && (lbal < 0) {
-                rcase = RotationCase::LR;
-            } else if (bal < -1) && (rbal > 0) {
-                rcase = RotationCase::RL;
-            } else {
-                rcase = RotationCase::Nil;
+    // takes the node with the lowest key out of the subtree under id without
+    // freeing it, returns the new root and the node that was taken out
+    fn delete_min(&mut self, id: NodeId) -> (Link, NodeId) {
+        match self.nodes[id].left {
+            None => (self.nodes[id].right.take(), id),
+            Some(left) => {
+                let (left, min) = self.delete_min(left);
+                self.nodes[id].left = left;
+                (Some(self.rebalance(id)), min)
             }
         }
+    }
 
-        let rem_unwrap = rem_node.clone().unwrap();
+    // removes the entry with the smallest key, returns the new root and the entry
+    fn pop_min(&mut self, id: NodeId) -> (Link, (K, V)) {
+        let (root, min) = self.delete_min(id);
+        let n = self.nodes.free(min);
+        (root, (n.key, n.value))
+    }
 
-        // return new root after rotations
-        return match rcase {
-            RotationCase::LL => Self::rightRotate(rem_unwrap),
-            RotationCase::RR => Self::leftRotate(rem_unwrap),
-            RotationCase::LR => {
-                let tmp = Self::leftRotate(rem_unwrap.borrow().left.clone().unwrap());
-                rem_unwrap.borrow_mut().left = tmp;
-                Self::rightRotate(rem_unwrap)
+    // removes the entry with the largest key, returns the new root and the entry
+    fn pop_max(&mut self, id: NodeId) -> (Link, (K, V)) {
+        match self.nodes[id].right {
+            None => {
+                let n = self.nodes.free(id);
+                (n.left, (n.key, n.value))
             }
-            RotationCase::RL => {
-                let tmp = Self::rightRotate(rem_unwrap.borrow().right.clone().unwrap());
-                rem_unwrap.borrow_mut().right = tmp;
-                Self::leftRotate(rem_unwrap)
+            Some(right) => {
+                let (right, entry) = self.pop_max(right);
+                self.nodes[id].right = right;
+                (Some(self.rebalance(id)), entry)
             }
-            RotationCase::Nil => rem_node,
-        };
+        }
     }
 
-    // counts nodes rooted at root
-    pub fn count(root: AVLTree<T>) -> i32 {
-        return match root {
-            None => 0,
-            Some(node) => {
-                let n = node.borrow();
-                return 1 + Self::count(n.left.clone()) + Self::count(n.right.clone());
+    // joins left, a node for key and value, and right into one tree, returns
+    // the new root. every key of left must be smaller than key and every key
+    // of right larger. the node goes down the spine of the taller tree to where
+    // the heights meet, so the cost is the difference in height
+    fn join_nodes(&mut self, left: Link, key: K, value: V, right: Link) -> NodeId {
+        let lh = self.height_of(left);
+        let rh = self.height_of(right);
+
+        if lh > rh + 1 {
+            let l = left.unwrap();
+            let joined = self.join_nodes(self.nodes[l].right, key, value, right);
+            self.nodes[l].right = Some(joined);
+            self.rebalance(l)
+        } else if rh > lh + 1 {
+            let r = right.unwrap();
+            let joined = self.join_nodes(left, key, value, self.nodes[r].left);
+            self.nodes[r].left = Some(joined);
+            self.rebalance(r)
+        } else {
+            let id = self.new_node(key, value);
+            self.nodes[id].left = left;
+            self.nodes[id].right = right;
+            self.update(id);
+            id
+        }
+    }
+
+    // joins two trees where every key of left is smaller than every key of right
+    fn concat_nodes(&mut self, left: Link, right: Link) -> Link {
+        match right {
+            None => left,
+            Some(r) => {
+                let (rest, (key, value)) = self.pop_min(r);
+                Some(self.join_nodes(left, key, value, rest))
             }
+        }
+    }
+
+    // splits the subtree under node into the keys smaller than key and the
+    // rest, joining the pieces on the way back up from key's position
+    fn split_nodes<Q>(&mut self, node: Link, key: &Q) -> (Link, Link)
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let n = match node {
+            None => return (None, None),
+            Some(id) => self.nodes.free(id),
         };
+
+        if key.cmp(n.key.borrow()) == Ordering::Greater {
+            let (lower, upper) = self.split_nodes(n.right, key);
+            (Some(self.join_nodes(n.left, n.key, n.value, lower)), upper)
+        } else {
+            let (lower, upper) = self.split_nodes(n.left, key);
+            (lower, Some(self.join_nodes(upper, n.key, n.value, n.right)))
+        }
     }
 
-    fn count_leaves(&self) -> i32 {
-        // Node has no children which means that it is a leaf
-        if self.left.is_none() && self.right.is_none() {
-            return 1;
+    // moves the subtree under node out of the arena from into this tree's
+    // arena, returns the index of its root here
+    fn adopt(&mut self, from: &mut Arena<AVLNode<K, V, A>>, node: Link) -> Link {
+        let mut n = from.free(node?);
+        n.left = self.adopt(from, n.left);
+        n.right = self.adopt(from, n.right);
+        Some(self.nodes.alloc(n))
+    }
+
+    // moves the subtree under node into a map of its own
+    fn detach(&mut self, node: Link) -> Self {
+        let mut detached = Self {
+            nodes: Arena::with_capacity(self.size_of(node)),
+            root: None,
+        };
+        detached.root = detached.adopt(&mut self.nodes, node);
+        detached
+    }
+
+    // moves every node of other into this tree's arena, returns the root they hang from
+    fn absorb(&mut self, mut other: Self) -> Link {
+        let root = other.root.take();
+        self.adopt(&mut other.nodes, root)
+    }
+
+    // joins the trees left and right with key and value between them. the
+    // nodes of the smaller tree move into the arena of the larger
+    fn join_trees(left: Self, key: K, value: V, right: Self) -> Self {
+        let (mut tree, lower, upper);
+        if left.len() >= right.len() {
+            tree = left;
+            lower = tree.root;
+            upper = tree.absorb(right);
+        } else {
+            tree = right;
+            upper = tree.root;
+            lower = tree.absorb(left);
         }
+        tree.root = Some(tree.join_nodes(lower, key, value, upper));
+        tree
+    }
 
-        let mut leaf_count = 0;
+    // joins two trees where every key of lower is smaller than every key of
+    // upper, the nodes of the smaller tree move into the arena of the larger
+    fn concat_trees(lower: Self, upper: Self) -> Self {
+        let (mut tree, l, u);
+        if lower.len() >= upper.len() {
+            tree = lower;
+            l = tree.root;
+            u = tree.absorb(upper);
+        } else {
+            tree = upper;
+            u = tree.root;
+            l = tree.absorb(lower);
+        }
+        tree.root = tree.concat_nodes(l, u);
+        tree
+    }
 
-        // If the left child isn't none, traverse into it
-        if let Some(ref left) = self.left {
-            leaf_count += left.borrow().count_leaves();
+    // moves the keys from key onwards into a new map. the smaller of the two
+    // parts moves into an arena of its own, the larger keeps this one
+    fn split_off_nodes<Q>(&mut self, key: &Q) -> Self
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let root = self.root.take();
+        let (lower, upper) = self.split_nodes(root, key);
+        if self.size_of(lower) < self.size_of(upper) {
+            let lower = self.detach(lower);
+            self.root = upper;
+            std::mem::replace(self, lower)
+        } else {
+            self.root = lower;
+            self.detach(upper)
         }
+    }
+
+    // builds a balanced tree from entries in strictly ascending key order in O(n)
+    fn from_sorted(entries: Vec<(K, V)>) -> Self {
+        let n = entries.len();
+        let mut map = Self {
+            nodes: Arena::with_capacity(n),
+            root: None,
+        };
+        map.root = map.build_sorted(&mut entries.into_iter(), n);
+        map
+    }
 
-        // If the right child isn't none, traverse into it
-        if let Some(ref right) = self.right {
-            leaf_count += right.borrow().count_leaves();
+    // builds a tree from the next n entries, which must be in strictly ascending
+    // key order. halving the count at every level keeps the two subtrees of
+    // each node within one of each other in height, so no rotations are needed
+    fn build_sorted<I>(&mut self, entries: &mut I, n: usize) -> Link
+    where
+        I: Iterator<Item = (K, V)>,
+    {
+        if n == 0 {
+            return None;
         }
+        let left = self.build_sorted(entries, n / 2);
+        let (key, value) = entries.next().expect("fewer entries than the count given");
+        let right = self.build_sorted(entries, n - n / 2 - 1);
 
-        leaf_count
+        let id = self.new_node(key, value);
+        self.nodes[id].left = left;
+        self.nodes[id].right = right;
+        self.update(id);
+        Some(id)
     }
 
-    // print inorder traversal
-    pub fn inorder(root: AVLTree<T>) {
-        match root {
-            None => {}
-            Some(node) => {
-                let n = node.borrow();
-                Self::inorder(n.left.clone());
-                print!(" {} ", n.key);
-                Self::inorder(n.right.clone());
-            }
+    // writes the header and then every node in pre-order: its tag, height, key and value
+    fn save<W: Write>(&self, header: Header, writer: &mut W) -> Result<(), SnapshotError>
+    where
+        K: Codec,
+        V: Codec,
+    {
+        snapshot::write_header(writer, TreeKind::AVL, header)?;
+        if let Some(root) = self.root {
+            self.save_node(root, writer)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn save_node<W: Write>(&self, id: NodeId, writer: &mut W) -> Result<(), SnapshotError>
+    where
+        K: Codec,
+        V: Codec,
+    {
+        let node = &self.nodes[id];
+        let tag = NodeTag {
+            has_left: node.left.is_some(),
+            has_right: node.right.is_some(),
+            red: false,
+        };
+        tag.write(writer)?;
+        // heights stay far below 256 for any tree that fits in memory
+        (node.height as u8).encode(writer)?;
+        node.key.encode(writer)?;
+        node.value.encode(writer)?;
+        for child in [node.left, node.right].iter().copied().flatten() {
+            self.save_node(child, writer)?;
         }
+        Ok(())
     }
 
-    // computes if empty
-    pub fn isEmpty(root: AVLTree<T>) -> bool {
-        match root {
-            None => true,
-            Some(_) => false,
-        }
-    }
-
-    /**
-        algorithmic idea drawn from https://www.baeldung.com/java-print-binary-tree-diagram
-    */
-    pub fn prettyPrint(root: AVLTree<T>) -> String {
-        return match root {
-            None => "".to_string(),
-            Some(node) => {
-                let rc_sb = Rc::from(RefCell::from(String::from("")));
-                let n = node.borrow();
-                {
-                    let mut sb = rc_sb.borrow_mut();
-                    sb.push_str(n.key.clone().to_string().as_str());
-                }
-
-                let pointerRight = "└──";
-                let pointerLeft;
-                if n.right.is_none() {
-                    pointerLeft = "└──";
-                } else {
-                    pointerLeft = "├──";
-                }
-                // let pointLeft =
-                // Self::prettyPrintHelper(rc_sb.clone(), "", pointerRight, n.right.clone(), false);
-                Self::prettyPrintHelper(
-                    rc_sb.clone(),
-                    "",
-                    pointerLeft,
-                    n.left.clone(),
-                    n.right.is_some(),
-                );
-                Self::prettyPrintHelper(rc_sb.clone(), "", pointerRight, n.right.clone(), false);
-
-                rc_sb.clone().borrow_mut().to_string()
+    // reads back a tree written by save, returns it and its header. the tree
+    // is rebuilt with the exact shape it was saved with, and is rejected unless
+    // every recorded height is right, every node balanced and the keys ascending,
+    // strictly unless the tree keeps duplicates
+    fn load<R: Read>(reader: &mut R) -> Result<(Self, Header), SnapshotError>
+    where
+        K: Ord + Codec,
+        V: Codec,
+    {
+        let header = snapshot::read_header(reader, TreeKind::AVL)?;
+        let len = header.len;
+        let mut remaining = len;
+        let mut map = Self {
+            nodes: Arena::new(),
+            root: None,
+        };
+        if len != 0 {
+            map.root = Some(map.load_node(reader, &mut remaining, 1)?);
+        }
+        if remaining != 0 {
+            return snapshot::corrupt("fewer nodes than the header counts");
+        }
+
+        let mut keys = map.iter().map(|(key, _)| key);
+        let mut prev = keys.next();
+        for key in keys {
+            let ordered = match header.duplicates {
+                DuplicatePolicy::Count => prev.is_none_or(|p| p <= key),
+                _ => prev.is_none_or(|p| p < key),
+            };
+            if !ordered {
+                return snapshot::corrupt("keys are not in ascending order");
             }
+            prev = Some(key);
+        }
+        Ok((map, header))
+    }
+
+    fn load_node<R: Read>(
+        &mut self,
+        reader: &mut R,
+        remaining: &mut usize,
+        depth: usize,
+    ) -> Result<NodeId, SnapshotError>
+    where
+        K: Codec,
+        V: Codec,
+    {
+        if depth > snapshot::MAX_DEPTH {
+            return snapshot::corrupt("tree is deeper than any balanced tree");
+        }
+        if *remaining == 0 {
+            return snapshot::corrupt("more nodes than the header counts");
+        }
+        *remaining -= 1;
+
+        let tag = NodeTag::read(reader)?;
+        if tag.red {
+            return snapshot::corrupt("AVL node has a color");
+        }
+        let height = u8::decode(reader)? as i32;
+        let key = K::decode(reader)?;
+        let value = V::decode(reader)?;
+        let left = match tag.has_left {
+            true => Some(self.load_node(reader, remaining, depth + 1)?),
+            false => None,
+        };
+        let right = match tag.has_right {
+            true => Some(self.load_node(reader, remaining, depth + 1)?),
+            false => None,
+        };
+
+        let balance = self.height_of(left) - self.height_of(right);
+        if balance.abs() > 1 {
+            return snapshot::corrupt("node is out of balance");
+        }
+        let id = self.new_node(key, value);
+        self.nodes[id].left = left;
+        self.nodes[id].right = right;
+        self.update(id);
+        if self.nodes[id].height != height {
+            return snapshot::corrupt("recorded height does not match the tree");
+        }
+        Ok(id)
+    }
+
+    // checks every invariant of the tree with the keys in the order of cmp,
+    // where keys may repeat if duplicates is Count
+    fn validate_with<C: Comparator<K>>(
+        &self,
+        duplicates: DuplicatePolicy,
+        cmp: &C,
+    ) -> Report<'_, K> {
+        let mut problems = Vec::new();
+        let (_, nodes) = self.validate_node(
+            self.root,
+            cmp,
+            duplicates,
+            &mut Vec::new(),
+            None,
+            None,
+            &mut problems,
+        );
+        Report {
+            len: self.len(),
+            nodes,
+            problems,
+        }
+    }
+
+    // checks the subtree under node, whose keys must lie between lower and upper,
+    // and adds every invariant it breaks to problems. returns the actual height
+    // and size of the subtree
+    #[allow(clippy::too_many_arguments)]
+    fn validate_node<'a, C: Comparator<K>>(
+        &'a self,
+        node: Link,
+        cmp: &C,
+        duplicates: DuplicatePolicy,
+        path: &mut Vec<Side>,
+        lower: Option<&'a K>,
+        upper: Option<&'a K>,
+        problems: &mut Vec<Problem<'a, K>>,
+    ) -> (i32, usize) {
+        let n = match node {
+            None => return (0, 0),
+            Some(id) => &self.nodes[id],
         };
+        // the problems of node go in front of those of its subtrees
+        let at = problems.len();
+        path.push(Side::Left);
+        let (left_height, left_size) =
+            self.validate_node(n.left, cmp, duplicates, path, lower, Some(&n.key), problems);
+        path.pop();
+        path.push(Side::Right);
+        let (right_height, right_size) = self.validate_node(
+            n.right,
+            cmp,
+            duplicates,
+            path,
+            Some(&n.key),
+            upper,
+            problems,
+        );
+        path.pop();
+
+        let height = 1 + left_height.max(right_height);
+        let size = 1 + left_size + right_size;
+        let mut found = Vec::new();
+        if !validate::in_bounds(&n.key, lower, upper, duplicates, cmp) {
+            found.push(Violation::OutOfOrder);
+        }
+        if n.height != height {
+            found.push(Violation::WrongHeight {
+                stored: n.height,
+                actual: height,
+            });
+        }
+        if (left_height - right_height).abs() > 1 {
+            found.push(Violation::Unbalanced {
+                balance: left_height - right_height,
+            });
+        }
+        if n.size != size {
+            found.push(Violation::WrongSize {
+                stored: n.size,
+                actual: size,
+            });
+        }
+        let left = n.left.map(|l| &self.nodes[l].aggregate);
+        let right = n.right.map(|r| &self.nodes[r].aggregate);
+        if n.aggregate != augment::summarize(&n.key, &n.value, left, right) {
+            found.push(Violation::WrongAggregate);
+        }
+        problems.splice(
+            at..at,
+            found.into_iter().map(|violation| Problem {
+                key: &n.key,
+                path: path.clone(),
+                violation,
+            }),
+        );
+        (height, size)
     }
 
-    // helpers the pretty printer draw recursively
-    pub fn prettyPrintHelper(
-        rc_sb: Rc<RefCell<String>>,
-        padding: &str,
-        pointer: &str,
-        node: AVLTree<T>,
-        hasRight: bool,
-    ) {
-        match node {
-            None => {}
-            Some(cur) => {
-                let n = cur.borrow();
-                {
-                    let mut sb = rc_sb.borrow_mut();
-                    sb.push_str("\n");
-                    sb.push_str(padding);
-                    sb.push_str(pointer);
-                    sb.push_str(n.key.clone().to_string().as_str());
-                }
-
-                let mut psb = String::from(padding);
-                if hasRight {
-                    psb.push_str("│  ");
-                } else {
-                    psb.push_str("   ");
-                }
-
-                let bothPad = String::from(psb);
-                let pointerRight = "└──";
-                // String pointerLeft = (root.getRight() != null) ? "├──" : "└──";
-                let pointerLeft;
-                if n.right.is_none() {
-                    pointerLeft = "└──";
-                } else {
-                    pointerLeft = "├──";
-                }
-
-                Self::prettyPrintHelper(
-                    rc_sb.clone(),
-                    &*bothPad,
-                    pointerLeft,
-                    n.left.clone(),
-                    n.right.is_some(),
-                );
-                Self::prettyPrintHelper(
-                    rc_sb.clone(),
-                    &*bothPad,
-                    pointerRight,
-                    n.right.clone(),
-                    false,
-                );
+    // takes the tree apart into its entries in ascending order
+    fn into_entries(mut self) -> Vec<(K, V)> {
+        let order: Vec<NodeId> = InOrder::new(self.root_ref(), self.len())
+            .map(|n| n.id)
+            .collect();
+        order
+            .into_iter()
+            .map(|id| {
+                let n = self.nodes.free(id);
+                (n.key, n.value)
+            })
+            .collect()
+    }
+
+    fn count_leaves(&self) -> i32 {
+        let mut leaf_count = 0;
+        let mut stack: Vec<NodeId> = self.root.into_iter().collect();
+        while let Some(id) = stack.pop() {
+            let n = &self.nodes[id];
+            // Node has no children which means that it is a leaf
+            if n.left.is_none() && n.right.is_none() {
+                leaf_count += 1;
             }
+            stack.extend(n.left);
+            stack.extend(n.right);
         }
+        leaf_count
+    }
+
+    // renders the tree sideways, see tree_core::pretty
+    fn pretty_print(&self) -> String
+    where
+        K: Display,
+    {
+        pretty::pretty_print(
+            self.root,
+            |&id| self.nodes[id].key.to_string(),
+            |&id| (self.nodes[id].left, self.nodes[id].right),
+        )
     }
 }
 
 // implementation that abstracts the details of the nodes away from the user
-impl<T: Ord + Copy + Display> AVL<T> {
+impl<T: Ord> AVL<T> {
     pub fn new() -> Self {
-        Self { root: None }
+        Self::default()
     }
 
-    pub fn isEmpty(&mut self) -> bool {
-        // let node = self.root
-        AVLNode::isEmpty(self.root.clone())
+    // empty multiset, a tree that keeps every copy of a key inserted more than once
+    pub fn multiset() -> Self {
+        Self::with_duplicates(DuplicatePolicy::Count)
+    }
+}
+
+// a tree ordered by a comparator instead of Ord, see tree_core::compare
+impl<T, C: Comparator<T>> AVL<T, (), C> {
+    // empty tree ordered by cmp, e.g. AVL::with_comparator(|a: &i32, b: &i32| b.cmp(a))
+    // for a descending one
+    pub fn with_comparator(cmp: C) -> Self {
+        Self::with_duplicates_and_comparator(DuplicatePolicy::default(), cmp)
+    }
+}
+
+// the methods work on any aggregate and comparator, lookups on a tree with a
+// comparator take a &T as the comparator only knows how to order those
+impl<T, A: Aggregate<T, ()>, C: Comparator<T>> AVL<T, A, C> {
+    // empty tree ordered by cmp that handles a key inserted again the way duplicates says
+    pub fn with_duplicates_and_comparator(duplicates: DuplicatePolicy, cmp: C) -> Self {
+        Self {
+            map: AVLMap::default(),
+            duplicates,
+            cmp,
+        }
     }
 
-    pub fn insert(&mut self, data: T) {
-        self.root = AVLNode::insert(self.root.clone(), data);
+    pub fn duplicates(&self) -> DuplicatePolicy {
+        self.duplicates
     }
 
-    pub fn delete(&mut self, data: T) {
-        self.root = AVLNode::delete(self.root.clone(), data);
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    // inserts data, returns whether it was added as a new entry. a key that is
+    // already in the tree is rejected, replaced or added again as the duplicate
+    // policy says, only the last counts as new
+    pub fn insert(&mut self, data: T) -> bool {
+        let (root, displaced) =
+            self.map
+                .insert_entry(self.map.root, data, (), self.duplicates, &self.cmp);
+        self.map.root = Some(root);
+        displaced.is_none()
+    }
+
+    // deletes data, returns the key that was stored or None if data is not in
+    // the tree. takes out one copy of a key stored more than once
+    pub fn delete<Q>(&mut self, data: &Q) -> Option<T>
+    where
+        T: borrow::Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let (root, removed) = self.map.delete_by(self.map.root, data, &self.cmp);
+        self.map.root = root;
+        removed.map(|(key, _)| key)
+    }
+
+    // how many copies of data the tree holds, at most 1 unless it keeps duplicates. O(log n)
+    pub fn count_of<Q>(&self, data: &Q) -> usize
+    where
+        T: borrow::Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        order::count_by(self.map.root_ref(), data, &self.cmp)
+    }
+
+    // deletes one copy of data, same as delete
+    pub fn remove_one<Q>(&mut self, data: &Q) -> Option<T>
+    where
+        T: borrow::Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.delete(data)
+    }
+
+    // deletes every copy of data, returns how many there were
+    pub fn remove_all<Q>(&mut self, data: &Q) -> usize
+    where
+        T: borrow::Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut removed = 0;
+        while self.delete(data).is_some() {
+            removed += 1;
+        }
+        removed
     }
 
     pub fn count(&self) -> i32 {
-        AVLNode::count(self.root.clone())
+        self.map.len() as i32
     }
 
     pub fn count_leaves(&self) -> i32 {
-        match self.root {
-            None => 0,
-            Some(ref root) => root.borrow().count_leaves(),
+        self.map.count_leaves()
+    }
+
+    pub fn height(&self) -> i32 {
+        self.map.height()
+    }
+
+    pub fn inorder(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.iter().cloned().collect()
+    }
+
+    // keys in ascending order, .rev() for descending
+    pub fn iter(&self) -> Keys<InOrder<NodeRef<'_, T, (), A>>> {
+        Keys::new(InOrder::new(self.map.root_ref(), self.map.len()))
+    }
+
+    // each key before the keys of its left and then right subtree
+    pub fn preorder(&self) -> Keys<PreOrder<NodeRef<'_, T, (), A>>> {
+        Keys::new(PreOrder::new(self.map.root_ref(), self.map.len()))
+    }
+
+    // each key after the keys of its left and then right subtree
+    pub fn postorder(&self) -> Keys<PostOrder<NodeRef<'_, T, (), A>>> {
+        Keys::new(PostOrder::new(self.map.root_ref(), self.map.len()))
+    }
+
+    // keys level by level starting at the root
+    pub fn level_order(&self) -> Keys<LevelOrder<NodeRef<'_, T, (), A>>> {
+        Keys::new(LevelOrder::new(self.map.root_ref(), self.map.len()))
+    }
+
+    // checks every invariant of the tree: keys in order, stored heights and sizes
+    // right, balance factors within one and len matching the nodes. each broken
+    // invariant is reported with the key and path of the node that breaks it
+    pub fn validate(&self) -> Report<'_, T> {
+        self.map.validate_with(self.duplicates, &self.cmp)
+    }
+
+    // number of keys smaller than key, whether or not key is in the tree
+    pub fn rank<Q>(&self, key: &Q) -> usize
+    where
+        T: borrow::Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        order::rank_by(self.map.root_ref(), key, &self.cmp)
+    }
+
+    // the key with the k-th smallest key counting from 1, None if k is 0 or past the end
+    pub fn select(&self, k: usize) -> Option<&T> {
+        k.checked_sub(1).and_then(|n| self.nth(n))
+    }
+
+    // the key at position n in ascending order counting from 0, like iter().nth(n)
+    pub fn nth(&self, n: usize) -> Option<&T> {
+        self.map.nth(n).map(|(key, _)| key)
+    }
+
+    // the key in the middle, the lower one of the two middle keys for an even count
+    pub fn median(&self) -> Option<&T> {
+        self.map.median().map(|(key, _)| key)
+    }
+
+    // the key at the p-th percentile by the nearest rank method, p goes from 0 to 100
+    pub fn percentile(&self, p: f64) -> Option<&T> {
+        self.map.percentile(p).map(|(key, _)| key)
+    }
+
+    // aggregate of every key in the tree, None if it is empty
+    pub fn aggregate(&self) -> Option<&A> {
+        self.map.aggregate()
+    }
+
+    pub fn print(&self) -> String
+    where
+        T: Display,
+    {
+        self.map.pretty_print()
+    }
+
+    pub fn search<Q>(&self, data: &Q) -> bool
+    where
+        T: borrow::Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.map.find(data, &self.cmp).is_some()
+    }
+
+    // the smallest key, None if the tree is empty. O(log n)
+    pub fn first(&self) -> Option<&T> {
+        order::first(self.map.root_ref()).map(|n| n.entry().0)
+    }
+
+    // the largest key, None if the tree is empty. O(log n)
+    pub fn last(&self) -> Option<&T> {
+        order::last(self.map.root_ref()).map(|n| n.entry().0)
+    }
+
+    // the largest key less than or equal to key, whether or not key is in the tree
+    pub fn floor<Q>(&self, key: &Q) -> Option<&T>
+    where
+        T: borrow::Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        order::floor_by(self.map.root_ref(), key, &self.cmp).map(|n| n.entry().0)
+    }
+
+    // the smallest key greater than or equal to key, whether or not key is in the tree
+    pub fn ceiling<Q>(&self, key: &Q) -> Option<&T>
+    where
+        T: borrow::Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        order::ceiling_by(self.map.root_ref(), key, &self.cmp).map(|n| n.entry().0)
+    }
+
+    // the largest key strictly less than key, whether or not key is in the tree
+    pub fn predecessor<Q>(&self, key: &Q) -> Option<&T>
+    where
+        T: borrow::Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        order::predecessor_by(self.map.root_ref(), key, &self.cmp).map(|n| n.entry().0)
+    }
+
+    // the smallest key strictly greater than key, whether or not key is in the tree
+    pub fn successor<Q>(&self, key: &Q) -> Option<&T>
+    where
+        T: borrow::Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        order::successor_by(self.map.root_ref(), key, &self.cmp).map(|n| n.entry().0)
+    }
+
+    // deletes the smallest key and returns it, None if the tree is empty. O(log n)
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.map.pop_first().map(|(key, _)| key)
+    }
+
+    // deletes the largest key and returns it, None if the tree is empty. O(log n)
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.map.pop_last().map(|(key, _)| key)
+    }
+}
+
+// the methods that need the keys in their Ord order, an AVL<T, A> is made with AVL::default()
+impl<T: Ord, A: Aggregate<T, ()>> AVL<T, A> {
+    // empty tree that handles a key inserted again the way duplicates says
+    pub fn with_duplicates(duplicates: DuplicatePolicy) -> Self {
+        Self::with_duplicates_and_comparator(duplicates, Natural)
+    }
+
+    // keys with keys inside range in ascending order, .rev() for descending
+    pub fn range<Q, R>(&self, range: R) -> Keys<Range<NodeRef<'_, T, (), A>>>
+    where
+        T: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        Keys::new(Range::new(self.map.root_ref(), range))
+    }
+
+    // number of keys inside range in O(log n), counted from the subtree sizes
+    pub fn count_range<Q, R>(&self, range: R) -> usize
+    where
+        T: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.range(range).len()
+    }
+
+    // removes every key inside range, returns how many were removed
+    pub fn remove_range<Q, R>(&mut self, range: R) -> usize
+    where
+        T: borrow::Borrow<Q> + Clone,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let keys: Vec<T> = self.range(range).cloned().collect();
+        for key in &keys {
+            self.delete::<T>(key);
+        }
+        keys.len()
+    }
+
+    // moves the keys from key onwards into a new tree, keeping the smaller ones.
+    // O(log n) plus moving the smaller of the two parts into an arena of its own
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        T: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Self {
+            map: self.map.split_off_nodes(key),
+            duplicates: self.duplicates,
+            cmp: Natural,
+        }
+    }
+
+    // splits the tree into the keys smaller than key and the rest, see split_off
+    pub fn split<Q>(mut self, key: &Q) -> (Self, Self)
+    where
+        T: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let upper = self.split_off(key);
+        (self, upper)
+    }
+
+    // joins the keys of left, pivot and the keys of right into one tree in
+    // O(log n) plus moving the nodes of the smaller tree into the arena of the
+    // larger. panics unless left < pivot < right for all of their keys
+    pub fn join(left: Self, pivot: T, right: Self) -> Self {
+        assert!(
+            left.iter().next_back().is_none_or(|max| *max < pivot)
+                && right.iter().next().is_none_or(|min| pivot < *min),
+            "join needs every key of left below pivot and every key of right above it"
+        );
+        Self {
+            duplicates: left.duplicates,
+            map: AVLMap::join_trees(left.map, pivot, (), right.map),
+            cmp: Natural,
+        }
+    }
+
+    // moves every key of other into this tree, leaving other empty. takes
+    // O(log n) plus moving the nodes of the smaller tree when all keys of one
+    // tree are smaller than all keys of the other, otherwise the keys of other
+    // are inserted one by one
+    pub fn append(&mut self, other: &mut Self) {
+        let other = std::mem::replace(other, Self::with_duplicates(other.duplicates));
+        let below = match (self.iter().next_back(), other.iter().next()) {
+            (Some(max), Some(min)) => max < min,
+            _ => true,
+        };
+        let above = match (other.iter().next_back(), self.iter().next()) {
+            (Some(max), Some(min)) => max < min,
+            _ => true,
+        };
+
+        if below {
+            let lower = std::mem::take(&mut self.map);
+            self.map = AVLMap::concat_trees(lower, other.map);
+        } else if above {
+            let upper = std::mem::take(&mut self.map);
+            self.map = AVLMap::concat_trees(other.map, upper);
+        } else {
+            for key in other {
+                self.insert(key);
+            }
+        }
+    }
+
+    // keys in self, other or both as a new tree. O(n + m)
+    pub fn union(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        self.merge(other, |item| Some(item.into_left()))
+    }
+
+    // keys in both self and other as a new tree. O(n + m)
+    pub fn intersection(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        self.merge(other, |item| match item {
+            MergeItem::Both(key, _) => Some(key),
+            _ => None,
+        })
+    }
+
+    // keys in self but not in other as a new tree. O(n + m)
+    pub fn difference(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        self.merge(other, |item| match item {
+            MergeItem::Left(key) => Some(key),
+            _ => None,
+        })
+    }
+
+    // keys in exactly one of self and other as a new tree. O(n + m)
+    pub fn symmetric_difference(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        self.merge(other, |item| match item {
+            MergeItem::Left(key) | MergeItem::Right(key) => Some(key),
+            MergeItem::Both(..) => None,
+        })
+    }
+
+    // true if every key of self is in other
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.map.len() <= other.map.len()
+            && Merge::new(self.iter(), other.iter()).all(|item| !matches!(item, MergeItem::Left(_)))
+    }
+
+    // true if every key of other is in self
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    // true if self and other have no key in common
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        Merge::new(self.iter(), other.iter()).all(|item| !matches!(item, MergeItem::Both(..)))
+    }
+
+    // walks both trees in order and builds a balanced tree from the keys that
+    // keep picks, without any rotations
+    fn merge<'a, F>(&'a self, other: &'a Self, keep: F) -> Self
+    where
+        T: Clone,
+        F: FnMut(MergeItem<&'a T>) -> Option<&'a T>,
+    {
+        let mut keys: Vec<(T, ())> = Merge::new(self.iter(), other.iter())
+            .filter_map(keep)
+            .map(|key| (key.clone(), ()))
+            .collect();
+        // a key other holds more than once must not repeat in a tree without duplicates
+        if self.duplicates != DuplicatePolicy::Count {
+            keys.dedup_by(|a, b| a.0 == b.0);
+        }
+        Self::from_entries_with(keys, self.duplicates)
+    }
+
+    // builds a balanced tree from keys in strictly ascending order in O(n),
+    // without any rotations. panics if the keys are out of order or repeat,
+    // collect() accepts keys in any order
+    pub fn from_sorted_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let keys: Vec<(T, ())> = iter.into_iter().map(|key| (key, ())).collect();
+        assert!(
+            build::is_strictly_ascending(&keys),
+            "from_sorted_iter needs keys in strictly ascending order"
+        );
+        Self::from_entries(keys)
+    }
+
+    // writes the tree to writer in the binary snapshot format of
+    // tree_core::snapshot, which keeps the exact shape of the tree
+    pub fn save_to<W: Write>(&self, mut writer: W) -> Result<(), SnapshotError>
+    where
+        T: Codec,
+    {
+        let header = Header {
+            len: self.map.len(),
+            duplicates: self.duplicates,
+        };
+        self.map.save(header, &mut writer)
+    }
+
+    // reads a tree written by save_to, checking that it is a valid AVL tree.
+    // truncated or corrupt input is an error, never a panic
+    pub fn load_from<R: Read>(mut reader: R) -> Result<Self, SnapshotError>
+    where
+        T: Codec,
+    {
+        let (map, header) = AVLMap::load(&mut reader)?;
+        Ok(Self {
+            map,
+            duplicates: header.duplicates,
+            cmp: Natural,
+        })
+    }
+
+    // builds the tree from keys in strictly ascending order
+    pub(crate) fn from_entries(keys: Vec<(T, ())>) -> Self {
+        Self {
+            map: AVLMap::from_sorted(keys),
+            duplicates: DuplicatePolicy::default(),
+            cmp: Natural,
         }
     }
 
+    // same as from_entries for a tree with the given duplicate policy, keys
+    // may repeat under Count
+    pub(crate) fn from_entries_with(keys: Vec<(T, ())>, duplicates: DuplicatePolicy) -> Self {
+        let mut tree = Self::from_entries(keys);
+        tree.duplicates = duplicates;
+        tree
+    }
+
+    // aggregate of the keys inside range, None if there are none
+    pub fn fold_range<Q, R>(&self, range: R) -> Option<A>
+    where
+        T: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.map.fold_range(range)
+    }
+}
+
+impl<T, A, C> Default for AVL<T, A, C>
+where
+    A: Aggregate<T, ()>,
+    C: Comparator<T> + Default,
+{
+    fn default() -> Self {
+        Self::with_duplicates_and_comparator(DuplicatePolicy::default(), C::default())
+    }
+}
+
+// copies the arena so the clone never sees changes made to the original
+impl<T, A, C> Clone for AVL<T, A, C>
+where
+    T: Clone,
+    A: Aggregate<T, ()>,
+    C: Comparator<T> + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            map: AVLMap {
+                nodes: self.map.nodes.clone(),
+                root: self.map.root,
+            },
+            duplicates: self.duplicates,
+            cmp: self.cmp.clone(),
+        }
+    }
+}
+
+impl<T: Ord, A: Aggregate<T, ()>> SortedTree<T> for AVL<T, A> {
+    fn insert(&mut self, key: T) -> bool {
+        AVL::insert(self, key)
+    }
+
+    fn remove<Q>(&mut self, key: &Q) -> Option<T>
+    where
+        T: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.delete(key)
+    }
+
+    fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.search(key)
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn height(&self) -> usize {
+        AVL::height(self) as usize
+    }
+
+    fn leaf_count(&self) -> usize {
+        self.count_leaves() as usize
+    }
+
+    fn is_empty(&self) -> bool {
+        AVL::is_empty(self)
+    }
+
+    fn inorder(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        AVL::inorder(self)
+    }
+
+    fn render(&self) -> String
+    where
+        T: Display,
+    {
+        self.print()
+    }
+}
+
+impl<K: Ord, V> AVLMap<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // only maps without an aggregate hand out &mut to values, changing a value
+    // behind the tree's back would leave the aggregates above it stale
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let id = self.find(key, &Natural)?;
+        Some(&mut self.nodes[id].value)
+    }
+}
+
+// the methods that do not look at the order of the keys
+impl<K, V, A: Aggregate<K, V>> AVLMap<K, V, A> {
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
     pub fn height(&self) -> i32 {
-        AVLNode::height(self.root.clone())
+        self.height_of(self.root)
+    }
+
+    // the entry with the k-th smallest key counting from 1, None if k is 0 or past the end
+    pub fn select(&self, k: usize) -> Option<(&K, &V)> {
+        k.checked_sub(1).and_then(|n| self.nth(n))
+    }
+
+    // the entry at position n in ascending order counting from 0, like iter().nth(n)
+    pub fn nth(&self, n: usize) -> Option<(&K, &V)> {
+        order::nth(self.root_ref(), n).map(NodeHandle::entry)
+    }
+
+    // the entry in the middle, the lower one of the two middle entries for an even count
+    pub fn median(&self) -> Option<(&K, &V)> {
+        self.len()
+            .checked_sub(1)
+            .and_then(|last| self.nth(last / 2))
+    }
+
+    // the entry at the p-th percentile by the nearest rank method, p goes from 0 to 100
+    pub fn percentile(&self, p: f64) -> Option<(&K, &V)> {
+        order::percentile_index(self.len(), p).and_then(|n| self.nth(n))
+    }
+
+    // aggregate of every entry in the map, None if it is empty
+    pub fn aggregate(&self) -> Option<&A> {
+        self.root.map(|root| &self.nodes[root].aggregate)
+    }
+
+    pub fn print(&self) -> String
+    where
+        K: Display,
+    {
+        self.pretty_print()
+    }
+
+    // entries in ascending key order, .rev() for descending
+    pub fn iter(&self) -> Entries<InOrder<NodeRef<'_, K, V, A>>> {
+        Entries::new(InOrder::new(self.root_ref(), self.len()))
+    }
+
+    // each entry before the entries of its left and then right subtree
+    pub fn preorder(&self) -> Entries<PreOrder<NodeRef<'_, K, V, A>>> {
+        Entries::new(PreOrder::new(self.root_ref(), self.len()))
+    }
+
+    // each entry after the entries of its left and then right subtree
+    pub fn postorder(&self) -> Entries<PostOrder<NodeRef<'_, K, V, A>>> {
+        Entries::new(PostOrder::new(self.root_ref(), self.len()))
+    }
+
+    // entries level by level starting at the root
+    pub fn level_order(&self) -> Entries<LevelOrder<NodeRef<'_, K, V, A>>> {
+        Entries::new(LevelOrder::new(self.root_ref(), self.len()))
+    }
+
+    // deletes the entry with the smallest key and returns it, None if the map is empty. O(log n)
+    pub(crate) fn pop_first(&mut self) -> Option<(K, V)> {
+        let (root, entry) = self.pop_min(self.root?);
+        self.root = root;
+        Some(entry)
+    }
+
+    // deletes the entry with the largest key and returns it, None if the map is empty. O(log n)
+    pub(crate) fn pop_last(&mut self) -> Option<(K, V)> {
+        let (root, entry) = self.pop_max(self.root?);
+        self.root = root;
+        Some(entry)
+    }
+}
+
+// the methods work on any aggregate, an AVLMap<K, V, A> is made with AVLMap::default()
+impl<K: Ord, V, A: Aggregate<K, V>> AVLMap<K, V, A> {
+    // inserts key with value, returns the old value if key was already present
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let (root, replaced) =
+            self.insert_entry(self.root, key, value, DuplicatePolicy::Replace, &Natural);
+        self.root = Some(root);
+        replaced.map(|(_, value)| value)
+    }
+
+    // removes key, returns its value if it was present
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (root, removed) = self.delete_by(self.root, key, &Natural);
+        self.root = root;
+        removed.map(|(_, value)| value)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key, &Natural).map(|id| &self.nodes[id].value)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key, &Natural).is_some()
+    }
+
+    // number of keys smaller than key, whether or not key is in the tree
+    pub fn rank<Q>(&self, key: &Q) -> usize
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        order::rank(self.root_ref(), key)
+    }
+
+    // aggregate of the entries with keys inside range, None if there are none
+    pub fn fold_range<Q, R>(&self, range: R) -> Option<A>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        augment::fold_range(self.root_ref(), range)
+    }
+
+    // entries with keys inside range in ascending order, .rev() for descending
+    pub fn range<Q, R>(&self, range: R) -> Entries<Range<NodeRef<'_, K, V, A>>>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        Entries::new(Range::new(self.root_ref(), range))
+    }
+
+    // number of keys inside range in O(log n), counted from the subtree sizes
+    pub fn count_range<Q, R>(&self, range: R) -> usize
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.range(range).len()
+    }
+
+    // removes every key inside range, returns how many were removed
+    pub fn remove_range<Q, R>(&mut self, range: R) -> usize
+    where
+        K: borrow::Borrow<Q> + Clone,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let keys: Vec<K> = self.range(range).map(|(k, _)| k.clone()).collect();
+        for key in &keys {
+            self.remove::<K>(key);
+        }
+        keys.len()
+    }
+
+    // builds a balanced tree from entries in strictly ascending key order in
+    // O(n), without any rotations. panics if the keys are out of order or
+    // repeat, collect() accepts entries in any order
+    pub fn from_sorted_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let entries: Vec<(K, V)> = iter.into_iter().collect();
+        assert!(
+            build::is_strictly_ascending(&entries),
+            "from_sorted_iter needs keys in strictly ascending order"
+        );
+        Self::from_entries(entries)
+    }
+
+    // writes the map to writer in the binary snapshot format of
+    // tree_core::snapshot, which keeps the exact shape of the tree
+    pub fn save_to<W: Write>(&self, mut writer: W) -> Result<(), SnapshotError>
+    where
+        K: Codec,
+        V: Codec,
+    {
+        let header = Header {
+            len: self.len(),
+            duplicates: DuplicatePolicy::Reject,
+        };
+        self.save(header, &mut writer)
+    }
+
+    // reads a map written by save_to, checking that it is a valid AVL tree.
+    // truncated or corrupt input is an error, never a panic
+    pub fn load_from<R: Read>(mut reader: R) -> Result<Self, SnapshotError>
+    where
+        K: Codec,
+        V: Codec,
+    {
+        let (map, header) = Self::load(&mut reader)?;
+        if header.duplicates == DuplicatePolicy::Count {
+            return snapshot::corrupt("a map cannot hold duplicate keys");
+        }
+        Ok(map)
+    }
+
+    // checks every invariant of the map: keys in order, stored heights and sizes
+    // right, balance factors within one and len matching the nodes. each broken
+    // invariant is reported with the key and path of the node that breaks it.
+    // len is the number of nodes in the arena, so a node the root cannot reach
+    // shows up as a difference between the two
+    pub fn validate(&self) -> Report<'_, K> {
+        self.validate_with(DuplicatePolicy::Reject, &Natural)
+    }
+
+    // builds the tree from entries in strictly ascending key order
+    pub(crate) fn from_entries(entries: Vec<(K, V)>) -> Self {
+        Self::from_sorted(entries)
+    }
+
+    // moves the entries with keys from key onwards into a new map, keeping the
+    // smaller ones. O(log n) plus moving the smaller of the two parts into an
+    // arena of its own
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.split_off_nodes(key)
+    }
+
+    // splits the map into the entries with keys smaller than key and the rest, see split_off
+    pub fn split<Q>(mut self, key: &Q) -> (Self, Self)
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let upper = self.split_off(key);
+        (self, upper)
+    }
+
+    // joins the entries of left, key with value and the entries of right into
+    // one map in O(log n) plus moving the nodes of the smaller map into the
+    // arena of the larger. panics unless left < key < right for all of their keys
+    pub fn join(left: Self, key: K, value: V, right: Self) -> Self {
+        assert!(
+            left.iter().next_back().is_none_or(|(max, _)| *max < key)
+                && right.iter().next().is_none_or(|(min, _)| key < *min),
+            "join needs every key of left below key and every key of right above it"
+        );
+        Self::join_trees(left, key, value, right)
+    }
+
+    // moves every entry of other into this map, leaving other empty. values
+    // from other replace those of equal keys. takes O(log n) plus moving the
+    // nodes of the smaller map when all keys of one map are smaller than all
+    // keys of the other, otherwise the entries of other are inserted one by one
+    pub fn append(&mut self, other: &mut Self) {
+        let other = std::mem::take(other);
+        let below = match (self.iter().next_back(), other.iter().next()) {
+            (Some((max, _)), Some((min, _))) => max < min,
+            _ => true,
+        };
+        let above = match (other.iter().next_back(), self.iter().next()) {
+            (Some((max, _)), Some((min, _))) => max < min,
+            _ => true,
+        };
+
+        if below {
+            let lower = std::mem::take(self);
+            *self = Self::concat_trees(lower, other);
+        } else if above {
+            let upper = std::mem::take(self);
+            *self = Self::concat_trees(other, upper);
+        } else {
+            for (key, value) in other {
+                self.insert(key, value);
+            }
+        }
+    }
+}
+
+// copies the arena so the clone never sees changes made to the original
+impl<K: Ord + Clone, V: Clone, A: Aggregate<K, V>> Clone for AVLMap<K, V, A> {
+    fn clone(&self) -> Self {
+        Self {
+            nodes: self.nodes.clone(),
+            root: self.root,
+        }
+    }
+}
+
+impl<K, V, A: Aggregate<K, V>> Default for AVLMap<K, V, A> {
+    fn default() -> Self {
+        Self {
+            nodes: Arena::new(),
+            root: None,
+        }
+    }
+}
+
+impl<'a, K, V, A> Clone for NodeRef<'a, K, V, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, K, V, A> Copy for NodeRef<'a, K, V, A> {}
+
+// lets the shared traversals walk the nodes of the arena
+impl<'a, K, V, A> NodeHandle for NodeRef<'a, K, V, A> {
+    type Key = &'a K;
+    type Value = &'a V;
+
+    fn left(self) -> Option<Self> {
+        self.nodes[self.id].left.map(|id| NodeRef { id, ..self })
+    }
+
+    fn right(self) -> Option<Self> {
+        self.nodes[self.id].right.map(|id| NodeRef { id, ..self })
+    }
+
+    fn entry(self) -> (&'a K, &'a V) {
+        let n = &self.nodes[self.id];
+        (&n.key, &n.value)
+    }
+
+    fn size(self) -> usize {
+        self.nodes[self.id].size
+    }
+}
+
+impl<'a, K, V, A> Augmented for NodeRef<'a, K, V, A> {
+    type Aggregate = &'a A;
+
+    fn aggregate(self) -> &'a A {
+        &self.nodes[self.id].aggregate
+    }
+}
+
+impl<'a, T, A: Aggregate<T, ()>, C: Comparator<T>> IntoIterator for &'a AVL<T, A, C> {
+    type Item = &'a T;
+    type IntoIter = Keys<InOrder<NodeRef<'a, T, (), A>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, A: Aggregate<T, ()>, C> IntoIterator for AVL<T, A, C> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    // moves the keys out in ascending order
+    fn into_iter(self) -> Self::IntoIter {
+        let keys: Vec<T> = self
+            .map
+            .into_entries()
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        keys.into_iter()
     }
+}
+
+// sorts and dedupes the keys unless they already ascend, then builds the tree in O(n)
+impl<T: Ord, A: Aggregate<T, ()>> FromIterator<T> for AVL<T, A> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut keys: Vec<(T, ())> = iter.into_iter().map(|key| (key, ())).collect();
+        build::sort_dedup(&mut keys);
+        Self::from_entries(keys)
+    }
+}
+
+// large batches are merged with the keys of the tree and rebuilt in one
+// O(n + m) pass, small ones are inserted one by one. either way equal keys
+// follow the duplicate policy the same as insert
+impl<T: Ord, A: Aggregate<T, ()>> Extend<T> for AVL<T, A> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut keys: Vec<(T, ())> = iter.into_iter().map(|key| (key, ())).collect();
+        if !build::should_rebuild(self.map.len(), keys.len()) {
+            for (key, _) in keys {
+                self.insert(key);
+            }
+            return;
+        }
+        build::sort_stable(&mut keys);
+        let old = std::mem::take(&mut self.map).into_entries();
+        let duplicates = self.duplicates;
+        *self = Self::from_entries_with(build::merge_entries(old, keys, duplicates), duplicates);
+    }
+}
+
+impl<'a, K: Ord, V, A: Aggregate<K, V>> IntoIterator for &'a AVLMap<K, V, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Entries<InOrder<NodeRef<'a, K, V, A>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Ord, V, A: Aggregate<K, V>> IntoIterator for AVLMap<K, V, A> {
+    type Item = (K, V);
+    type IntoIter = std::vec::IntoIter<(K, V)>;
+
+    // moves the entries out in ascending key order
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_entries().into_iter()
+    }
+}
+
+// sorts the entries unless their keys already ascend, the last entry of a key
+// wins like with insert, then builds the tree in O(n)
+impl<K: Ord, V, A: Aggregate<K, V>> FromIterator<(K, V)> for AVLMap<K, V, A> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut entries: Vec<(K, V)> = iter.into_iter().collect();
+        build::sort_dedup(&mut entries);
+        Self::from_entries(entries)
+    }
+}
+
+// large batches are merged with the entries of the tree and rebuilt in one
+// O(n + m) pass, small ones are inserted one by one. values from the batch
+// replace those of equal keys
+impl<K: Ord, V, A: Aggregate<K, V>> Extend<(K, V)> for AVLMap<K, V, A> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let mut entries: Vec<(K, V)> = iter.into_iter().collect();
+        if !build::should_rebuild(self.len(), entries.len()) {
+            for (key, value) in entries {
+                self.insert(key, value);
+            }
+            return;
+        }
+        build::sort_stable(&mut entries);
+        let old = std::mem::take(self).into_entries();
+        // insert replaces the whole entry of a key that is there already
+        let entries = build::merge_entries(old, entries, DuplicatePolicy::Replace);
+        *self = Self::from_entries(entries);
+    }
+}
+
+// the validator on trees that break exactly one invariant. a test builds a
+// healthy tree, corrupts one field of one node and checks that validate()
+// reports that node, its path and the broken rule and nothing else
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // builds a node over the given children with height, size and aggregate right
+    fn node(map: &mut AVLMap<i32, ()>, key: i32, left: Link, right: Link) -> Link {
+        let id = map.new_node(key, ());
+        map.nodes[id].left = left;
+        map.nodes[id].right = right;
+        map.update(id);
+        Some(id)
+    }
+
+    // the node reached by following path down from the root
+    fn at<A>(map: &AVLMap<i32, (), A>, path: &[Side]) -> NodeId {
+        path.iter().fold(map.root.unwrap(), |id, side| {
+            match side {
+                Side::Left => map.nodes[id].left,
+                Side::Right => map.nodes[id].right,
+            }
+            .unwrap()
+        })
+    }
+
+    // keys 1 to 7 as a perfect tree with 4 at the root
+    fn perfect() -> AVLMap<i32, ()> {
+        let map = AVLMap::from_sorted_iter((1..=7).map(|key| (key, ())));
+        assert!(map.validate().is_valid());
+        map
+    }
+
+    fn problem(key: &i32, path: Vec<Side>, violation: Violation) -> Problem<'_, i32> {
+        Problem {
+            key,
+            path,
+            violation,
+        }
+    }
+
+    fn assert_only(map: &AVLMap<i32, ()>, expected: Vec<Problem<'_, i32>>) {
+        let report = map.validate();
+        assert_eq!(report.len, report.nodes);
+        assert_eq!(report.problems, expected);
+    }
+
+    #[test]
+    fn reports_a_stale_height() {
+        let mut map = perfect();
+        let id = at(&map, &[Side::Left]);
+        map.nodes[id].height = 5;
+        assert_only(
+            &map,
+            vec![problem(
+                &2,
+                vec![Side::Left],
+                Violation::WrongHeight {
+                    stored: 5,
+                    actual: 2,
+                },
+            )],
+        );
+    }
+
+    #[test]
+    fn reports_a_node_leaning_right_by_two() {
+        let mut map = AVLMap::default();
+        let four = node(&mut map, 4, None, None);
+        let three = node(&mut map, 3, None, four);
+        map.root = node(&mut map, 2, None, three);
+        assert_only(
+            &map,
+            vec![problem(&2, vec![], Violation::Unbalanced { balance: -2 })],
+        );
+    }
+
+    #[test]
+    fn reports_a_node_leaning_left_by_two() {
+        let mut map = AVLMap::default();
+        let one = node(&mut map, 1, None, None);
+        let two = node(&mut map, 2, one, None);
+        map.root = node(&mut map, 3, two, None);
+        assert_only(
+            &map,
+            vec![problem(&3, vec![], Violation::Unbalanced { balance: 2 })],
+        );
+    }
+
+    #[test]
+    fn reports_a_wrong_size() {
+        let mut map = perfect();
+        let id = at(&map, &[Side::Right]);
+        map.nodes[id].size = 9;
+        assert_only(
+            &map,
+            vec![problem(
+                &6,
+                vec![Side::Right],
+                Violation::WrongSize {
+                    stored: 9,
+                    actual: 3,
+                },
+            )],
+        );
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Sum(i64);
+
+    impl Aggregate<i32, ()> for Sum {
+        fn from_entry(key: &i32, _: &()) -> Self {
+            Sum(*key as i64)
+        }
+
+        fn combine(&self, right: &Self) -> Self {
+            Sum(self.0 + right.0)
+        }
+    }
+
+    #[test]
+    fn reports_a_stale_aggregate() {
+        let mut map: AVLMap<i32, (), Sum> = AVLMap::from_sorted_iter((1..=7).map(|key| (key, ())));
+        assert!(map.validate().is_valid());
+        // a node is checked against the aggregates stored on its children, so a
+        // stale one below the root would show up on its parent as well
+        let root = map.root.unwrap();
+        map.nodes[root].aggregate = Sum(27);
+        let report = map.validate();
+        assert_eq!(
+            report.problems,
+            vec![problem(&4, vec![], Violation::WrongAggregate)]
+        );
+    }
+
+    #[test]
+    fn reports_swapped_keys() {
+        let mut map = perfect();
+        let parent = at(&map, &[Side::Left]);
+        let child = at(&map, &[Side::Left, Side::Left]);
+        map.nodes[parent].key = 1;
+        map.nodes[child].key = 2;
+        // 1 still lies between the bounds of its position, 2 is above its parent
+        assert_only(
+            &map,
+            vec![problem(
+                &2,
+                vec![Side::Left, Side::Left],
+                Violation::OutOfOrder,
+            )],
+        );
+    }
+
+    #[test]
+    fn reports_a_repeated_key_unless_the_tree_counts_duplicates() {
+        let mut tree: AVL<i32> = AVL::from_sorted_iter(1..=7);
+        let id = tree.map.root.unwrap();
+        let right = tree.map.nodes[id].right.unwrap();
+        let leaf = tree.map.nodes[right].left.unwrap();
+        tree.map.nodes[leaf].key = 4;
+        assert_eq!(
+            tree.validate().problems,
+            vec![problem(
+                &4,
+                vec![Side::Right, Side::Left],
+                Violation::OutOfOrder,
+            )]
+        );
 
-    pub fn inorder(&self) {
-        AVLNode::inorder(self.root.clone());
-        println!();
+        tree.duplicates = DuplicatePolicy::Count;
+        assert!(tree.validate().is_valid());
     }
 
-    pub fn print(&self) -> String {
-        AVLNode::prettyPrint(self.root.clone())
+    #[test]
+    fn reports_every_broken_rule_of_a_node_in_order() {
+        let mut map = perfect();
+        let id = at(&map, &[Side::Right, Side::Right]);
+        map.nodes[id].key = 0;
+        map.nodes[id].height = 3;
+        map.nodes[id].size = 2;
+        assert_only(
+            &map,
+            vec![
+                problem(&0, vec![Side::Right, Side::Right], Violation::OutOfOrder),
+                problem(
+                    &0,
+                    vec![Side::Right, Side::Right],
+                    Violation::WrongHeight {
+                        stored: 3,
+                        actual: 1,
+                    },
+                ),
+                problem(
+                    &0,
+                    vec![Side::Right, Side::Right],
+                    Violation::WrongSize {
+                        stored: 2,
+                        actual: 1,
+                    },
+                ),
+            ],
+        );
     }
 
-    pub fn search(&self, data: T) -> bool {
-        AVLNode::search(self.root.clone(), data)
+    #[test]
+    fn reports_nodes_the_root_cannot_reach() {
+        let mut map = perfect();
+        let id = at(&map, &[Side::Left]);
+        map.nodes[id].left = None;
+        let report = map.validate();
+        assert_eq!((report.len, report.nodes), (7, 6));
+        assert!(!report.is_valid());
     }
 }
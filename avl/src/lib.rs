@@ -0,0 +1,8 @@
+mod avl;
+mod persistent;
+#[cfg(feature = "serde")]
+mod serde_impl;
+
+pub use crate::avl::{AVLMap, AVL};
+pub use crate::persistent::{PersistentAVL, PersistentAVLMap};
+pub use tree_core::{Aggregate, DuplicatePolicy};
@@ -1,23 +1,40 @@
+use avl::AVL;
 use criterion::{criterion_group, criterion_main, Criterion};
+use std::ops::Range;
+use tree_core::SortedTree;
 
-mod avl;
-
-fn create_tree(size: i32) {
-	let mut tree = avl::AVL::new();
+fn create_tree<S: SortedTree<i32>>(size: i32) {
+	let mut tree = S::default();
 
 	for i in 0..size {
 		tree.insert(i);
 	}
 
 	for i in 0..size / 10 {
-		tree.search(i);
+		tree.contains(&i);
+	}
+}
+
+fn build_tree<S: SortedTree<i32>>(size: i32, build: fn(Range<i32>) -> S) {
+	let tree = build(0..size);
+
+	for i in 0..size / 10 {
+		tree.contains(&i);
 	}
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
 	let tree_sizes = vec![10000, 40000, 70000, 100000, 130000];
 	for size in tree_sizes {
-		c.bench_function("your function: ", |b| b.iter(|| create_tree(size)));
+		c.bench_function("your function: ", |b| {
+			b.iter(|| create_tree::<AVL<i32>>(size))
+		});
+		c.bench_function("from_sorted_iter: ", |b| {
+			b.iter(|| build_tree::<AVL<i32>>(size, AVL::from_sorted_iter))
+		});
+		c.bench_function("collect: ", |b| {
+			b.iter(|| build_tree::<AVL<i32>>(size, Iterator::collect))
+		});
 	}
 }
 
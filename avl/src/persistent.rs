@@ -0,0 +1,524 @@
+use std::borrow;
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::ops::RangeBounds;
+use std::rc::Rc;
+use tree_core::traverse::{Entries, InOrder, Keys, NodeHandle, Range};
+use tree_core::validate::{self, Problem, Report, Side, Violation};
+use tree_core::{pretty, DuplicatePolicy, Natural};
+
+type Link<K, V> = Option<Rc<Node<K, V>>>;
+
+// a node that never changes once built. updates build new nodes along the path
+// they touch and link them to the untouched subtrees of the old version
+#[derive(Debug)]
+pub struct Node<K, V> {
+    key: K,
+    value: V,
+    height: i32,
+    // number of nodes in the subtree rooted here
+    size: usize,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+// persistent avl tree mapping keys to values. insert and remove leave the tree
+// as it is and return a new version that shares every subtree they did not
+// touch with it, so each update copies O(log n) nodes, every older version
+// stays valid and clone is O(1)
+#[derive(Debug)]
+pub struct PersistentAVLMap<K, V> {
+    root: Link<K, V>,
+}
+
+// persistent avl tree of keys, backed by a PersistentAVLMap without values
+#[derive(Debug)]
+pub struct PersistentAVL<T> {
+    map: PersistentAVLMap<T, ()>,
+}
+
+impl<K: Ord + Clone, V: Clone> PersistentAVLMap<K, V> {
+    pub fn new() -> Self {
+        Self { root: None }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    // a new version with key mapped to value, replacing the value of key if it
+    // is already there
+    pub fn insert(&self, key: K, value: V) -> Self {
+        Self {
+            root: Some(insert(&self.root, key, value)),
+        }
+    }
+
+    // a new version without key. when key is not in the tree the new version
+    // shares the whole tree with this one
+    pub fn remove<Q>(&self, key: &Q) -> Self
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if !self.contains_key(key) {
+            return self.clone();
+        }
+        Self {
+            root: remove(&self.root, key),
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get_entry(key).is_some()
+    }
+
+    fn get_entry<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut node = self.root.as_ref();
+        while let Some(n) = node {
+            node = match key.cmp(n.key.borrow()) {
+                Ordering::Less => n.left.as_ref(),
+                Ordering::Greater => n.right.as_ref(),
+                Ordering::Equal => return Some((&n.key, &n.value)),
+            };
+        }
+        None
+    }
+
+    pub fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    pub fn height(&self) -> i32 {
+        height(&self.root)
+    }
+
+    // entries in ascending key order, .rev() for descending
+    pub fn iter(&self) -> Entries<InOrder<&Node<K, V>>> {
+        Entries::new(InOrder::new(self.root.as_deref(), self.len()))
+    }
+
+    // entries with keys inside range in ascending order
+    pub fn range<Q, R>(&self, range: R) -> Entries<Range<&Node<K, V>>>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        Entries::new(Range::new(self.root.as_deref(), range))
+    }
+
+    // true if both versions are the same tree, sharing the same root
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.root, &other.root) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    // checks every invariant of this version the same as AVLMap::validate
+    pub fn validate(&self) -> Report<'_, K> {
+        let mut problems = Vec::new();
+        let (_, nodes) = validate_node(&self.root, &mut Vec::new(), None, None, &mut problems);
+        Report {
+            len: self.len(),
+            nodes,
+            problems,
+        }
+    }
+
+    pub fn print(&self) -> String
+    where
+        K: Display,
+    {
+        pretty::pretty_print(
+            self.root.as_deref(),
+            |node| node.key.to_string(),
+            |node| (node.left.as_deref(), node.right.as_deref()),
+        )
+    }
+}
+
+// shares the root, so cloning is O(1) and never copies a node
+impl<K, V> Clone for PersistentAVLMap<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Default for PersistentAVLMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + Clone> PersistentAVL<T> {
+    pub fn new() -> Self {
+        Self {
+            map: PersistentAVLMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    // a new version with data in it
+    pub fn insert(&self, data: T) -> Self {
+        Self {
+            map: self.map.insert(data, ()),
+        }
+    }
+
+    // a new version without data
+    pub fn delete<Q>(&self, data: &Q) -> Self
+    where
+        T: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Self {
+            map: self.map.remove(data),
+        }
+    }
+
+    pub fn search<Q>(&self, data: &Q) -> bool
+    where
+        T: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.map.contains_key(data)
+    }
+
+    pub fn count(&self) -> i32 {
+        self.map.len() as i32
+    }
+
+    pub fn height(&self) -> i32 {
+        self.map.height()
+    }
+
+    // keys in ascending order, .rev() for descending
+    pub fn iter(&self) -> Keys<InOrder<&Node<T, ()>>> {
+        Keys::new(InOrder::new(self.map.root.as_deref(), self.map.len()))
+    }
+
+    // keys inside range in ascending order
+    pub fn range<Q, R>(&self, range: R) -> Keys<Range<&Node<T, ()>>>
+    where
+        T: borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        Keys::new(Range::new(self.map.root.as_deref(), range))
+    }
+
+    // true if both versions are the same tree, sharing the same root
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.map.ptr_eq(&other.map)
+    }
+
+    // checks every invariant of this version, see PersistentAVLMap::validate
+    pub fn validate(&self) -> Report<'_, T> {
+        self.map.validate()
+    }
+
+    pub fn print(&self) -> String
+    where
+        T: Display,
+    {
+        self.map.print()
+    }
+}
+
+impl<T> Clone for PersistentAVL<T> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<T: Ord + Clone> Default for PersistentAVL<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, K: Ord + Clone, V: Clone> IntoIterator for &'a PersistentAVLMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Entries<InOrder<&'a Node<K, V>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T: Ord + Clone> IntoIterator for &'a PersistentAVL<T> {
+    type Item = &'a T;
+    type IntoIter = Keys<InOrder<&'a Node<T, ()>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V> NodeHandle for &'a Node<K, V> {
+    type Key = &'a K;
+    type Value = &'a V;
+
+    fn left(self) -> Option<Self> {
+        self.left.as_deref()
+    }
+
+    fn right(self) -> Option<Self> {
+        self.right.as_deref()
+    }
+
+    fn entry(self) -> (&'a K, &'a V) {
+        (&self.key, &self.value)
+    }
+
+    fn size(self) -> usize {
+        self.size
+    }
+}
+
+// height of a subtree, 0 for empty ones
+fn height<K, V>(link: &Link<K, V>) -> i32 {
+    link.as_ref().map_or(0, |n| n.height)
+}
+
+// number of nodes in a subtree, 0 for empty ones
+fn size<K, V>(link: &Link<K, V>) -> usize {
+    link.as_ref().map_or(0, |n| n.size)
+}
+
+// builds a new node over two existing subtrees
+fn node<K, V>(left: Link<K, V>, key: K, value: V, right: Link<K, V>) -> Rc<Node<K, V>> {
+    Rc::new(Node {
+        height: 1 + height(&left).max(height(&right)),
+        size: 1 + size(&left) + size(&right),
+        key,
+        value,
+        left,
+        right,
+    })
+}
+
+// builds a node over left and right, whose heights differ by at most two, and
+// rotates it back into balance. the rotations copy the nodes they move instead
+// of relinking them, since older versions still point at them
+fn balance<K: Clone, V: Clone>(
+    left: Link<K, V>,
+    key: K,
+    value: V,
+    right: Link<K, V>,
+) -> Rc<Node<K, V>> {
+    let (lh, rh) = (height(&left), height(&right));
+    if lh > rh + 1 {
+        let l = left.expect("the taller side is never empty");
+        if height(&l.left) >= height(&l.right) {
+            // LL
+            node(
+                l.left.clone(),
+                l.key.clone(),
+                l.value.clone(),
+                Some(node(l.right.clone(), key, value, right)),
+            )
+        } else {
+            // LR
+            let lr = l.right.as_ref().expect("the taller side is never empty");
+            node(
+                Some(node(
+                    l.left.clone(),
+                    l.key.clone(),
+                    l.value.clone(),
+                    lr.left.clone(),
+                )),
+                lr.key.clone(),
+                lr.value.clone(),
+                Some(node(lr.right.clone(), key, value, right)),
+            )
+        }
+    } else if rh > lh + 1 {
+        let r = right.expect("the taller side is never empty");
+        if height(&r.right) >= height(&r.left) {
+            // RR
+            node(
+                Some(node(left, key, value, r.left.clone())),
+                r.key.clone(),
+                r.value.clone(),
+                r.right.clone(),
+            )
+        } else {
+            // RL
+            let rl = r.left.as_ref().expect("the taller side is never empty");
+            node(
+                Some(node(left, key, value, rl.left.clone())),
+                rl.key.clone(),
+                rl.value.clone(),
+                Some(node(
+                    rl.right.clone(),
+                    r.key.clone(),
+                    r.value.clone(),
+                    r.right.clone(),
+                )),
+            )
+        }
+    } else {
+        node(left, key, value, right)
+    }
+}
+
+// copies the path down to key, adding a node at its end or replacing the value
+// of key, and rebalances every copy on the way back up
+fn insert<K: Ord + Clone, V: Clone>(link: &Link<K, V>, key: K, value: V) -> Rc<Node<K, V>> {
+    let n = match link {
+        None => return node(None, key, value, None),
+        Some(n) => n,
+    };
+    match key.cmp(&n.key) {
+        Ordering::Less => balance(
+            Some(insert(&n.left, key, value)),
+            n.key.clone(),
+            n.value.clone(),
+            n.right.clone(),
+        ),
+        Ordering::Greater => balance(
+            n.left.clone(),
+            n.key.clone(),
+            n.value.clone(),
+            Some(insert(&n.right, key, value)),
+        ),
+        Ordering::Equal => node(n.left.clone(), key, value, n.right.clone()),
+    }
+}
+
+// copies the path down to key and leaves it out, a node with two children is
+// replaced by the smallest entry of its right subtree
+fn remove<K, V, Q>(link: &Link<K, V>, key: &Q) -> Link<K, V>
+where
+    K: Ord + Clone + borrow::Borrow<Q>,
+    V: Clone,
+    Q: Ord + ?Sized,
+{
+    let n = link.as_ref()?;
+    match key.cmp(n.key.borrow()) {
+        Ordering::Less => Some(balance(
+            remove(&n.left, key),
+            n.key.clone(),
+            n.value.clone(),
+            n.right.clone(),
+        )),
+        Ordering::Greater => Some(balance(
+            n.left.clone(),
+            n.key.clone(),
+            n.value.clone(),
+            remove(&n.right, key),
+        )),
+        Ordering::Equal => match (&n.left, &n.right) {
+            (None, _) => n.right.clone(),
+            (_, None) => n.left.clone(),
+            (_, Some(right)) => {
+                let (rest, (k, v)) = remove_min(right);
+                Some(balance(n.left.clone(), k, v, rest))
+            }
+        },
+    }
+}
+
+// copies the left spine of node without its smallest entry, returns the new
+// subtree and a copy of that entry
+fn remove_min<K: Clone, V: Clone>(n: &Rc<Node<K, V>>) -> (Link<K, V>, (K, V)) {
+    match n.left {
+        None => (n.right.clone(), (n.key.clone(), n.value.clone())),
+        Some(ref left) => {
+            let (rest, entry) = remove_min(left);
+            (
+                Some(balance(
+                    rest,
+                    n.key.clone(),
+                    n.value.clone(),
+                    n.right.clone(),
+                )),
+                entry,
+            )
+        }
+    }
+}
+
+// checks the subtree under link, whose keys must lie between lower and upper,
+// and adds every invariant it breaks to problems. returns the actual height and
+// size of the subtree
+fn validate_node<'a, K: Ord, V>(
+    link: &'a Link<K, V>,
+    path: &mut Vec<Side>,
+    lower: Option<&'a K>,
+    upper: Option<&'a K>,
+    problems: &mut Vec<Problem<'a, K>>,
+) -> (i32, usize) {
+    let n = match link {
+        None => return (0, 0),
+        Some(n) => n,
+    };
+    // the problems of node go in front of those of its subtrees
+    let at = problems.len();
+    path.push(Side::Left);
+    let (left_height, left_size) = validate_node(&n.left, path, lower, Some(&n.key), problems);
+    path.pop();
+    path.push(Side::Right);
+    let (right_height, right_size) = validate_node(&n.right, path, Some(&n.key), upper, problems);
+    path.pop();
+
+    let height = 1 + left_height.max(right_height);
+    let size = 1 + left_size + right_size;
+    let mut found = Vec::new();
+    if !validate::in_bounds(&n.key, lower, upper, DuplicatePolicy::Reject, &Natural) {
+        found.push(Violation::OutOfOrder);
+    }
+    if n.height != height {
+        found.push(Violation::WrongHeight {
+            stored: n.height,
+            actual: height,
+        });
+    }
+    if (left_height - right_height).abs() > 1 {
+        found.push(Violation::Unbalanced {
+            balance: left_height - right_height,
+        });
+    }
+    if n.size != size {
+        found.push(Violation::WrongSize {
+            stored: n.size,
+            actual: size,
+        });
+    }
+    problems.splice(
+        at..at,
+        found.into_iter().map(|violation| Problem {
+            key: &n.key,
+            path: path.clone(),
+            violation,
+        }),
+    );
+    (height, size)
+}
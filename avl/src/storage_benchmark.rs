@@ -0,0 +1,79 @@
+use avl::AVL;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tree_core::SortedTree;
+
+// counts the bytes currently allocated so the memory use of the arena can be reported
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const TREE_SIZES: [i32; 3] = [10000, 70000, 130000];
+
+fn insert_lookup_remove<S: SortedTree<i32>>(size: i32) {
+    let mut tree = S::default();
+
+    for i in 0..size {
+        tree.insert(i);
+    }
+
+    for i in 0..size {
+        tree.contains(&i);
+    }
+
+    for i in 0..size {
+        tree.remove(&i);
+    }
+}
+
+fn bytes_used<S: SortedTree<i32>>(size: i32) -> usize {
+    let before = ALLOCATED.load(Ordering::SeqCst);
+    let mut tree = S::default();
+    for i in 0..size {
+        tree.insert(i);
+    }
+    let used = ALLOCATED.load(Ordering::SeqCst) - before;
+    drop(tree);
+    used
+}
+
+fn throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("avl storage");
+    for &size in TREE_SIZES.iter() {
+        group.bench_with_input(BenchmarkId::new("arena", size), &size, |b, &size| {
+            b.iter(|| insert_lookup_remove::<AVL<i32>>(size))
+        });
+    }
+    group.finish();
+}
+
+fn memory(_: &mut Criterion) {
+    for &size in TREE_SIZES.iter() {
+        let arena = bytes_used::<AVL<i32>>(size);
+        println!(
+            "avl storage/memory/{}: arena {} bytes ({} per key)",
+            size,
+            arena,
+            arena / size as usize
+        );
+    }
+}
+
+criterion_group!(benches, throughput, memory);
+criterion_main!(benches);
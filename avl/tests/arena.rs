@@ -0,0 +1,185 @@
+// model-based tests for the arena storage: random operation sequences are
+// applied to an AVL or AVLMap and to a BTreeSet or BTreeMap side by side, and
+// after every step the two must agree and the tree must pass validate().
+// deletes put slots on the free list that later inserts reuse, and validate()
+// fails if a slot is lost or still holds an unlinked node
+
+use avl::{AVLMap, AVL};
+use proptest::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(i32),
+    Delete(i32),
+    Search(i32),
+    /// Removes the keys from the first up to but not including the second
+    RemoveRange(i32, i32),
+}
+
+fn op(keys: std::ops::Range<i32>) -> impl Strategy<Value = Op> {
+    prop_oneof![
+        6 => keys.clone().prop_map(Op::Insert),
+        4 => keys.clone().prop_map(Op::Delete),
+        2 => keys.clone().prop_map(Op::Search),
+        1 => (keys, 0..8).prop_map(|(key, width)| Op::RemoveRange(key, key + width)),
+    ]
+}
+
+fn max_height(n: usize) -> f64 {
+    1.4405 * ((n + 2) as f64).log2()
+}
+
+fn run_set(ops: &[Op]) -> Result<(), TestCaseError> {
+    let mut tree = AVL::new();
+    let mut model = BTreeSet::new();
+    for (step, op) in ops.iter().enumerate() {
+        match *op {
+            Op::Insert(key) => {
+                prop_assert_eq!(
+                    tree.insert(key),
+                    model.insert(key),
+                    "step {}: {:?}",
+                    step,
+                    op
+                );
+            }
+            Op::Delete(key) => {
+                prop_assert_eq!(
+                    tree.delete(&key),
+                    model.take(&key),
+                    "step {}: {:?}",
+                    step,
+                    op
+                );
+            }
+            Op::Search(key) => {
+                prop_assert_eq!(
+                    tree.search(&key),
+                    model.contains(&key),
+                    "step {}: {:?}",
+                    step,
+                    op
+                );
+                prop_assert_eq!(tree.rank(&key), model.range(..key).count());
+                let n = key.rem_euclid(model.len() as i32 + 1) as usize;
+                prop_assert_eq!(tree.nth(n), model.iter().nth(n));
+                prop_assert!(tree.range(key..key + 8).eq(model.range(key..key + 8)));
+                prop_assert_eq!(
+                    tree.count_range(key..key + 8),
+                    model.range(key..key + 8).count()
+                );
+            }
+            Op::RemoveRange(low, high) => {
+                let removed = model.range(low..high).count();
+                model.retain(|key| !(low..high).contains(key));
+                prop_assert_eq!(
+                    tree.remove_range(low..high),
+                    removed,
+                    "step {}: {:?}",
+                    step,
+                    op
+                );
+            }
+        }
+        let report = tree.validate();
+        prop_assert!(report.is_valid(), "step {}: {:?}\n{}", step, op, report);
+        prop_assert!(
+            tree.iter().eq(model.iter()),
+            "step {}: {:?}\n{}",
+            step,
+            op,
+            tree.print()
+        );
+        prop_assert_eq!(tree.count() as usize, model.len());
+        prop_assert!(f64::from(tree.height()) <= max_height(model.len()));
+    }
+    Ok(())
+}
+
+// values are the step that wrote them, so a stale value shows up
+fn run_map(ops: &[Op]) -> Result<(), TestCaseError> {
+    let mut map = AVLMap::new();
+    let mut model = BTreeMap::new();
+    for (step, op) in ops.iter().enumerate() {
+        match *op {
+            Op::Insert(key) => {
+                prop_assert_eq!(
+                    map.insert(key, step),
+                    model.insert(key, step),
+                    "step {}: {:?}",
+                    step,
+                    op
+                );
+            }
+            Op::Delete(key) => {
+                prop_assert_eq!(
+                    map.remove(&key),
+                    model.remove(&key),
+                    "step {}: {:?}",
+                    step,
+                    op
+                );
+            }
+            Op::Search(key) => {
+                prop_assert_eq!(map.get(&key), model.get(&key), "step {}: {:?}", step, op);
+                if let Some(value) = map.get_mut(&key) {
+                    *value += 1;
+                }
+                if let Some(value) = model.get_mut(&key) {
+                    *value += 1;
+                }
+            }
+            Op::RemoveRange(low, high) => {
+                let removed = model.range(low..high).count();
+                model.retain(|key, _| !(low..high).contains(key));
+                prop_assert_eq!(
+                    map.remove_range(low..high),
+                    removed,
+                    "step {}: {:?}",
+                    step,
+                    op
+                );
+            }
+        }
+        let report = map.validate();
+        prop_assert!(report.is_valid(), "step {}: {:?}\n{}", step, op, report);
+        prop_assert!(map.iter().eq(model.iter()), "step {}: {:?}", step, op);
+        prop_assert_eq!(map.len(), model.len());
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn set_matches_btreeset_on_few_keys(ops in prop::collection::vec(op(0..32), 0..300)) {
+        run_set(&ops)?;
+    }
+
+    #[test]
+    fn set_matches_btreeset_on_many_keys(ops in prop::collection::vec(op(-1000..1000), 0..600)) {
+        run_set(&ops)?;
+    }
+
+    #[test]
+    fn map_matches_btreemap_on_few_keys(ops in prop::collection::vec(op(0..32), 0..300)) {
+        run_map(&ops)?;
+    }
+
+    #[test]
+    fn map_matches_btreemap_on_many_keys(ops in prop::collection::vec(op(-1000..1000), 0..600)) {
+        run_map(&ops)?;
+    }
+}
+
+// fills the tree, empties it in an order that rotates on most deletes, then
+// fills it again from the free list
+#[test]
+fn refills_from_the_free_list() {
+    let mut ops: Vec<Op> = (0..500).map(Op::Insert).collect();
+    ops.extend((0..500).map(|key| Op::Delete(key * 7 % 500)));
+    ops.extend((0..500).rev().map(Op::Insert));
+    ops.extend((0..500).step_by(2).map(Op::Delete));
+    run_set(&ops).unwrap();
+    run_map(&ops).unwrap();
+}
@@ -0,0 +1,110 @@
+use avl::{Aggregate, AVLMap, AVL};
+
+#[derive(Clone, Debug, PartialEq)]
+struct Sum(i64);
+
+impl Aggregate<i32, ()> for Sum {
+    fn from_entry(key: &i32, _: &()) -> Self {
+        Sum(*key as i64)
+    }
+
+    fn combine(&self, right: &Self) -> Self {
+        Sum(self.0 + right.0)
+    }
+}
+
+fn keys(tree: &AVL<i32>) -> Vec<i32> {
+    tree.iter().copied().collect()
+}
+
+#[test]
+fn clone_has_the_same_keys_and_shape() {
+    let mut tree = AVL::new();
+    for key in [5, 3, 8, 1, 4, 7, 9, 2, 6].iter() {
+        tree.insert(*key);
+    }
+    let copy = tree.clone();
+
+    assert_eq!(keys(&copy), keys(&tree));
+    assert_eq!(copy.count(), tree.count());
+    assert_eq!(copy.height(), tree.height());
+    assert_eq!(copy.print(), tree.print());
+}
+
+#[test]
+fn changing_the_clone_leaves_the_original_alone() {
+    let mut tree = AVL::new();
+    for key in 0..20 {
+        tree.insert(key);
+    }
+    let before = tree.print();
+
+    let mut copy = tree.clone();
+    for key in 20..40 {
+        copy.insert(key);
+    }
+    for key in (0..20).step_by(2) {
+        copy.delete(&key);
+    }
+
+    assert_eq!(keys(&tree), (0..20).collect::<Vec<_>>());
+    assert_eq!(tree.count(), 20);
+    assert_eq!(tree.print(), before);
+    assert!(copy.search(&25));
+    assert!(!copy.search(&4));
+}
+
+#[test]
+fn changing_the_original_leaves_the_clone_alone() {
+    let mut tree = AVL::new();
+    for key in 0..20 {
+        tree.insert(key);
+    }
+    let copy = tree.clone();
+
+    tree.remove_range(5..15);
+    tree.insert(100);
+
+    assert_eq!(keys(&copy), (0..20).collect::<Vec<_>>());
+    assert_eq!(copy.count(), 20);
+    assert!(!copy.search(&100));
+}
+
+#[test]
+fn clone_keeps_its_own_aggregates() {
+    let mut tree: AVL<i32, Sum> = AVL::default();
+    for key in 1..=10 {
+        tree.insert(key);
+    }
+    let mut copy = tree.clone();
+    copy.insert(100);
+    copy.delete(&1);
+
+    assert_eq!(tree.aggregate(), Some(&Sum(55)));
+    assert_eq!(copy.aggregate(), Some(&Sum(154)));
+    assert_eq!(tree.fold_range(..=3), Some(Sum(6)));
+    assert_eq!(copy.fold_range(..=3), Some(Sum(5)));
+}
+
+#[test]
+fn map_clone_has_its_own_values() {
+    let mut map = AVLMap::new();
+    map.insert(1, "one");
+    map.insert(2, "two");
+
+    let mut copy = map.clone();
+    copy.insert(1, "uno");
+    if let Some(value) = copy.get_mut(&2) {
+        *value = "dos";
+    }
+    copy.remove(&3);
+    copy.insert(3, "tres");
+
+    assert_eq!(map.get(&1), Some(&"one"));
+    assert_eq!(map.get(&2), Some(&"two"));
+    assert_eq!(map.get(&3), None);
+    assert_eq!(map.len(), 2);
+    assert_eq!(copy.get(&1), Some(&"uno"));
+    assert_eq!(copy.get(&2), Some(&"dos"));
+    assert_eq!(copy.len(), 3);
+}
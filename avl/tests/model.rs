@@ -0,0 +1,100 @@
+// model-based tests: the shared runs of tree_harness::model check an AVL
+// against a BTreeSet, or a BTreeMap counting copies for a multiset, after every
+// step. the run with a comparator is specific to this crate and lives here
+
+use avl::AVL;
+use proptest::prelude::*;
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use tree_harness::model::{self, multiset_op, op, Op};
+
+// a tree made with a descending comparator against a model of reversed keys
+fn run_descending(ops: &[Op]) -> Result<(), TestCaseError> {
+    let mut tree = AVL::with_comparator(|a: &i32, b: &i32| b.cmp(a));
+    let mut model = BTreeSet::new();
+    for (step, op) in ops.iter().enumerate() {
+        match *op {
+            Op::Insert(key) => {
+                prop_assert_eq!(
+                    tree.insert(key),
+                    model.insert(Reverse(key)),
+                    "step {}: {:?}",
+                    step,
+                    op
+                );
+            }
+            Op::Delete(key) => {
+                prop_assert_eq!(
+                    tree.delete(&key),
+                    model.take(&Reverse(key)).map(|Reverse(key)| key),
+                    "step {}: {:?}",
+                    step,
+                    op
+                );
+            }
+            Op::DeleteAll(key) => {
+                let expected = usize::from(model.remove(&Reverse(key)));
+                prop_assert_eq!(tree.remove_all(&key), expected, "step {}: {:?}", step, op);
+            }
+            Op::Search(key) => {
+                prop_assert_eq!(
+                    tree.search(&key),
+                    model.contains(&Reverse(key)),
+                    "step {}: {:?}",
+                    step,
+                    op
+                );
+                prop_assert_eq!(tree.rank(&key), model.range(..Reverse(key)).count());
+                let below = model.range(..=Reverse(key)).next_back();
+                prop_assert_eq!(tree.floor(&key), below.map(|Reverse(key)| key));
+                let above = model.range(Reverse(key)..).next();
+                prop_assert_eq!(tree.ceiling(&key), above.map(|Reverse(key)| key));
+            }
+            Op::PopFirst => {
+                let expected = model.pop_first().map(|Reverse(key)| key);
+                prop_assert_eq!(tree.pop_first(), expected, "step {}: {:?}", step, op);
+            }
+            Op::PopLast => {
+                let expected = model.pop_last().map(|Reverse(key)| key);
+                prop_assert_eq!(tree.pop_last(), expected, "step {}: {:?}", step, op);
+            }
+        }
+        let report = tree.validate();
+        prop_assert!(report.is_valid(), "step {}: {:?}\n{}", step, op, report);
+        prop_assert!(
+            tree.iter().eq(model.iter().map(|Reverse(key)| key)),
+            "step {}: {:?}\n{}",
+            step,
+            op,
+            tree.print()
+        );
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn matches_btreeset_on_few_keys(ops in prop::collection::vec(op(0..32), 0..300)) {
+        model::run::<AVL<i32>>(&ops)?;
+    }
+
+    #[test]
+    fn matches_btreeset_on_many_keys(ops in prop::collection::vec(op(-1000..1000), 0..600)) {
+        model::run::<AVL<i32>>(&ops)?;
+    }
+
+    #[test]
+    fn orders_by_comparator(ops in prop::collection::vec(op(0..32), 0..300)) {
+        run_descending(&ops)?;
+    }
+
+    #[test]
+    fn counts_duplicates(ops in prop::collection::vec(multiset_op(0..16), 0..300)) {
+        model::run_counted::<AVL<i32>>(&ops)?;
+    }
+
+    #[test]
+    fn sorted_inserts_stay_balanced(n in 0..500i32, descending in any::<bool>()) {
+        model::run::<AVL<i32>>(&model::sorted_inserts(n, descending))?;
+    }
+}
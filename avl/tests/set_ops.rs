@@ -0,0 +1,72 @@
+// union, intersection, difference and symmetric_difference against BTreeSet,
+// with validate() on every result, and the subset, superset and disjoint
+// checks against theirs. both sides draw from one small range so the random
+// pairs overlap partly, fully or not at all
+
+use avl::AVL;
+use proptest::prelude::*;
+use std::collections::BTreeSet;
+
+fn tree_of(keys: &BTreeSet<i32>) -> AVL<i32> {
+    keys.iter().copied().collect()
+}
+
+fn check(tree: &AVL<i32>, model: BTreeSet<i32>) -> Result<(), TestCaseError> {
+    let report = tree.validate();
+    prop_assert!(report.is_valid(), "{}", report);
+    prop_assert!(tree.iter().eq(model.iter()), "{}", tree.print());
+    prop_assert_eq!(tree.count() as usize, model.len());
+    Ok(())
+}
+
+fn check_all(a: &BTreeSet<i32>, b: &BTreeSet<i32>) -> Result<(), TestCaseError> {
+    let (x, y) = (tree_of(a), tree_of(b));
+    check(&x.union(&y), a.union(b).copied().collect())?;
+    check(&x.intersection(&y), a.intersection(b).copied().collect())?;
+    check(&x.difference(&y), a.difference(b).copied().collect())?;
+    check(
+        &x.symmetric_difference(&y),
+        a.symmetric_difference(b).copied().collect(),
+    )?;
+    prop_assert_eq!(x.is_subset(&y), a.is_subset(b));
+    prop_assert_eq!(x.is_superset(&y), a.is_superset(b));
+    prop_assert_eq!(x.is_disjoint(&y), a.is_disjoint(b));
+    // the inputs are left as they were
+    check(&x, a.clone())?;
+    check(&y, b.clone())?;
+    Ok(())
+}
+
+fn keys() -> impl Strategy<Value = BTreeSet<i32>> {
+    prop::collection::btree_set(0..60i32, 0..50)
+}
+
+proptest! {
+    #[test]
+    fn set_ops_match_btreeset(a in keys(), b in keys()) {
+        check_all(&a, &b)?;
+        check_all(&b, &a)?;
+    }
+
+    #[test]
+    fn subsets_match_btreeset(a in keys(), drop in prop::collection::vec(any::<bool>(), 50)) {
+        // a random subset of a, which plain random pairs hardly ever produce
+        let b = a.iter().zip(drop).filter(|(_, drop)| !drop).map(|(&key, _)| key).collect();
+        check_all(&a, &b)?;
+        check_all(&b, &a)?;
+    }
+}
+
+#[test]
+fn empty_identical_and_disjoint_sets() {
+    let empty = BTreeSet::new();
+    let low: BTreeSet<i32> = (0..40).collect();
+    let high: BTreeSet<i32> = (40..90).collect();
+    let odd: BTreeSet<i32> = (0..90).filter(|key| key % 2 == 1).collect();
+    let sets = [&empty, &low, &high, &odd];
+    for a in sets.iter() {
+        for b in sets.iter() {
+            check_all(a, b).unwrap();
+        }
+    }
+}
@@ -0,0 +1,227 @@
+// snapshot tests: trees are saved and loaded back with the same shape, and
+// every way a file can be cut short, damaged, foreign or describe a broken
+// tree is a SnapshotError rather than a panic or a bad tree
+
+use avl::{AVLMap, DuplicatePolicy, AVL};
+use proptest::prelude::*;
+use tree_core::snapshot::{self, Codec, Header, NodeTag, SnapshotError, TreeKind};
+
+fn save(tree: &AVL<i32>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    tree.save_to(&mut bytes).unwrap();
+    bytes
+}
+
+// a tree whose shape came from inserts and deletes rather than a bulk build
+fn shuffled_tree(policy: DuplicatePolicy) -> AVL<i32> {
+    let mut tree = AVL::with_duplicates(policy);
+    for key in 0..60 {
+        tree.insert(key * 37 % 50);
+    }
+    for key in (0..50).step_by(3) {
+        tree.delete(&key);
+    }
+    tree
+}
+
+// the header of a version 1 AVL snapshot of a set with len keys
+fn header(len: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let header = Header {
+        len,
+        duplicates: DuplicatePolicy::Reject,
+    };
+    snapshot::write_header(&mut bytes, TreeKind::AVL, header).unwrap();
+    bytes
+}
+
+// appends one node of a set as save_to writes it
+fn node(bytes: &mut Vec<u8>, has_left: bool, has_right: bool, height: u8, key: i32) {
+    let tag = NodeTag {
+        has_left,
+        has_right,
+        red: false,
+    };
+    tag.write(bytes).unwrap();
+    height.encode(bytes).unwrap();
+    key.encode(bytes).unwrap();
+}
+
+fn corrupt(bytes: &[u8]) -> bool {
+    matches!(AVL::<i32>::load_from(bytes), Err(SnapshotError::Corrupt(_)))
+}
+
+#[test]
+fn round_trip_keeps_shape_heights_and_policy() {
+    for &policy in [
+        DuplicatePolicy::Reject,
+        DuplicatePolicy::Replace,
+        DuplicatePolicy::Count,
+    ]
+    .iter()
+    {
+        let tree = shuffled_tree(policy);
+        let bytes = save(&tree);
+        let copy = AVL::<i32>::load_from(&bytes[..]).unwrap();
+        assert!(copy.validate().is_valid());
+        assert_eq!(copy.duplicates(), policy);
+        assert_eq!(copy.print(), tree.print());
+        assert!(copy.preorder().eq(tree.preorder()));
+        assert_eq!(copy.height(), tree.height());
+        // heights are written out, so saving the copy gives the same bytes
+        assert_eq!(save(&copy), bytes);
+    }
+
+    let empty = save(&AVL::new());
+    assert!(AVL::<i32>::load_from(&empty[..]).unwrap().is_empty());
+}
+
+#[test]
+fn map_round_trip_keeps_values() {
+    let mut map = AVLMap::new();
+    for key in 0..40 {
+        map.insert(key * 7 % 40, key.to_string());
+    }
+    let mut bytes = Vec::new();
+    map.save_to(&mut bytes).unwrap();
+    let copy = AVLMap::<i32, String>::load_from(&bytes[..]).unwrap();
+    assert!(copy.validate().is_valid());
+    assert!(copy.iter().eq(map.iter()));
+    assert!(copy.preorder().eq(map.preorder()));
+}
+
+#[test]
+fn every_truncation_is_truncated() {
+    let bytes = save(&shuffled_tree(DuplicatePolicy::Count));
+    for end in 0..bytes.len() {
+        let result = AVL::<i32>::load_from(&bytes[..end]);
+        assert!(
+            matches!(result, Err(SnapshotError::Truncated)),
+            "cut at {} of {}: {:?}",
+            end,
+            bytes.len(),
+            result.map(|tree| tree.print())
+        );
+    }
+}
+
+#[test]
+fn flipped_bits_never_load_a_broken_tree() {
+    let bytes = save(&shuffled_tree(DuplicatePolicy::Reject));
+    for at in 0..bytes.len() {
+        for bit in 0..8 {
+            let mut damaged = bytes.clone();
+            damaged[at] ^= 1 << bit;
+            // a flipped key can still make a valid tree, anything else is an error
+            if let Ok(tree) = AVL::<i32>::load_from(&damaged[..]) {
+                assert!(tree.validate().is_valid(), "bit {} of byte {}", bit, at);
+                assert_eq!(tree.iter().count(), tree.count() as usize);
+            }
+        }
+    }
+}
+
+#[test]
+fn foreign_files_are_rejected_by_their_header() {
+    let bytes = save(&shuffled_tree(DuplicatePolicy::Reject));
+
+    let mut magic = bytes.clone();
+    magic[0] = b'X';
+    assert!(matches!(
+        AVL::<i32>::load_from(&magic[..]),
+        Err(SnapshotError::BadMagic)
+    ));
+
+    // the version is the u16 after the magic
+    let mut version = bytes.clone();
+    version[4..6].copy_from_slice(&2u16.to_le_bytes());
+    assert!(matches!(
+        AVL::<i32>::load_from(&version[..]),
+        Err(SnapshotError::UnsupportedVersion(2))
+    ));
+
+    // the tree kind is the byte after the version, 2 is red-black
+    let mut kind = bytes.clone();
+    kind[6] = 2;
+    assert!(matches!(
+        AVL::<i32>::load_from(&kind[..]),
+        Err(SnapshotError::WrongKind {
+            expected: TreeKind::AVL,
+            found: 2
+        })
+    ));
+
+    let mut policy = bytes;
+    policy[7] = 9;
+    assert!(corrupt(&policy));
+}
+
+#[test]
+fn readable_files_that_break_the_invariants_are_corrupt() {
+    // a well formed file of a tree 1 -> 2 -> 3 leaning right
+    let mut unbalanced = header(3);
+    node(&mut unbalanced, false, true, 3, 1);
+    node(&mut unbalanced, false, true, 2, 2);
+    node(&mut unbalanced, false, false, 1, 3);
+    assert!(corrupt(&unbalanced));
+
+    // balanced but 3 hangs left of 2
+    let mut unordered = header(3);
+    node(&mut unordered, true, true, 2, 2);
+    node(&mut unordered, false, false, 1, 3);
+    node(&mut unordered, false, false, 1, 1);
+    assert!(corrupt(&unordered));
+
+    // a repeated key in a tree that does not keep duplicates
+    let mut repeated = header(2);
+    node(&mut repeated, false, true, 2, 1);
+    node(&mut repeated, false, false, 1, 1);
+    assert!(corrupt(&repeated));
+
+    let mut wrong_height = header(1);
+    node(&mut wrong_height, false, false, 2, 1);
+    assert!(corrupt(&wrong_height));
+
+    // the header counts one node more or one less than follow
+    let mut short = header(2);
+    node(&mut short, false, false, 1, 1);
+    assert!(corrupt(&short));
+    let mut long = header(1);
+    node(&mut long, false, true, 2, 1);
+    node(&mut long, false, false, 1, 2);
+    assert!(corrupt(&long));
+
+    // tag bits that mean nothing, and a color on an AVL node
+    let mut tag = header(1);
+    tag.extend_from_slice(&[0x80, 1, 1, 0, 0, 0]);
+    assert!(corrupt(&tag));
+    let mut colored = header(1);
+    colored.extend_from_slice(&[0x04, 1, 1, 0, 0, 0]);
+    assert!(corrupt(&colored));
+}
+
+#[test]
+fn deep_chains_are_rejected_before_the_stack_runs_out() {
+    let depth = snapshot::MAX_DEPTH + 1;
+    let mut bytes = header(depth);
+    for key in 0..depth {
+        node(&mut bytes, false, key + 1 < depth, 1, key as i32);
+    }
+    assert!(corrupt(&bytes));
+}
+
+proptest! {
+    #[test]
+    fn random_trees_round_trip(keys in prop::collection::vec(-500..500i32, 0..200)) {
+        let mut tree = AVL::new();
+        for key in keys {
+            if !tree.insert(key) {
+                tree.delete(&key);
+            }
+        }
+        let bytes = save(&tree);
+        let copy = AVL::<i32>::load_from(&bytes[..]).unwrap();
+        prop_assert_eq!(copy.print(), tree.print());
+        prop_assert_eq!(save(&copy), bytes);
+    }
+}
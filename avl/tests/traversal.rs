@@ -0,0 +1,162 @@
+// traversals on trees of known shape, and the lengths the iterators report
+// while they are consumed from the front, the back or both
+
+use avl::{AVLMap, AVL};
+use proptest::prelude::*;
+
+fn orders(tree: &AVL<i32>) -> (Vec<i32>, Vec<i32>, Vec<i32>) {
+    (
+        tree.preorder().copied().collect(),
+        tree.postorder().copied().collect(),
+        tree.level_order().copied().collect(),
+    )
+}
+
+#[test]
+fn traverses_a_perfect_tree() {
+    //       4
+    //    2     6
+    //   1 3   5 7
+    let tree: AVL<i32> = AVL::from_sorted_iter(1..=7);
+    assert_eq!(
+        orders(&tree),
+        (
+            vec![4, 2, 1, 3, 6, 5, 7],
+            vec![1, 3, 2, 5, 7, 6, 4],
+            vec![4, 2, 6, 1, 3, 5, 7],
+        )
+    );
+}
+
+#[test]
+fn traverses_a_tree_with_a_missing_leaf() {
+    //       4
+    //    2     6
+    //   1 3   5
+    let tree: AVL<i32> = AVL::from_sorted_iter(1..=6);
+    assert_eq!(
+        orders(&tree),
+        (
+            vec![4, 2, 1, 3, 6, 5],
+            vec![1, 3, 2, 5, 6, 4],
+            vec![4, 2, 6, 1, 3, 5],
+        )
+    );
+}
+
+#[test]
+fn traverses_the_shape_left_by_rotations() {
+    // ascending inserts rotate left at 1, 3, 2 and 5, which leaves
+    //       4
+    //    2     6
+    //   1 3   5 7
+    //            8
+    let mut tree = AVL::new();
+    for key in 1..=8 {
+        tree.insert(key);
+    }
+    assert_eq!(
+        orders(&tree),
+        (
+            vec![4, 2, 1, 3, 6, 5, 7, 8],
+            vec![1, 3, 2, 5, 8, 7, 6, 4],
+            vec![4, 2, 6, 1, 3, 5, 7, 8],
+        )
+    );
+}
+
+#[test]
+fn traverses_map_entries_with_their_values() {
+    //    2
+    //   1 3
+    let map: AVLMap<i32, char> = vec![(1, 'a'), (2, 'b'), (3, 'c')].into_iter().collect();
+    assert_eq!(
+        map.preorder().collect::<Vec<_>>(),
+        vec![(&2, &'b'), (&1, &'a'), (&3, &'c')]
+    );
+    assert_eq!(
+        map.postorder().collect::<Vec<_>>(),
+        vec![(&1, &'a'), (&3, &'c'), (&2, &'b')]
+    );
+    assert_eq!(
+        map.level_order().collect::<Vec<_>>(),
+        vec![(&2, &'b'), (&1, &'a'), (&3, &'c')]
+    );
+}
+
+#[test]
+fn traverses_empty_and_single_key_trees() {
+    let mut tree = AVL::new();
+    assert_eq!(orders(&tree), (vec![], vec![], vec![]));
+    tree.insert(1);
+    assert_eq!(orders(&tree), (vec![1], vec![1], vec![1]));
+}
+
+proptest! {
+    #[test]
+    fn len_counts_the_keys_left_from_either_end(
+        keys in prop::collection::btree_set(-1000..1000i32, 0..200),
+        from_back in prop::collection::vec(any::<bool>(), 0..250),
+    ) {
+        let tree: AVL<i32> = keys.iter().copied().collect();
+        let mut iter = tree.iter();
+        let mut model = keys.iter();
+        prop_assert_eq!(iter.len(), keys.len());
+        for &back in &from_back {
+            if back {
+                prop_assert_eq!(iter.next_back(), model.next_back());
+            } else {
+                prop_assert_eq!(iter.next(), model.next());
+            }
+            prop_assert_eq!(iter.len(), model.len());
+            prop_assert_eq!(iter.size_hint(), (model.len(), Some(model.len())));
+        }
+    }
+
+    #[test]
+    fn range_len_counts_the_keys_left_from_either_end(
+        keys in prop::collection::btree_set(-1000..1000i32, 0..200),
+        bounds in (-1100..1100i32, 0..600i32),
+        from_back in prop::collection::vec(any::<bool>(), 0..250),
+    ) {
+        let (start, width) = bounds;
+        let tree: AVL<i32> = keys.iter().copied().collect();
+        let mut iter = tree.range(start..start + width);
+        let mut model = keys.range(start..start + width);
+        let mut left = model.clone().count();
+        prop_assert_eq!(iter.len(), left);
+        for &back in &from_back {
+            let (got, expected) = match back {
+                true => (iter.next_back(), model.next_back()),
+                false => (iter.next(), model.next()),
+            };
+            prop_assert_eq!(got, expected);
+            left = left.saturating_sub(1);
+            prop_assert_eq!(iter.len(), left);
+        }
+    }
+
+    #[test]
+    fn len_counts_the_keys_left_in_every_traversal(
+        keys in prop::collection::btree_set(-1000..1000i32, 0..200),
+    ) {
+        let tree: AVL<i32> = keys.iter().copied().collect();
+        let map: AVLMap<i32, ()> = keys.iter().map(|&key| (key, ())).collect();
+        let iters: Vec<Box<dyn ExactSizeIterator<Item = &i32>>> = vec![
+            Box::new(tree.preorder()),
+            Box::new(tree.postorder()),
+            Box::new(tree.level_order()),
+            Box::new(map.preorder().map(|(key, _)| key)),
+            Box::new(map.postorder().map(|(key, _)| key)),
+            Box::new(map.level_order().map(|(key, _)| key)),
+        ];
+        for mut iter in iters {
+            for left in (0..keys.len()).rev() {
+                prop_assert!(iter.next().is_some());
+                prop_assert_eq!(iter.len(), left);
+            }
+            prop_assert_eq!(iter.next(), None);
+            prop_assert_eq!(iter.len(), 0);
+        }
+    }
+}
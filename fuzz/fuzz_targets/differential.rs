@@ -0,0 +1,12 @@
+#![no_main]
+
+// Replays the decoded operations into AVL and RBTree and fails on the first
+// step where they disagree, see tree_harness::compare
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Err(divergence) = tree_harness::compare(&tree_harness::decode(data)) {
+        panic!("{}", divergence);
+    }
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+// Decodes the input into a sequence of inserts, deletes, finds and clears on a
+// small key range and runs it on AVL and RBTree next to a BTreeSet, see
+// tree_harness::check. Crashing inputs go into tree-harness/seeds.
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    tree_harness::check(&tree_harness::decode(data));
+});
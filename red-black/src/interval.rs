@@ -0,0 +1,300 @@
+use std::iter::FusedIterator;
+use std::ops::Range;
+use tree_core::augment::Augmented;
+use tree_core::traverse::{Entries, InOrder, NodeHandle};
+use tree_core::Aggregate;
+
+use crate::rbt::{NodeRef, RBTreeMap};
+
+/**
+ * A half-open interval [start, end), ordered by start and then by end
+ **/
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Interval<T> {
+  pub start: T,
+  pub end: T,
+}
+
+impl<T: Ord> Interval<T> {
+  /**
+   * Returns if the interval shares at least one point with range
+   **/
+  pub fn overlaps(&self, range: &Range<T>) -> bool {
+    self.start < range.end && range.start < self.end
+  }
+
+  /**
+   * Returns if point lies inside the interval
+   **/
+  pub fn contains(&self, point: &T) -> bool {
+    self.start <= *point && *point < self.end
+  }
+}
+
+impl<T> From<Range<T>> for Interval<T> {
+  fn from(range: Range<T>) -> Self {
+    Interval {
+      start: range.start,
+      end: range.end,
+    }
+  }
+}
+
+/**
+ * Largest end of the intervals in a subtree, which lets a search skip every
+ * subtree that ends before the query starts
+ **/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaxEnd<T>(pub T);
+
+impl<T: Ord + Clone, V> Aggregate<Interval<T>, V> for MaxEnd<T> {
+  fn from_entry(key: &Interval<T>, _: &V) -> Self {
+    MaxEnd(key.end.clone())
+  }
+
+  fn combine(&self, right: &Self) -> Self {
+    if right.0 > self.0 {
+      right.clone()
+    } else {
+      self.clone()
+    }
+  }
+}
+
+type IntervalNode<'a, T, V> = NodeRef<'a, Interval<T>, V, MaxEnd<T>>;
+
+/**
+ * Red-black tree of half-open intervals, each mapped to a value. Every node
+ * also holds the largest end in its subtree, kept up to date through the
+ * rotations of insertion and deletion like any other aggregate
+ **/
+#[derive(Debug)]
+pub struct IntervalTree<T, V = ()> {
+  map: RBTreeMap<Interval<T>, V, MaxEnd<T>>,
+}
+
+impl<T, V> IntervalTree<T, V>
+where
+  T: Ord + Clone,
+{
+  /**
+   * Creates a new empty tree
+   **/
+  pub fn new() -> Self {
+    Self {
+      map: RBTreeMap::default(),
+    }
+  }
+
+  /**
+   * Inserts interval with value, returning the old value if the exact same
+   * interval is already in the tree. Panics if the interval is empty
+   **/
+  pub fn insert(&mut self, interval: Range<T>, value: V) -> Option<V> {
+    assert!(
+      interval.start < interval.end,
+      "interval start must be less than its end"
+    );
+    self.map.insert(Interval::from(interval), value)
+  }
+
+  /**
+   * Removes the exact interval from the tree, returning its value
+   **/
+  pub fn remove(&mut self, interval: Range<T>) -> Option<V> {
+    self.map.remove(&Interval::from(interval))
+  }
+
+  /**
+   * Returns a reference to the value of the exact interval
+   **/
+  pub fn get(&self, interval: Range<T>) -> Option<&V> {
+    self.map.get(&Interval::from(interval))
+  }
+
+  /**
+   * Number of intervals in the tree
+   **/
+  pub fn len(&self) -> usize {
+    self.map.len()
+  }
+
+  /**
+   * Returns if the tree is empty or not
+   **/
+  pub fn is_empty(&self) -> bool {
+    self.map.is_empty()
+  }
+
+  /**
+   * Iterates over the intervals in ascending order, .rev() for descending
+   **/
+  pub fn iter(&self) -> Entries<InOrder<IntervalNode<'_, T, V>>> {
+    self.map.iter()
+  }
+
+  /**
+   * Iterates in ascending order over the intervals that share at least one
+   * point with query. Panics if query starts after it ends
+   **/
+  pub fn overlapping(&self, query: Range<T>) -> Overlapping<'_, T, V> {
+    assert!(
+      query.start <= query.end,
+      "range start is greater than range end"
+    );
+    // An empty query overlaps nothing
+    let root = if query.start < query.end {
+      self.map.root_ref()
+    } else {
+      None
+    };
+    Overlapping::new(root, Query::Overlapping(query))
+  }
+
+  /**
+   * Iterates in ascending order over the intervals that contain point
+   **/
+  pub fn containing(&self, point: T) -> Overlapping<'_, T, V> {
+    Overlapping::new(self.map.root_ref(), Query::Containing(point))
+  }
+
+  /**
+   * Returns if any interval shares at least one point with query, following a
+   * single path down from the root. Panics if query starts after it ends
+   **/
+  pub fn any_overlap(&self, query: Range<T>) -> bool {
+    assert!(
+      query.start <= query.end,
+      "range start is greater than range end"
+    );
+    if query.start == query.end {
+      return false;
+    }
+
+    let mut node = self.map.root_ref();
+    while let Some(n) = node {
+      if n.entry().0.overlaps(&query) {
+        return true;
+      }
+
+      // If the left subtree ends after the query starts but holds no overlap,
+      // its interval with that end starts after the query ends and so does
+      // everything to the right of it
+      node = match n.left() {
+        Some(left) if left.aggregate().0 > query.start => Some(left),
+        _ => n.right(),
+      };
+    }
+    false
+  }
+}
+
+impl<T, V> Default for IntervalTree<T, V>
+where
+  T: Ord + Clone,
+{
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<'a, T: Ord + Clone, V> IntoIterator for &'a IntervalTree<T, V> {
+  type Item = (&'a Interval<T>, &'a V);
+  type IntoIter = Entries<InOrder<IntervalNode<'a, T, V>>>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
+
+/**
+ * What an Overlapping iterator searches for
+ **/
+#[derive(Clone, Debug)]
+enum Query<T> {
+  Overlapping(Range<T>),
+  Containing(T),
+}
+
+impl<T: Ord> Query<T> {
+  /**
+   * Intervals ending at or before this point are never a match
+   **/
+  fn low(&self) -> &T {
+    match self {
+      Query::Overlapping(range) => &range.start,
+      Query::Containing(point) => point,
+    }
+  }
+
+  /**
+   * Returns if intervals starting at start lie past the query
+   **/
+  fn is_before(&self, start: &T) -> bool {
+    match self {
+      Query::Overlapping(range) => range.end <= *start,
+      Query::Containing(point) => point < start,
+    }
+  }
+}
+
+/**
+ * Iterates over the intervals matching a query in ascending order. It skips
+ * every subtree whose largest end is at or before the query start and stops
+ * at the first interval starting past the query
+ **/
+#[derive(Clone, Debug)]
+pub struct Overlapping<'a, T, V> {
+  stack: Vec<IntervalNode<'a, T, V>>,
+  query: Query<T>,
+}
+
+impl<'a, T: Ord + Clone, V> Overlapping<'a, T, V> {
+  fn new(root: Option<IntervalNode<'a, T, V>>, query: Query<T>) -> Self {
+    let mut overlapping = Self {
+      stack: vec![],
+      query,
+    };
+    overlapping.push_left(root);
+    overlapping
+  }
+
+  /**
+   * Pushes node and the left spine under it, stopping at the first subtree
+   * that ends before the query starts
+   **/
+  fn push_left(&mut self, node: Option<IntervalNode<'a, T, V>>) {
+    let mut node = node;
+    while let Some(n) = node {
+      if n.aggregate().0 <= *self.query.low() {
+        break;
+      }
+      self.stack.push(n);
+      node = n.left();
+    }
+  }
+}
+
+impl<'a, T: Ord + Clone, V> Iterator for Overlapping<'a, T, V> {
+  type Item = (&'a Interval<T>, &'a V);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let node = self.stack.pop()?;
+      let (interval, value) = node.entry();
+
+      // Every interval from here on starts at least as late
+      if self.query.is_before(&interval.start) {
+        self.stack.clear();
+        return None;
+      }
+
+      self.push_left(node.right());
+      if interval.end > *self.query.low() {
+        return Some((interval, value));
+      }
+    }
+  }
+}
+
+impl<'a, T: Ord + Clone, V> FusedIterator for Overlapping<'a, T, V> {}
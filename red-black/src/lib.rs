@@ -0,0 +1,10 @@
+mod interval;
+mod persistent;
+mod rbt;
+#[cfg(feature = "serde")]
+mod serde_impl;
+
+pub use crate::interval::{Interval, IntervalTree, MaxEnd, Overlapping};
+pub use crate::persistent::{PersistentRBTree, PersistentRBTreeMap};
+pub use crate::rbt::{NodeColor, RBTree, RBTreeMap};
+pub use tree_core::{Aggregate, DuplicatePolicy};
@@ -0,0 +1,827 @@
+use std::borrow;
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::ops::RangeBounds;
+use std::rc::Rc;
+use tree_core::traverse::{Entries, InOrder, Keys, NodeHandle, Range};
+use tree_core::validate::{self, Problem, Report, Side, Violation};
+use tree_core::{pretty, DuplicatePolicy, Natural};
+
+use crate::rbt::NodeColor;
+
+type Link<K, V> = Option<Rc<Node<K, V>>>;
+
+/**
+ * A node that never changes once built. Updates build new nodes along the
+ * path they touch and link them to the untouched subtrees of the old version
+ **/
+#[derive(Debug)]
+pub struct Node<K, V> {
+  color: NodeColor,
+  key: K,
+  value: V,
+  // Number of nodes in the subtree rooted here
+  size: usize,
+  left: Link<K, V>,
+  right: Link<K, V>,
+}
+
+/**
+ * Persistent red-black tree mapping keys to values. insert and remove leave
+ * the tree as it is and return a new version that shares every subtree they
+ * did not touch with it, so each update copies O(log n) nodes, every older
+ * version stays valid and clone is O(1)
+ **/
+#[derive(Debug)]
+pub struct PersistentRBTreeMap<K, V> {
+  root: Link<K, V>,
+}
+
+/**
+ * Persistent red-black tree of keys, a map without values
+ **/
+#[derive(Debug)]
+pub struct PersistentRBTree<T> {
+  map: PersistentRBTreeMap<T, ()>,
+}
+
+impl<K, V> PersistentRBTreeMap<K, V>
+where
+  K: Ord + Clone,
+  V: Clone,
+{
+  /**
+   * Creates a new empty tree
+   **/
+  pub fn new() -> Self {
+    Self { root: None }
+  }
+
+  /**
+   * Returns a new version with key mapped to value, replacing the value of
+   * key if it is already there
+   **/
+  pub fn insert(&self, key: K, value: V) -> Self {
+    Self {
+      root: blacken(insert(&self.root, key, value)),
+    }
+  }
+
+  /**
+   * Returns a new version without key. When key is not in the tree the new
+   * version shares the whole tree with this one
+   **/
+  pub fn remove<Q>(&self, key: &Q) -> Self
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    if !self.contains_key(key) {
+      return self.clone();
+    }
+    Self {
+      root: blacken(remove(&self.root, key)),
+    }
+  }
+
+  /**
+   * Returns a reference to the value of key
+   **/
+  pub fn get<Q>(&self, key: &Q) -> Option<&V>
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    self.get_entry(key).map(|(_, v)| v)
+  }
+
+  fn get_entry<Q>(&self, key: &Q) -> Option<(&K, &V)>
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    let mut node = self.root.as_ref();
+    while let Some(n) = node {
+      node = match key.cmp(n.key.borrow()) {
+        Ordering::Less => n.left.as_ref(),
+        Ordering::Greater => n.right.as_ref(),
+        Ordering::Equal => return Some((&n.key, &n.value)),
+      };
+    }
+    None
+  }
+
+  /**
+   * Returns if key is in the tree
+   **/
+  pub fn contains_key<Q>(&self, key: &Q) -> bool
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    self.get(key).is_some()
+  }
+
+  /**
+   * Number of entries in the tree
+   **/
+  pub fn len(&self) -> usize {
+    size_of(&self.root)
+  }
+
+  /**
+   * Returns if the tree is empty or not
+   **/
+  pub fn is_empty(&self) -> bool {
+    self.root.is_none()
+  }
+
+  /**
+   * Iterates over the entries in ascending key order, .rev() for descending
+   **/
+  pub fn iter(&self) -> Entries<InOrder<&Node<K, V>>> {
+    Entries::new(InOrder::new(self.root.as_deref(), self.len()))
+  }
+
+  /**
+   * Iterates over the entries with keys inside range in ascending order
+   **/
+  pub fn range<Q, R>(&self, range: R) -> Entries<Range<&Node<K, V>>>
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+  {
+    Entries::new(Range::new(self.root.as_deref(), range))
+  }
+
+  /**
+   * Returns if both versions are the same tree, sharing the same root
+   **/
+  pub fn ptr_eq(&self, other: &Self) -> bool {
+    match (&self.root, &other.root) {
+      (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+      (None, None) => true,
+      _ => false,
+    }
+  }
+
+  /**
+   * Checks every invariant of this version: keys in order, stored sizes
+   * right, a black root, no red node under a red one and the same number of
+   * black nodes on every path down
+   **/
+  pub fn validate(&self) -> Report<'_, K> {
+    let mut problems = Vec::new();
+    if let Some(ref root) = self.root {
+      if root.color == NodeColor::Red {
+        problems.push(Problem {
+          key: &root.key,
+          path: Vec::new(),
+          violation: Violation::RedRoot,
+        });
+      }
+    }
+    let (_, nodes) = validate_node(
+      &self.root,
+      false,
+      &mut Vec::new(),
+      None,
+      None,
+      &mut problems,
+    );
+    Report {
+      len: self.len(),
+      nodes,
+      problems,
+    }
+  }
+
+  pub fn pretty_print(&self) -> String
+  where
+    K: Display,
+  {
+    pretty::pretty_print(
+      self.root.as_deref(),
+      |node| {
+        let color_string = match node.color {
+          NodeColor::Red => "R",
+          NodeColor::Black => "B",
+        };
+        format!("{}{}", node.key, color_string)
+      },
+      |node| (node.left.as_deref(), node.right.as_deref()),
+    )
+  }
+}
+
+/**
+ * Shares the root, so cloning is O(1) and never copies a node
+ **/
+impl<K, V> Clone for PersistentRBTreeMap<K, V> {
+  fn clone(&self) -> Self {
+    Self {
+      root: self.root.clone(),
+    }
+  }
+}
+
+impl<K, V> Default for PersistentRBTreeMap<K, V>
+where
+  K: Ord + Clone,
+  V: Clone,
+{
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<T> PersistentRBTree<T>
+where
+  T: Ord + Clone,
+{
+  /**
+   * Creates a new empty tree
+   **/
+  pub fn new() -> Self {
+    Self {
+      map: PersistentRBTreeMap::new(),
+    }
+  }
+
+  /**
+   * Returns a new version with key in it
+   **/
+  pub fn insert(&self, key: T) -> Self {
+    Self {
+      map: self.map.insert(key, ()),
+    }
+  }
+
+  /**
+   * Returns a new version without key
+   **/
+  pub fn delete<Q>(&self, key: &Q) -> Self
+  where
+    T: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    Self {
+      map: self.map.remove(key),
+    }
+  }
+
+  /**
+   * Returns the key in the tree equal to key, if any
+   **/
+  pub fn find<Q>(&self, key: &Q) -> Option<&T>
+  where
+    T: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    self.map.get_entry(key).map(|(k, _)| k)
+  }
+
+  /**
+   * Number of keys in the tree
+   **/
+  pub fn len(&self) -> usize {
+    self.map.len()
+  }
+
+  /**
+   * Returns if the tree is empty or not
+   **/
+  pub fn is_empty(&self) -> bool {
+    self.map.is_empty()
+  }
+
+  /**
+   * Iterates over the keys in ascending order, .rev() for descending
+   **/
+  pub fn iter(&self) -> Keys<InOrder<&Node<T, ()>>> {
+    Keys::new(InOrder::new(self.map.root.as_deref(), self.len()))
+  }
+
+  /**
+   * Iterates over the keys inside range in ascending order
+   **/
+  pub fn range<Q, R>(&self, range: R) -> Keys<Range<&Node<T, ()>>>
+  where
+    T: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+  {
+    Keys::new(Range::new(self.map.root.as_deref(), range))
+  }
+
+  /**
+   * Returns if both versions are the same tree, sharing the same root
+   **/
+  pub fn ptr_eq(&self, other: &Self) -> bool {
+    self.map.ptr_eq(&other.map)
+  }
+
+  /**
+   * Checks every invariant of this version, see PersistentRBTreeMap::validate
+   **/
+  pub fn validate(&self) -> Report<'_, T> {
+    self.map.validate()
+  }
+
+  pub fn pretty_print(&self) -> String
+  where
+    T: Display,
+  {
+    self.map.pretty_print()
+  }
+}
+
+impl<T> Clone for PersistentRBTree<T> {
+  fn clone(&self) -> Self {
+    Self {
+      map: self.map.clone(),
+    }
+  }
+}
+
+impl<T> Default for PersistentRBTree<T>
+where
+  T: Ord + Clone,
+{
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<'a, K: Ord + Clone, V: Clone> IntoIterator for &'a PersistentRBTreeMap<K, V> {
+  type Item = (&'a K, &'a V);
+  type IntoIter = Entries<InOrder<&'a Node<K, V>>>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
+
+impl<'a, T: Ord + Clone> IntoIterator for &'a PersistentRBTree<T> {
+  type Item = &'a T;
+  type IntoIter = Keys<InOrder<&'a Node<T, ()>>>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
+
+impl<'a, K, V> NodeHandle for &'a Node<K, V> {
+  type Key = &'a K;
+  type Value = &'a V;
+
+  fn left(self) -> Option<Self> {
+    self.left.as_deref()
+  }
+
+  fn right(self) -> Option<Self> {
+    self.right.as_deref()
+  }
+
+  fn entry(self) -> (&'a K, &'a V) {
+    (&self.key, &self.value)
+  }
+
+  fn size(self) -> usize {
+    self.size
+  }
+}
+
+/**
+ * Builds a new node over two existing subtrees
+ **/
+fn node<K, V>(
+  color: NodeColor,
+  left: Link<K, V>,
+  key: K,
+  value: V,
+  right: Link<K, V>,
+) -> Link<K, V> {
+  Some(Rc::new(Node {
+    color,
+    size: size_of(&left) + size_of(&right) + 1,
+    key,
+    value,
+    left,
+    right,
+  }))
+}
+
+/**
+ * Number of nodes in a subtree, 0 for empty ones
+ **/
+fn size_of<K, V>(link: &Link<K, V>) -> usize {
+  link.as_ref().map_or(0, |n| n.size)
+}
+
+fn is_red<K, V>(link: &Link<K, V>) -> bool {
+  matches!(link, Some(n) if n.color == NodeColor::Red)
+}
+
+fn is_black<K, V>(link: &Link<K, V>) -> bool {
+  matches!(link, Some(n) if n.color == NodeColor::Black)
+}
+
+/**
+ * The same subtree with its root in color, copying only the root if it changes
+ **/
+fn paint<K: Clone, V: Clone>(link: &Link<K, V>, color: NodeColor) -> Link<K, V> {
+  match link {
+    Some(n) if n.color != color => node(
+      color,
+      n.left.clone(),
+      n.key.clone(),
+      n.value.clone(),
+      n.right.clone(),
+    ),
+    _ => link.clone(),
+  }
+}
+
+fn blacken<K: Clone, V: Clone>(link: Link<K, V>) -> Link<K, V> {
+  paint(&link, NodeColor::Black)
+}
+
+/**
+ * Builds a black node over left and right and repairs a red node with a red
+ * child right below it, turning the three nodes involved into a red node with
+ * two black children. A red left and right are recolored the same way
+ **/
+fn balance<K: Clone, V: Clone>(
+  left: Link<K, V>,
+  key: K,
+  value: V,
+  right: Link<K, V>,
+) -> Link<K, V> {
+  use NodeColor::{Black, Red};
+
+  if is_red(&left) && is_red(&right) {
+    return node(Red, paint(&left, Black), key, value, paint(&right, Black));
+  }
+  if let Some(l) = left.as_ref().filter(|l| l.color == Red) {
+    if let Some(ll) = l.left.as_ref().filter(|ll| ll.color == Red) {
+      return node(
+        Red,
+        node(
+          Black,
+          ll.left.clone(),
+          ll.key.clone(),
+          ll.value.clone(),
+          ll.right.clone(),
+        ),
+        l.key.clone(),
+        l.value.clone(),
+        node(Black, l.right.clone(), key, value, right),
+      );
+    }
+    if let Some(lr) = l.right.as_ref().filter(|lr| lr.color == Red) {
+      return node(
+        Red,
+        node(
+          Black,
+          l.left.clone(),
+          l.key.clone(),
+          l.value.clone(),
+          lr.left.clone(),
+        ),
+        lr.key.clone(),
+        lr.value.clone(),
+        node(Black, lr.right.clone(), key, value, right),
+      );
+    }
+  }
+  if let Some(r) = right.as_ref().filter(|r| r.color == Red) {
+    if let Some(rr) = r.right.as_ref().filter(|rr| rr.color == Red) {
+      return node(
+        Red,
+        node(Black, left, key, value, r.left.clone()),
+        r.key.clone(),
+        r.value.clone(),
+        node(
+          Black,
+          rr.left.clone(),
+          rr.key.clone(),
+          rr.value.clone(),
+          rr.right.clone(),
+        ),
+      );
+    }
+    if let Some(rl) = r.left.as_ref().filter(|rl| rl.color == Red) {
+      return node(
+        Red,
+        node(Black, left, key, value, rl.left.clone()),
+        rl.key.clone(),
+        rl.value.clone(),
+        node(
+          Black,
+          rl.right.clone(),
+          r.key.clone(),
+          r.value.clone(),
+          r.right.clone(),
+        ),
+      );
+    }
+  }
+  node(Black, left, key, value, right)
+}
+
+/**
+ * Copies the path down to key, adding a red node at its end or replacing the
+ * value of key, and rebalances below every black node on the way back up. The
+ * root may come back red
+ **/
+fn insert<K: Ord + Clone, V: Clone>(link: &Link<K, V>, key: K, value: V) -> Link<K, V> {
+  let n = match link {
+    None => return node(NodeColor::Red, None, key, value, None),
+    Some(n) => n,
+  };
+  let (k, v) = (n.key.clone(), n.value.clone());
+  match key.cmp(&n.key) {
+    Ordering::Less => {
+      let left = insert(&n.left, key, value);
+      match n.color {
+        NodeColor::Black => balance(left, k, v, n.right.clone()),
+        NodeColor::Red => node(NodeColor::Red, left, k, v, n.right.clone()),
+      }
+    }
+    Ordering::Greater => {
+      let right = insert(&n.right, key, value);
+      match n.color {
+        NodeColor::Black => balance(n.left.clone(), k, v, right),
+        NodeColor::Red => node(NodeColor::Red, n.left.clone(), k, v, right),
+      }
+    }
+    Ordering::Equal => node(n.color.clone(), n.left.clone(), key, value, n.right.clone()),
+  }
+}
+
+/**
+ * Copies the path down to key, which must be in the subtree, and leaves it
+ * out. Removing from below a black node lowers the black height of that side
+ * by one, which bal_left and bal_right make up for on the way back up. This is
+ * the deletion of Kahrs, "Red-black trees with types"
+ **/
+fn remove<K, V, Q>(link: &Link<K, V>, key: &Q) -> Link<K, V>
+where
+  K: Ord + Clone + borrow::Borrow<Q>,
+  V: Clone,
+  Q: Ord + ?Sized,
+{
+  let n = match link {
+    None => return None,
+    Some(n) => n,
+  };
+  let (k, v) = (n.key.clone(), n.value.clone());
+  match key.cmp(n.key.borrow()) {
+    Ordering::Less => {
+      let left = remove(&n.left, key);
+      if is_black(&n.left) {
+        bal_left(left, k, v, n.right.clone())
+      } else {
+        node(NodeColor::Red, left, k, v, n.right.clone())
+      }
+    }
+    Ordering::Greater => {
+      let right = remove(&n.right, key);
+      if is_black(&n.right) {
+        bal_right(n.left.clone(), k, v, right)
+      } else {
+        node(NodeColor::Red, n.left.clone(), k, v, right)
+      }
+    }
+    Ordering::Equal => fuse(&n.left, &n.right),
+  }
+}
+
+/**
+ * Builds a node whose left side has a black height one below its right side
+ **/
+fn bal_left<K: Clone, V: Clone>(
+  left: Link<K, V>,
+  key: K,
+  value: V,
+  right: Link<K, V>,
+) -> Link<K, V> {
+  use NodeColor::{Black, Red};
+
+  if is_red(&left) {
+    return node(Red, paint(&left, Black), key, value, right);
+  }
+  match right {
+    Some(ref r) if r.color == Black => balance(left, key, value, paint(&right, Red)),
+    Some(ref r) if is_black(&r.left) => {
+      let rl = r.left.as_ref().expect("checked to be black above");
+      node(
+        Red,
+        node(Black, left, key, value, rl.left.clone()),
+        rl.key.clone(),
+        rl.value.clone(),
+        balance(
+          rl.right.clone(),
+          r.key.clone(),
+          r.value.clone(),
+          paint(&r.right, Red),
+        ),
+      )
+    }
+    _ => panic!("Red-black invariant violated while removing"),
+  }
+}
+
+/**
+ * Builds a node whose right side has a black height one below its left side
+ **/
+fn bal_right<K: Clone, V: Clone>(
+  left: Link<K, V>,
+  key: K,
+  value: V,
+  right: Link<K, V>,
+) -> Link<K, V> {
+  use NodeColor::{Black, Red};
+
+  if is_red(&right) {
+    return node(Red, left, key, value, paint(&right, Black));
+  }
+  match left {
+    Some(ref l) if l.color == Black => balance(paint(&left, Red), key, value, right),
+    Some(ref l) if is_black(&l.right) => {
+      let lr = l.right.as_ref().expect("checked to be black above");
+      node(
+        Red,
+        balance(
+          paint(&l.left, Red),
+          l.key.clone(),
+          l.value.clone(),
+          lr.left.clone(),
+        ),
+        lr.key.clone(),
+        lr.value.clone(),
+        node(Black, lr.right.clone(), key, value, right),
+      )
+    }
+    _ => panic!("Red-black invariant violated while removing"),
+  }
+}
+
+/**
+ * Joins the two subtrees of a removed node, every key of left is smaller than
+ * every key of right and both have the same black height
+ **/
+fn fuse<K: Clone, V: Clone>(left: &Link<K, V>, right: &Link<K, V>) -> Link<K, V> {
+  use NodeColor::{Black, Red};
+
+  let (l, r) = match (left, right) {
+    (None, _) => return right.clone(),
+    (_, None) => return left.clone(),
+    (Some(l), Some(r)) => (l, r),
+  };
+  match (&l.color, &r.color) {
+    (Red, Red) => {
+      let middle = fuse(&l.right, &r.left);
+      match middle {
+        Some(ref m) if m.color == Red => node(
+          Red,
+          node(
+            Red,
+            l.left.clone(),
+            l.key.clone(),
+            l.value.clone(),
+            m.left.clone(),
+          ),
+          m.key.clone(),
+          m.value.clone(),
+          node(
+            Red,
+            m.right.clone(),
+            r.key.clone(),
+            r.value.clone(),
+            r.right.clone(),
+          ),
+        ),
+        _ => node(
+          Red,
+          l.left.clone(),
+          l.key.clone(),
+          l.value.clone(),
+          node(Red, middle, r.key.clone(), r.value.clone(), r.right.clone()),
+        ),
+      }
+    }
+    (Black, Black) => {
+      let middle = fuse(&l.right, &r.left);
+      match middle {
+        Some(ref m) if m.color == Red => node(
+          Red,
+          node(
+            Black,
+            l.left.clone(),
+            l.key.clone(),
+            l.value.clone(),
+            m.left.clone(),
+          ),
+          m.key.clone(),
+          m.value.clone(),
+          node(
+            Black,
+            m.right.clone(),
+            r.key.clone(),
+            r.value.clone(),
+            r.right.clone(),
+          ),
+        ),
+        _ => bal_left(
+          l.left.clone(),
+          l.key.clone(),
+          l.value.clone(),
+          node(
+            Black,
+            middle,
+            r.key.clone(),
+            r.value.clone(),
+            r.right.clone(),
+          ),
+        ),
+      }
+    }
+    (Black, Red) => node(
+      Red,
+      fuse(left, &r.left),
+      r.key.clone(),
+      r.value.clone(),
+      r.right.clone(),
+    ),
+    (Red, Black) => node(
+      Red,
+      l.left.clone(),
+      l.key.clone(),
+      l.value.clone(),
+      fuse(&l.right, right),
+    ),
+  }
+}
+
+/**
+ * Checks the subtree under link, whose parent is red if parent_red and whose
+ * keys must lie between lower and upper, and adds every invariant it breaks to
+ * problems. Returns the black height and the actual size of the subtree
+ **/
+fn validate_node<'a, K: Ord, V>(
+  link: &'a Link<K, V>,
+  parent_red: bool,
+  path: &mut Vec<Side>,
+  lower: Option<&'a K>,
+  upper: Option<&'a K>,
+  problems: &mut Vec<Problem<'a, K>>,
+) -> (usize, usize) {
+  let n = match link {
+    None => return (0, 0),
+    Some(n) => n,
+  };
+  let red = n.color == NodeColor::Red;
+  // The problems of node go in front of those of its subtrees
+  let at = problems.len();
+  path.push(Side::Left);
+  let (left_black, left_size) = validate_node(&n.left, red, path, lower, Some(&n.key), problems);
+  path.pop();
+  path.push(Side::Right);
+  let (right_black, right_size) = validate_node(&n.right, red, path, Some(&n.key), upper, problems);
+  path.pop();
+
+  let size = 1 + left_size + right_size;
+  let mut found = Vec::new();
+  if !validate::in_bounds(&n.key, lower, upper, DuplicatePolicy::Reject, &Natural) {
+    found.push(Violation::OutOfOrder);
+  }
+  if n.size != size {
+    found.push(Violation::WrongSize {
+      stored: n.size,
+      actual: size,
+    });
+  }
+  if red && parent_red {
+    found.push(Violation::RedUnderRed);
+  }
+  if left_black != right_black {
+    found.push(Violation::BlackHeightMismatch {
+      left: left_black,
+      right: right_black,
+    });
+  }
+  problems.splice(
+    at..at,
+    found.into_iter().map(|violation| Problem {
+      key: &n.key,
+      path: path.clone(),
+      violation,
+    }),
+  );
+
+  let black = if red { 0 } else { 1 };
+  (black + left_black.max(right_black), size)
+}
@@ -1,8 +1,18 @@
-use std::cell::RefCell;
-use std::cmp::max;
-use std::fmt::{Debug, Display};
-use std::io::{stdin, stdout, Write};
-use std::rc::{Rc, Weak};
+use std::borrow;
+use std::cmp::{max, Ordering};
+use std::fmt::{self, Debug, Display};
+use std::io::{Read, Write};
+use std::iter::FromIterator;
+use std::ops::RangeBounds;
+use tree_core::arena::{Arena, Link, NodeId};
+use tree_core::augment::{self, Aggregate, Augmented};
+use tree_core::merge::{Merge, MergeItem};
+use tree_core::snapshot::{self, Codec, Header, NodeTag, SnapshotError, TreeKind};
+use tree_core::traverse::{
+  Entries, InOrder, Keys, LevelOrder, NodeHandle, PostOrder, PreOrder, Range,
+};
+use tree_core::validate::{self, Problem, Report, Side, Violation};
+use tree_core::{build, order, pretty, Comparator, DuplicatePolicy, Natural, SortedTree};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum NodeColor {
@@ -10,1373 +20,2737 @@ pub enum NodeColor {
   Black,
 }
 
-type Child<T> = Option<Rc<RefCell<TreeNode<T>>>>;
-type Parent<T> = Option<Weak<RefCell<TreeNode<T>>>>;
+// The root of a detached subtree and its black height
+type Piece = (Link, usize);
 
+/**
+ * Node of RBTree and RBTreeMap. It lives in the arena of its tree and links
+ * to its parent and children by their index in that arena
+ **/
 #[derive(Debug, Clone)]
-pub struct TreeNode<T> {
-  pub color: NodeColor,
-  pub key: T,
-  pub parent: Parent<T>,
-  left: Child<T>,
-  right: Child<T>,
+struct TreeNode<K, V = (), A = ()> {
+  color: NodeColor,
+  key: K,
+  value: V,
+  parent: Link,
+  // Number of nodes in the subtree rooted here
+  size: usize,
+  // Aggregate of the entries in the subtree rooted here, see tree_core::augment
+  aggregate: A,
+  left: Link,
+  right: Link,
 }
+
+/**
+ * Borrowed handle to a node in the arena of a tree. The crate does not export
+ * it, it is only reachable as the item type of the traversals, which read the
+ * node through NodeHandle
+ **/
+pub struct NodeRef<'a, K, V = (), A = ()> {
+  nodes: &'a Arena<TreeNode<K, V, A>>,
+  id: NodeId,
+}
+
+/**
+ * Red-black tree mapping each key to a value. All nodes are kept in one
+ * arena, see tree_core::arena, so the tree needs no reference counting or
+ * runtime borrow checks and the slot of a removed node goes to the next insert
+ **/
 #[derive(Debug)]
-pub struct RBTree<T> {
-  pub root: Child<T>,
+pub struct RBTreeMap<K, V, A = ()> {
+  nodes: Arena<TreeNode<K, V, A>>,
+  root: Link,
 }
 
-impl<T> RBTree<T>
+/**
+ * Red-black tree of keys, a map without values
+ **/
+#[derive(Debug)]
+pub struct RBTree<T, A = (), C = Natural> {
+  map: RBTreeMap<T, (), A>,
+  // What insert does with a key that is already in the tree
+  duplicates: DuplicatePolicy,
+  // Decides the order of the keys, see tree_core::compare
+  cmp: C,
+}
+
+impl<K, V> RBTreeMap<K, V>
 where
-  T: Ord + Copy + Display + Debug,
+  K: Ord,
 {
   /**
-   * Creates a new empty tree
+   * Creates a new empty tree, trees with an aggregate are made with default()
    **/
   pub fn new() -> Self {
-    Self { root: None }
+    Self::default()
   }
 
   /**
-   * Right rotates the on the node specified
+   * Returns a mutable reference to the value of key. Only trees without an
+   * aggregate have it, a value changed behind the tree's back would leave the
+   * aggregates above it stale
    **/
-  fn right_rotation(&mut self, node: &mut Child<T>) {
-    let node = match node {
-      None => panic!("Rotation node in 'right_rotation' is None..."),
-      Some(ref node) => node,
-    };
-
-    // Step 1. Gets a reference to the left child of node
-    // Note that if we're performing a right-rotation, this shouldn't be None
-    let left_child = match node.borrow().left {
-      None => panic!("Right rotation: this should never be None"),
-      Some(ref node_left) => Rc::clone(node_left),
-    };
-
-    // If the rotation node is the root then we need to move the pointer
-    // to the left child
-    let root_value = match self.root {
-      None => panic!("This should never be none"),
-      Some(ref root) => root.borrow().key,
-    };
-
-    if root_value == node.borrow().key {
-      self.root = Some(Rc::clone(&left_child));
-    }
-
-    // Step 2. Get a reference to the left-right child
-    // This can definitely be None, so that case needs to be handled
-    let left_right_child = match left_child.borrow().right {
-      None => None,
-      Some(ref node_left_right) => Some(Rc::clone(node_left_right)),
-    };
-
-    // Step 3. Set the right child of the left child to be node
-    left_child.borrow_mut().right = Some(Rc::clone(&node));
-
-    // Step 3.5 Get a reference to the parent of the input node
-    let parent = match node.borrow().parent {
-      None => None,
-      Some(ref node_parent) => match node_parent.upgrade() {
-        None => None,
-        Some(ref node_parent) => Some(Rc::clone(node_parent)),
-      },
-    };
-
-    // Step 4. Set the parent of the left child to be the parent of node
-    left_child.borrow_mut().parent = match parent {
-      None => None,
-      Some(ref node_parent) => Some(Rc::downgrade(node_parent)),
-    };
-
-    // Step 5. Set the parent of node to be left child
-    node.borrow_mut().parent = Some(Rc::downgrade(&left_child));
-
-    // Step 6. Set the left child of node to be the left-right child
-    node.borrow_mut().left = match left_right_child {
-      None => None,
-      Some(ref node_left_right) => Some(Rc::clone(node_left_right)),
-    };
+  pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    let node = self.find_node(key)?;
+    Some(&mut self.nodes[node].value)
+  }
+}
 
-    // Step 7. Set the parent of left-right child to be node
-    match left_right_child {
-      None => {}
-      Some(ref node_left_right) => node_left_right.borrow_mut().parent = Some(Rc::downgrade(&node)),
-    }
+impl<K, V, A> RBTreeMap<K, V, A>
+where
+  A: Aggregate<K, V>,
+{
+  /**
+   * Stores a new red node without parent or children in the arena
+   **/
+  fn new_node(&mut self, key: K, value: V) -> NodeId {
+    self.nodes.alloc(TreeNode {
+      color: NodeColor::Red,
+      aggregate: A::from_entry(&key, &value),
+      key,
+      value,
+      parent: None,
+      size: 1,
+      left: None,
+      right: None,
+    })
+  }
 
-    // Step 8. Re-point the parent nodes child pointer that is pointing
-    // to the rotation node to the left child
+  /**
+   * Points the parent of old at new instead, new takes over old's parent
+   **/
+  fn transplant(&mut self, old: NodeId, new: Link) {
+    let parent = self.nodes[old].parent;
     match parent {
-      None => {}
-      Some(ref node_parent) => {
-        // Gets the value of the parents children
-        let is_left_child = match node_parent.borrow().left {
-          None => false,
-          Some(ref node_parent_left) => node.borrow().key == node_parent_left.borrow().key,
-        };
-
-        if is_left_child {
-          node_parent.borrow_mut().left = Some(Rc::clone(&left_child));
-          return;
-        }
-
-        let is_right_child = match node_parent.borrow().right {
-          None => false,
-          Some(ref node_parent_right) => node.borrow().key == node_parent_right.borrow().key,
-        };
-
-        if is_right_child {
-          node_parent.borrow_mut().right = Some(Rc::clone(&left_child));
-          return;
-        }
-      }
+      None => self.root = new,
+      Some(p) if self.nodes[p].left == Some(old) => self.nodes[p].left = new,
+      Some(p) => self.nodes[p].right = new,
+    }
+    if let Some(n) = new {
+      self.nodes[n].parent = parent;
     }
   }
 
   /**
-   * Left rotate on node
+   * Right rotates the on the node specified
    **/
-  fn left_rotation(&mut self, node: &mut Child<T>) {
-    let node = match node {
-      None => panic!("Rotation node in 'right_rotation' is None..."),
-      Some(ref node) => node,
-    };
-
-    // Step 1. Gets a reference to the right child of node
-    // Note that if we're performing a left-rotation, this shouldn't be None
-    let right_child = match node.borrow().right {
-      None => panic!("Left rotation: this should never be None"),
-      Some(ref node_right) => Rc::clone(node_right),
-    };
-
-    // If the rotation node is the root then we need to move the pointer
-    // to the left child
-    let root_value = match self.root {
-      None => panic!("This should never be none"),
-      Some(ref root) => root.borrow().key,
-    };
-
-    if root_value == node.borrow().key {
-      self.root = Some(Rc::clone(&right_child));
+  fn right_rotation(&mut self, node: NodeId) {
+    let left_child = self.nodes[node]
+      .left
+      .expect("Right rotation: this should never be None");
+    let left_right_child = self.nodes[left_child].right;
+
+    self.nodes[node].left = left_right_child;
+    if let Some(lr) = left_right_child {
+      self.nodes[lr].parent = Some(node);
     }
+    self.transplant(node, Some(left_child));
+    self.nodes[left_child].right = Some(node);
+    self.nodes[node].parent = Some(left_child);
 
-    // Step 2. Get a reference to the right-left child
-    // This can definitely be None, so that case needs to be handled
-    let right_left_child = match right_child.borrow().left {
-      None => None,
-      Some(ref node_right_left) => Some(Rc::clone(node_right_left)),
-    };
-
-    // Step 3. Set the left child of the right child to be node
-    right_child.borrow_mut().left = Some(Rc::clone(&node));
-
-    // Step 3.5 Get a reference to the parent of the input node
-    let parent = match node.borrow().parent {
-      None => None,
-      Some(ref node_parent) => match node_parent.upgrade() {
-        None => None,
-        Some(ref node_parent) => Some(Rc::clone(node_parent)),
-      },
-    };
-
-    // Step 4. Set the parent of the right child to be the parent of node
-    right_child.borrow_mut().parent = match parent {
-      None => None,
-      Some(ref node_parent) => Some(Rc::downgrade(node_parent)),
-    };
-
-    // Step 5. Set the parent of node to be right child
-    node.borrow_mut().parent = Some(Rc::downgrade(&right_child));
-
-    // Step 6. Set the right child of node to be the right-left child
-    node.borrow_mut().right = match right_left_child {
-      None => None,
-      Some(ref node_right_left) => Some(Rc::clone(node_right_left)),
-    };
+    // Node is now below the left child, so its size and aggregate are updated first
+    self.update_subtree(node);
+    self.update_subtree(left_child);
+  }
 
-    // Step 7. Set the parent of right-left child to be node
-    match right_left_child {
-      None => {}
-      Some(ref node_right_left) => node_right_left.borrow_mut().parent = Some(Rc::downgrade(&node)),
+  /**
+   * Left rotate on node
+   **/
+  fn left_rotation(&mut self, node: NodeId) {
+    let right_child = self.nodes[node]
+      .right
+      .expect("Left rotation: this should never be None");
+    let right_left_child = self.nodes[right_child].left;
+
+    self.nodes[node].right = right_left_child;
+    if let Some(rl) = right_left_child {
+      self.nodes[rl].parent = Some(node);
     }
+    self.transplant(node, Some(right_child));
+    self.nodes[right_child].left = Some(node);
+    self.nodes[node].parent = Some(right_child);
 
-    // Step 8. Re-point the parent nodes child pointer that is pointing
-    // to the rotation node to the left child
-    match parent {
-      None => {}
-      Some(ref node_parent) => {
-        // Gets the value of the parents children
-        let is_left_child = match node_parent.borrow().left {
-          None => false,
-          Some(ref node_parent_left) => node.borrow().key == node_parent_left.borrow().key,
-        };
-
-        if is_left_child {
-          node_parent.borrow_mut().left = Some(Rc::clone(&right_child));
-          return;
-        }
-
-        let is_right_child = match node_parent.borrow().right {
-          None => false,
-          Some(ref node_parent_right) => node.borrow().key == node_parent_right.borrow().key,
-        };
-
-        if is_right_child {
-          node_parent.borrow_mut().right = Some(Rc::clone(&right_child));
-          return;
-        }
-      }
-    }
+    // Node is now below the right child, so its size and aggregate are updated first
+    self.update_subtree(node);
+    self.update_subtree(right_child);
   }
 
   /**
-   * Checks and fixes property on tree
+   * Checks and fixes property on tree after node was added red. The root may
+   * be left red, the caller decides whether to blacken it
    **/
-  fn check_property(&mut self, node: &mut Child<T>) {
-    let current_node = node;
-    loop {
-      // Define node nad parent node
-      let node = match current_node {
-        None => None,
-        Some(ref n) => Some(Rc::clone(n)),
-      };
-      let mut parent = match node {
-        None => None,
-        Some(ref p) => match p.borrow().parent {
-          None => None,
-          Some(ref p) => match p.upgrade() {
-            None => None,
-            Some(ref p) => Some(Rc::clone(p)),
-          },
-        },
-      };
-
-      // Breaking condition. If parent is none, then exit
-      if parent.is_none() {
-        return;
-      }
-
-      // Define grandparent
-      let grandparent: Child<T> = match parent {
-        None => None,
-        Some(ref gp) => match gp.borrow().parent {
-          None => None,
-          Some(ref gp) => match gp.upgrade() {
-            None => None,
-            Some(ref gp) => Some(Rc::clone(gp)),
-          },
-        },
-      };
-
-      // Breaking condition if grandparent is none
-      if grandparent.is_none() {
-        return;
+  fn check_property(&mut self, node: NodeId) {
+    let mut node = node;
+    while let Some(parent) = self.nodes[node].parent {
+      if self.nodes[parent].color != NodeColor::Red {
+        break;
       }
-
-      match parent {
+      // Breaking condition if grandparent is none, a red root is fixed by the caller
+      let grandparent = match self.nodes[parent].parent {
         None => break,
-        Some(ref p) => {
-          // Breaking condition if parent is not red
-          if p.borrow().color != NodeColor::Red {
-            break;
-          }
-        }
-      };
-
-      // Define left uncle and right uncle
-      let grandparent_left = match grandparent {
-        None => None,
-        Some(ref gpl) => match gpl.borrow().left {
-          None => None,
-          Some(ref gpl) => Some(Rc::clone(gpl)),
-        },
-      };
-      let grandparent_right = match grandparent {
-        None => None,
-        Some(ref gpr) => match gpr.borrow().right {
-          None => None,
-          Some(ref gpr) => Some(Rc::clone(gpr)),
-        },
-      };
-
-      // Determine which is the right uncle
-      let mut key_equal = false;
-      match parent {
-        None => return,
-        Some(ref p) => match grandparent_left {
-          None => {}
-          Some(ref gplk) => {
-            if p.borrow().key == gplk.borrow().key {
-              key_equal = true;
-            }
-          }
-        },
+        Some(gp) => gp,
       };
 
-      if key_equal {
-        // Uncle is right grandparent
-        let uncle = grandparent_right;
-        // Case 1
-        match uncle {
-          None => {}
-          Some(ref u) => {
-            if u.borrow().color == NodeColor::Red {
-              u.borrow_mut().color = NodeColor::Black;
-              match parent {
-                None => {
-                  panic!("?")
-                }
-                Some(ref p) => p.borrow_mut().color = NodeColor::Black,
-              }
-              match grandparent {
-                None => {
-                  panic!("?")
-                }
-                Some(ref gp) => {
-                  gp.borrow_mut().color = NodeColor::Red;
-                  *current_node = Some(Rc::clone(gp));
-                  continue;
-                }
-              }
-            }
-          }
-        }
-
-        // Case 2
-        match parent {
-          None => {}
-          Some(ref p) => {
-            let mut equal = false;
-            match p.borrow().right {
-              None => {}
-              Some(ref pr) => match node {
-                None => {}
-                Some(ref n) => {
-                  if pr.borrow().key == n.borrow().key {
-                    equal = true;
-                  }
-                }
-              },
-            }
-            if equal {
-              // INSERT LEFT ROTATE PARENT
-              self.left_rotation(&mut Some(Rc::clone(p)));
-              let temp = Some(Rc::clone(p));
-              parent = match node {
-                None => {
-                  panic!("?")
-                }
-                Some(ref n) => Some(Rc::clone(n)),
-              };
-              *current_node = temp;
-            }
-          }
-        }
-        // Case 3
-        match parent {
-          None => {
-            panic!("?")
-          }
-          Some(ref p) => p.borrow_mut().color = NodeColor::Black,
-        }
-        match grandparent {
-          None => {
-            panic!("?")
-          }
-          Some(ref gp) => gp.borrow_mut().color = NodeColor::Red,
+      if self.nodes[grandparent].left == Some(parent) {
+        let uncle = self.nodes[grandparent].right;
+        // Case 1, red uncle: recolor and move up to the grandparent
+        if self.color_of(uncle) == NodeColor::Red {
+          self.set_color(uncle, NodeColor::Black);
+          self.nodes[parent].color = NodeColor::Black;
+          self.nodes[grandparent].color = NodeColor::Red;
+          node = grandparent;
+          continue;
         }
-        // INSERT RIGHT ROTATE GRANDPARENT
-        match grandparent {
-          None => {}
-          Some(ref gp) => self.right_rotation(&mut Some(Rc::clone(gp))),
+        // Case 2, node is an inner child: rotate it to the outside
+        let mut parent = parent;
+        if self.nodes[parent].right == Some(node) {
+          self.left_rotation(parent);
+          parent = node;
         }
+        // Case 3, node is an outer child: recolor and rotate the grandparent
+        self.nodes[parent].color = NodeColor::Black;
+        self.nodes[grandparent].color = NodeColor::Red;
+        self.right_rotation(grandparent);
+        break;
       } else {
-        // uncle is left grandparent
-        let uncle = grandparent_left;
-        // Case 1
-        match uncle {
-          None => {}
-          Some(ref u) => {
-            if u.borrow().color == NodeColor::Red {
-              u.borrow_mut().color = NodeColor::Black;
-              match parent {
-                None => {
-                  panic!("?")
-                }
-                Some(ref p) => p.borrow_mut().color = NodeColor::Black,
-              }
-              match grandparent {
-                None => {
-                  panic!("?")
-                }
-                Some(ref gp) => {
-                  gp.borrow_mut().color = NodeColor::Red;
-                  *current_node = Some(Rc::clone(gp));
-                  continue;
-                }
-              }
-            }
-          }
-        }
-
-        // Case 2
-        match parent {
-          None => {}
-          Some(ref p) => {
-            let mut equal = false;
-            match p.borrow().left {
-              None => {}
-              Some(ref pr) => match node {
-                None => {}
-                Some(ref n) => {
-                  if pr.borrow().key == n.borrow().key {
-                    equal = true;
-                  }
-                }
-              },
-            }
-            if equal {
-              // INSERT Right ROTATE PARENT
-              self.right_rotation(&mut Some(Rc::clone(p)));
-              let temp = Some(Rc::clone(p));
-              parent = match node {
-                None => {
-                  panic!("?")
-                }
-                Some(ref n) => Some(Rc::clone(n)),
-              };
-              *current_node = temp;
-            }
-          }
-        }
-
-        // Case 3
-        match parent {
-          None => {
-            panic!("?")
-          }
-          Some(ref p) => p.borrow_mut().color = NodeColor::Black,
-        }
-        match grandparent {
-          None => {
-            panic!("?")
-          }
-          Some(ref gp) => gp.borrow_mut().color = NodeColor::Red,
+        let uncle = self.nodes[grandparent].left;
+        if self.color_of(uncle) == NodeColor::Red {
+          self.set_color(uncle, NodeColor::Black);
+          self.nodes[parent].color = NodeColor::Black;
+          self.nodes[grandparent].color = NodeColor::Red;
+          node = grandparent;
+          continue;
         }
-        // INSERT LEFT ROTATE GRANDPARENT
-        match grandparent {
-          None => {}
-          Some(ref gp) => self.left_rotation(&mut Some(Rc::clone(gp))),
+        let mut parent = parent;
+        if self.nodes[parent].left == Some(node) {
+          self.right_rotation(parent);
+          parent = node;
         }
+        self.nodes[parent].color = NodeColor::Black;
+        self.nodes[grandparent].color = NodeColor::Red;
+        self.left_rotation(grandparent);
+        break;
       }
     }
   }
 
   /**
-   * Insert a key into the tree
+   * Insert a key into the tree, returning the old value if the key already exists
    **/
-  pub fn insert(&mut self, key: T) {
-    let mut insert_node = TreeNode::new(key);
-
+  pub fn insert(&mut self, key: K, value: V) -> Option<V>
+  where
+    K: Ord,
+  {
     // Checks if the key already exists
-    match self.find(key) {
-      None => {}
-      Some(_) => {
-        println!("{} already exists in the tree", key);
-        return;
-      }
-    };
+    if let Some(node) = self.find_node(&key) {
+      let replaced = std::mem::replace(&mut self.nodes[node].value, value);
+      // The aggregates from the node up depend on the value
+      self.update_subtrees_up(Some(node));
+      return Some(replaced);
+    }
 
-    // If tree is empty
-    if self.root.is_none() {
-      insert_node.color = NodeColor::Black;
-      self.root = Some(Rc::new(RefCell::new(insert_node)));
-    } else {
-      let mut y: Child<T> = None;
-      let mut x = match self.root {
-        None => None,
-        Some(ref r) => Some(Rc::clone(r)),
-      };
+    self.insert_new(key, value, &Natural);
+    None
+  }
 
-      // Determine where to insert the node
-      while !x.is_none() {
-        y = match x {
-          None => None,
-          Some(ref n) => Some(Rc::clone(n)),
-        };
-        let mut z: Child<T> = None;
-        match x {
-          None => {}
-          Some(ref x1) => {
-            if insert_node.key < x1.borrow().key {
-              z = match x1.borrow().left {
-                None => None,
-                Some(ref x1l) => Some(Rc::clone(x1l)),
-              }
-            } else {
-              z = match x1.borrow().right {
-                None => None,
-                Some(ref x1l) => Some(Rc::clone(x1l)),
-              }
-            }
-          }
-        }
-        x = z
-      }
-      insert_node.parent = match y {
-        None => None,
-        Some(ref y1) => Some(Rc::downgrade(&Rc::clone(y1))),
+  /**
+   * Adds a node for key and value without looking for an equal key first, a
+   * key equal to ones in the tree goes to the right of them. Keys are ordered
+   * by cmp
+   **/
+  fn insert_new<C: Comparator<K>>(&mut self, key: K, value: V, cmp: &C) {
+    // Determine where to insert the node
+    let mut parent = None;
+    let mut current = self.root;
+    let mut go_left = false;
+    while let Some(n) = current {
+      parent = current;
+      go_left = cmp.compare(&key, &self.nodes[n].key) == Ordering::Less;
+      current = match go_left {
+        true => self.nodes[n].left,
+        false => self.nodes[n].right,
       };
-      if y.is_none() {
-        self.root = Some(Rc::new(RefCell::new(insert_node)));
-      } else {
-        // Insert and check propery
-        match y {
-          None => panic!("?"),
-          Some(ref y1) => {
-            if insert_node.key < y1.borrow().key {
-              let w = Rc::new(RefCell::new(insert_node));
-              y1.borrow_mut().left = Some(Rc::clone(&w));
-              self.check_property(&mut Some(Rc::clone(&w)));
-            } else {
-              let w = Rc::new(RefCell::new(insert_node));
-              y1.borrow_mut().right = Some(Rc::clone(&w));
-              self.check_property(&mut Some(Rc::clone(&w)));
-            }
-          }
-        }
-      }
+    }
 
-      // Make sure root is black
-      match self.root {
-        None => {}
-        Some(ref r) => r.borrow_mut().color = NodeColor::Black,
-      }
+    let node = self.new_node(key, value);
+    self.nodes[node].parent = parent;
+    match parent {
+      None => self.root = Some(node),
+      Some(p) if go_left => self.nodes[p].left = Some(node),
+      Some(p) => self.nodes[p].right = Some(node),
     }
+
+    // Insert and check propery
+    self.update_subtrees_up(parent);
+    self.check_property(node);
+
+    // Make sure root is black
+    self.set_color(self.root, NodeColor::Black);
   }
 
   /**
-   * Fixes tree after deletion
+   * Fixes tree after deletion, node is the child that took the place of the
+   * removed black node and may be None
    **/
-  fn fix_delete(&mut self, node: &mut Child<T>, parent: &mut Child<T>) {
-    let mut other: Child<T>;
-    let mut node = match node {
-      None => None,
-      Some(ref n) => Some(Rc::clone(n)),
-    };
-    let mut parent = match parent {
-      None => None,
-      Some(ref p) => Some(Rc::clone(p)),
-    };
-    loop {
-      let root_key = match self.root {
-        None => break,
-        Some(ref r) => r.borrow().key,
-      };
-      let node_key = match node {
+  fn fix_delete(&mut self, node: Link, parent: Link) {
+    let mut node = node;
+    let mut parent = parent;
+
+    // Node carries an extra black until it reaches a red node or the root
+    while self.color_of(node) == NodeColor::Black {
+      let p = match parent {
         None => break,
-        Some(ref n) => n.borrow().key,
+        Some(p) => p,
       };
-      match node {
-        // exit conditon
-        None => break,
-        Some(ref n) => {
-          if !(n.borrow().key != root_key && n.borrow().color == NodeColor::Black) {
-            break;
-          }
+
+      if self.nodes[p].left == node {
+        let mut other = self.nodes[p].right;
+
+        // If the sibling is red
+        // Set sibling to black
+        // Set parent to red
+        // Left rotate on parent
+        if self.color_of(other) == NodeColor::Red {
+          self.set_color(other, NodeColor::Black);
+          self.nodes[p].color = NodeColor::Red;
+          self.left_rotation(p);
+          other = self.nodes[p].right;
         }
-      };
 
-      let mut is_left = false;
-      match parent {
-        None => break,
-        Some(ref p) => match p.borrow().left {
-          None => break,
-          Some(ref pl) => {
-            if pl.borrow().key == node_key {
-              other = match p.borrow().right {
-                None => None,
-                Some(ref pr) => Some(Rc::clone(pr)),
-              };
-              is_left = true;
-            } else {
-              other = match p.borrow().left {
-                None => None,
-                Some(ref pl) => Some(Rc::clone(pl)),
-              };
-            }
-          }
-        },
-      }
+        let o = other.expect("The sibling of a double black node is never None");
 
-      // If node is left child of parent
-      if is_left {
-        let mut flag = false;
-        match other {
-          None => break,
-          Some(ref o) => {
-            // If right child of parent is red
-            // Set color to black
-            // Set parent color to red
-            // Left rotate on parent
-            if o.borrow().color == NodeColor::Red {
-              o.borrow_mut().color = NodeColor::Black;
-              match parent {
-                None => break,
-                Some(ref p) => p.borrow_mut().color = NodeColor::Red,
-              };
-              match parent {
-                None => {}
-                Some(ref p) => self.left_rotation(&mut Some(Rc::clone(p))),
-              };
-              flag = true;
-            }
-          }
-        };
-        if flag {
-          // reassign right child
-          other = match parent {
-            None => break,
-            Some(ref p) => match p.borrow().right {
-              None => break,
-              Some(ref pr) => Some(Rc::clone(pr)),
-            },
-          };
+        // If both of the sibling's children are black
+        // Set sibling to red
+        // Move up to the parent
+        if self.color_of(self.nodes[o].left) == NodeColor::Black
+          && self.color_of(self.nodes[o].right) == NodeColor::Black
+        {
+          self.nodes[o].color = NodeColor::Red;
+          node = Some(p);
+          parent = self.nodes[p].parent;
+          continue;
         }
 
-        let mut matches = false;
-        match other {
-          None => break,
-          Some(ref o) => {
-            // Right left color
-            let other_left_color = match o.borrow().left {
-              None => break,
-              Some(ref ol) => {
-                if ol.borrow().color == NodeColor::Black {
-                  NodeColor::Black
-                } else {
-                  NodeColor::Red
-                }
-              }
-            };
-            // Right right color
-            let other_right_color = match o.borrow().right {
-              None => break,
-              Some(ref or) => {
-                if or.borrow().color == NodeColor::Black {
-                  NodeColor::Black
-                } else {
-                  NodeColor::Red
-                }
-              }
-            };
-
-            // If both children are black
-            // Set parent to red
-            // Set node to parent
-            // Set parent to node parent
-            if other_left_color == NodeColor::Black && other_right_color == NodeColor::Black {
-              o.borrow_mut().color = NodeColor::Red;
-              node = match parent {
-                None => None,
-                Some(ref p) => Some(Rc::clone(p)),
-              };
-              parent = match node {
-                None => None,
-                Some(ref n) => match n.borrow().parent {
-                  None => None,
-                  Some(ref np) => match np.upgrade() {
-                    None => None,
-                    Some(ref npu) => Some(Rc::clone(npu)),
-                  },
-                },
-              }
-            } else {
-              // If right is black
-              // Set left to black
-              // Set parent to red
-              // right rotate
-              if other_right_color == NodeColor::Black {
-                match o.borrow().left {
-                  None => break,
-                  Some(ref ol) => ol.borrow_mut().color = NodeColor::Black,
-                };
-                o.borrow_mut().color = NodeColor::Red;
-                self.right_rotation(&mut Some(Rc::clone(o)));
-                matches = true;
-              }
-            }
-          }
-        }
-        // Reassign node
-        if matches {
-          other = match parent {
-            None => break,
-            Some(ref p) => match p.borrow().right {
-              None => break,
-              Some(ref pr) => Some(Rc::clone(pr)),
-            },
-          };
-        }
+        // If the sibling's right child is black
+        // Set the left child to black
+        // Set sibling to red
+        // Right rotate on sibling
+        let o = if self.color_of(self.nodes[o].right) == NodeColor::Black {
+          self.set_color(self.nodes[o].left, NodeColor::Black);
+          self.nodes[o].color = NodeColor::Red;
+          self.right_rotation(o);
+          self.nodes[p]
+            .right
+            .expect("Right rotation moved a node into the sibling's place")
+        } else {
+          o
+        };
 
-        match other {
-          None => break,
-          Some(ref o) => match parent {
-            None => break,
-            Some(ref p) => {
-              if p.borrow().color == NodeColor::Black {
-                o.borrow_mut().color = NodeColor::Black;
-              } else {
-                o.borrow_mut().color = NodeColor::Red;
-              }
-              p.borrow_mut().color = NodeColor::Black;
-              match o.borrow().right {
-                None => break,
-                Some(ref or) => or.borrow_mut().color = NodeColor::Black,
-              }
-              self.left_rotation(&mut Some(Rc::clone(p)));
-              node = match self.root {
-                None => None,
-                Some(ref r) => Some(Rc::clone(r)),
-              };
-              break;
-            }
-          },
-        }
+        // Sibling takes the parent's color, parent and the sibling's right
+        // child become black, left rotate on parent
+        self.nodes[o].color = self.nodes[p].color.clone();
+        self.nodes[p].color = NodeColor::Black;
+        self.set_color(self.nodes[o].right, NodeColor::Black);
+        self.left_rotation(p);
+        node = self.root;
+        break;
       } else {
-        let mut flag = false;
-        match other {
-          None => break,
-          Some(ref o) => {
-            if o.borrow().color == NodeColor::Red {
-              o.borrow_mut().color = NodeColor::Black;
-              match parent {
-                None => break,
-                Some(ref p) => p.borrow_mut().color = NodeColor::Red,
-              };
-              match parent {
-                None => {}
-                Some(ref p) => self.right_rotation(&mut Some(Rc::clone(p))),
-              };
-              flag = true;
-            }
-          }
-        };
-        if flag {
-          other = match parent {
-            None => break,
-            Some(ref p) => match p.borrow().left {
-              None => break,
-              Some(ref pl) => Some(Rc::clone(pl)),
-            },
-          };
-        }
-        let mut matches = false;
-        match other {
-          None => break,
-          Some(ref o) => {
-            let other_left_color = match o.borrow().left {
-              None => break,
-              Some(ref ol) => {
-                if ol.borrow().color == NodeColor::Black {
-                  NodeColor::Black
-                } else {
-                  NodeColor::Red
-                }
-              }
-            };
-            let other_right_color = match o.borrow().right {
-              None => break,
-              Some(ref or) => {
-                if or.borrow().color == NodeColor::Black {
-                  NodeColor::Black
-                } else {
-                  NodeColor::Red
-                }
-              }
-            };
-
-            if other_left_color == NodeColor::Black && other_right_color == NodeColor::Black {
-              o.borrow_mut().color = NodeColor::Red;
-              node = match parent {
-                None => None,
-                Some(ref p) => Some(Rc::clone(p)),
-              };
-              parent = match node {
-                None => None,
-                Some(ref n) => match n.borrow().parent {
-                  None => None,
-                  Some(ref np) => match np.upgrade() {
-                    None => None,
-                    Some(ref npu) => Some(Rc::clone(npu)),
-                  },
-                },
-              }
-            } else {
-              if other_left_color == NodeColor::Black {
-                match o.borrow().right {
-                  None => break,
-                  Some(ref or) => or.borrow_mut().color = NodeColor::Black,
-                };
-                o.borrow_mut().color = NodeColor::Red;
-                self.left_rotation(&mut Some(Rc::clone(o)));
-                matches = true;
-              }
-            }
-          }
-        }
-        if matches {
-          other = match parent {
-            None => break,
-            Some(ref p) => match p.borrow().left {
-              None => break,
-              Some(ref pl) => Some(Rc::clone(pl)),
-            },
-          };
+        let mut other = self.nodes[p].left;
+
+        if self.color_of(other) == NodeColor::Red {
+          self.set_color(other, NodeColor::Black);
+          self.nodes[p].color = NodeColor::Red;
+          self.right_rotation(p);
+          other = self.nodes[p].left;
         }
-        match other {
-          None => break,
-          Some(ref o) => match parent {
-            None => break,
-            Some(ref p) => {
-              if p.borrow().color == NodeColor::Black {
-                o.borrow_mut().color = NodeColor::Black;
-              } else {
-                o.borrow_mut().color = NodeColor::Red;
-              }
-              p.borrow_mut().color = NodeColor::Black;
-              match o.borrow().left {
-                None => break,
-                Some(ref ol) => ol.borrow_mut().color = NodeColor::Black,
-              }
-              self.right_rotation(&mut Some(Rc::clone(p)));
-              node = match self.root {
-                None => None,
-                Some(ref r) => Some(Rc::clone(r)),
-              };
-              break;
-            }
-          },
+
+        let o = other.expect("The sibling of a double black node is never None");
+
+        if self.color_of(self.nodes[o].left) == NodeColor::Black
+          && self.color_of(self.nodes[o].right) == NodeColor::Black
+        {
+          self.nodes[o].color = NodeColor::Red;
+          node = Some(p);
+          parent = self.nodes[p].parent;
+          continue;
         }
+
+        let o = if self.color_of(self.nodes[o].left) == NodeColor::Black {
+          self.set_color(self.nodes[o].right, NodeColor::Black);
+          self.nodes[o].color = NodeColor::Red;
+          self.left_rotation(o);
+          self.nodes[p]
+            .left
+            .expect("Left rotation moved a node into the sibling's place")
+        } else {
+          o
+        };
+
+        self.nodes[o].color = self.nodes[p].color.clone();
+        self.nodes[p].color = NodeColor::Black;
+        self.set_color(self.nodes[o].left, NodeColor::Black);
+        self.right_rotation(p);
+        node = self.root;
+        break;
       }
     }
+
+    // The loop exits on a red node or the root, either of which ends black
+    self.set_color(node, NodeColor::Black);
   }
 
   /**
-   * Deletes a node from the tree
+   * Deletes a node from the tree, returning its value
    **/
-  pub fn delete(&mut self, key: T) {
-    fn min_node<T>(node: &Child<T>) -> Child<T> {
-      let mut temp = match node {
-        None => return None,
-        Some(ref n) => Some(Rc::clone(n)),
-      };
-      loop {
-        let mut temp_left = match temp {
-          None => None,
-          Some(ref t) => match t.borrow().left {
-            None => None,
-            Some(ref tl) => Some(Rc::clone(tl)),
-          },
-        };
-        if temp_left.is_none() {
-          break;
-        }
-        temp = temp_left;
-      }
-      return temp;
-    }
+  pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    self.remove_entry(key).map(|(_, value)| value)
+  }
 
-    let node_to_delete = self.find(key);
-    let node_left = match node_to_delete {
-      None => return,
-      Some(ref n) => match n.borrow().left {
-        None => None,
-        Some(ref nl) => Some(Rc::clone(nl)),
-      },
-    };
-    let node_right = match node_to_delete {
-      None => return,
-      Some(ref n) => match n.borrow().right {
-        None => None,
-        Some(ref nr) => Some(Rc::clone(nr)),
-      },
-    };
-    let root_key = match self.root {
-      None => return,
-      Some(ref rc) => rc.borrow().key,
-    };
-    if !node_left.is_none() && !node_right.is_none() {
-      let replace = min_node(&node_right);
-      let node_key = match node_to_delete {
-        None => return,
-        Some(ref nc) => nc.borrow().key,
-      };
+  /**
+   * Deletes a node from the tree, returning its key and value
+   **/
+  pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    let node = self.find_node(key)?;
+    Some(self.remove_node(node))
+  }
 
-      if node_key == root_key {
-        self.root = match replace {
-          None => None,
-          Some(ref r) => Some(Rc::clone(r)),
-        };
-      } else {
-        match node_to_delete {
-          None => return,
-          Some(ref n) => match n.borrow().parent {
-            None => return,
-            Some(ref np) => match np.upgrade() {
-              None => return,
-              Some(ref npu) => {
-                let node_parent_left_key = match Rc::clone(npu).borrow().left {
-                  None => return,
-                  Some(ref nplk) => nplk.borrow().key,
-                };
-                if node_parent_left_key == node_key {
-                  npu.borrow_mut().left = match replace {
-                    None => None,
-                    Some(ref r) => Some(Rc::clone(r)),
-                  };
-                } else {
-                  npu.borrow_mut().right = match replace {
-                    None => None,
-                    Some(ref r) => Some(Rc::clone(r)),
-                  };
-                }
-              }
-            },
-          },
-        }
+  /**
+   * Deletes node from the tree, returning its entry
+   **/
+  fn remove_node(&mut self, node: NodeId) -> (K, V) {
+    let (left, right) = (self.nodes[node].left, self.nodes[node].right);
+    let mut removed_color = self.nodes[node].color.clone();
+
+    // Child is the node that moves into the removed position, parent is where it hangs
+    let (child, parent) = match (left, right) {
+      (None, child) | (child, None) => {
+        let parent = self.nodes[node].parent;
+        self.transplant(node, child);
+        (child, parent)
       }
-
-      let child = match replace {
-        None => return,
-        Some(ref r) => match r.borrow().right {
-          None => None,
-          Some(ref rr) => Some(Rc::clone(rr)),
-        },
-      };
-      let parent = match replace {
-        None => return,
-        Some(ref r) => match r.borrow().parent {
-          None => None,
-          Some(ref rr) => match rr.upgrade() {
-            None => return,
-            Some(ref rru) => Some(Rc::clone(rru)),
-          },
-        },
-      };
-
-      let color = match replace {
-        None => return,
-        Some(ref r) => {
-          if r.borrow().color == NodeColor::Black {
-            NodeColor::Black
-          } else {
-            NodeColor::Red
-          }
-        }
-      };
-
-      match parent {
-        None => {}
-        Some(ref p) => {
-          if p.borrow().key == key {
-            match replace {
-              None => None,
-              Some(ref r) => Some(Rc::clone(r)),
-            };
-          } else {
-            if !child.is_none() {
-              match child {
-                None => {}
-                Some(ref c) => c.borrow_mut().parent = Some(Rc::downgrade(&Rc::clone(p))),
-              }
-            }
-            p.borrow_mut().left = match child {
-              None => None,
-              Some(ref c) => Some(Rc::clone(c)),
-            };
-            match replace {
-              None => {}
-              Some(ref r) => match node_to_delete {
-                None => return,
-                Some(ref n) => match n.borrow().right {
-                  None => r.borrow_mut().right = None,
-                  Some(ref nr) => {
-                    r.borrow_mut().right = Some(Rc::clone(nr));
-                    nr.borrow_mut().parent = Some(Rc::downgrade(&Rc::clone(r)))
-                  }
-                },
-              },
-            }
-          }
-        }
+      (Some(left), Some(right)) => {
+        // A node with two children is replaced by its in-order successor,
+        // which has no left child and leaves its own position instead
+        let successor = self.min_node(right);
+        removed_color = self.nodes[successor].color.clone();
+        let child = self.nodes[successor].right;
+        let parent = if successor == right {
+          successor
+        } else {
+          let parent = self.nodes[successor]
+            .parent
+            .expect("The successor lies below node");
+          self.transplant(successor, child);
+          self.nodes[successor].right = Some(right);
+          self.nodes[right].parent = Some(successor);
+          parent
+        };
+        self.transplant(node, Some(successor));
+        self.nodes[successor].left = Some(left);
+        self.nodes[left].parent = Some(successor);
+        self.nodes[successor].color = self.nodes[node].color.clone();
+        (child, Some(parent))
       }
+    };
 
-      match replace {
-        None => return,
-        Some(ref r) => {
-          match node_to_delete {
-            None => {}
-            Some(ref n) => {
-              r.borrow_mut().parent = match n.borrow().parent {
-                None => None,
-                Some(ref np) => match np.upgrade() {
-                  None => None,
-                  Some(ref npu) => Some(Rc::downgrade(&Rc::clone(npu))),
-                },
-              };
-              if n.borrow().color == NodeColor::Black {
-                r.borrow_mut().color = NodeColor::Black;
-              } else {
-                r.borrow_mut().color = NodeColor::Red;
-              }
-              r.borrow_mut().left = match n.borrow().left {
-                None => None,
-                Some(ref nl) => Some(Rc::clone(nl)),
-              };
-              match n.borrow().left {
-                None => {}
-                Some(ref nl) => nl.borrow_mut().parent = Some(Rc::downgrade(&Rc::clone(r))),
-              }
-            }
-          };
-        }
-      };
+    // Every ancestor of the removed position lost one node
+    self.update_subtrees_up(parent);
 
-      if color == NodeColor::Black {
-        match child {
-          None => return,
-          Some(ref c) => match parent {
-            None => return,
-            Some(ref p) => self.fix_delete(&mut Some(Rc::clone(c)), &mut Some(Rc::clone(p))),
-          },
-        }
-      }
-      return;
+    if removed_color == NodeColor::Black {
+      self.fix_delete(child, parent);
     }
 
-    let child: Child<T>;
-    if !node_left.is_none() {
-      child = node_left;
-    } else {
-      child = node_right;
-    }
-    let parent = match node_to_delete {
-      None => None,
-      Some(ref n) => match n.borrow().parent {
-        None => None,
-        Some(ref np) => match np.upgrade() {
-          None => None,
-          Some(ref npu) => Some(Rc::clone(npu)),
-        },
-      },
-    };
-    let color = match node_to_delete {
-      None => return,
-      Some(ref n) => {
-        if n.borrow().color == NodeColor::Black {
-          NodeColor::Black
-        } else {
-          NodeColor::Red
-        }
-      }
-    };
-    if !child.is_none() {
-      match child {
-        None => {}
-        Some(ref c) => match parent {
-          None => c.borrow_mut().parent = None,
-          Some(ref p) => c.borrow_mut().parent = Some(Rc::downgrade(&Rc::clone(p))),
-        },
-      };
-    }
-    if root_key == key {
-      self.root = match child {
-        None => None,
-        Some(ref c) => Some(Rc::clone(c)),
-      };
-    } else {
-      match parent {
-        None => return,
-        Some(ref p) => {
-          let mut found_key = false;
-          let pl_key;
-          match p.borrow().left {
-            None => {}
-            Some(ref pl) => {
-              pl_key = pl.borrow().key;
-              found_key = pl_key == key;
-            }
-          };
-          if found_key {
-            p.borrow_mut().left = match child {
-              None => None,
-              Some(ref c) => Some(Rc::clone(c)),
-            };
-          } else {
-            p.borrow_mut().right = match child {
-              None => None,
-              Some(ref c) => Some(Rc::clone(c)),
-            };
-          }
-        }
-      }
-    }
-    if color == NodeColor::Black {
-      // DELETE FIXUP CHILD PARENT
-      match child {
-        None => return,
-        Some(ref c) => match parent {
-          None => return,
-          Some(ref p) => self.fix_delete(&mut Some(Rc::clone(c)), &mut Some(Rc::clone(p))),
-        },
-      }
-    }
-    return;
+    let n = self.nodes.free(node);
+    (n.key, n.value)
   }
 
   /**
-   * Finds a node in the tree
+   * Borrowed handle to node, what the traversals walk
    **/
-  pub fn find(&mut self, key: T) -> Child<T> {
-    fn recurse<T: Ord + Copy>(node: &mut Child<T>, key: T) -> Child<T> {
-      if node.is_none() {
-        return None;
-      }
-      match node {
-        None => return None,
-        Some(ref n) => {
-          let nkey = n.borrow().key;
-          if nkey == key {
-            return Some(Rc::clone(n));
-          } else if nkey > key {
-            match n.borrow().left {
-              None => return None,
-              Some(ref nl) => return recurse(&mut Some(Rc::clone(nl)), key),
-            };
-          } else {
-            match n.borrow().right {
-              None => return None,
-              Some(ref nr) => return recurse(&mut Some(Rc::clone(nr)), key),
-            };
-          }
-        }
-      };
-    }
-    match self.root {
-      None => return None,
-      Some(ref r) => return recurse(&mut Some(Rc::clone(r)), key),
+  fn node_ref(&self, id: NodeId) -> NodeRef<'_, K, V, A> {
+    NodeRef {
+      nodes: &self.nodes,
+      id,
     }
   }
 
   /**
-   * Counts number of leaf nodes
+   * Borrows the root node for as long as the tree is borrowed
    **/
-  pub fn count_leaves(&self) -> i32 {
-    match self.root {
-      None => 0,
-      Some(ref root) => root.borrow().count_leaves(),
-    }
+  pub(crate) fn root_ref(&self) -> Option<NodeRef<'_, K, V, A>> {
+    self.root.map(|id| self.node_ref(id))
+  }
+
+  /**
+   * Finds the node holding key
+   **/
+  fn find_node<Q>(&self, key: &Q) -> Link
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    self.find_node_by(key, &Natural)
+  }
+
+  /**
+   * Finds the node holding key in the order of cmp
+   **/
+  fn find_node_by<Q, C>(&self, key: &Q, cmp: &C) -> Link
+  where
+    K: borrow::Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+  {
+    let mut current = self.root;
+    while let Some(n) = current {
+      let node = &self.nodes[n];
+      match cmp.compare(key, node.key.borrow()) {
+        Ordering::Equal => return Some(n),
+        Ordering::Less => current = node.left,
+        Ordering::Greater => current = node.right,
+      }
+    }
+    None
+  }
+
+  /**
+   * Returns a reference to the value of key
+   **/
+  pub fn get<Q>(&self, key: &Q) -> Option<&V>
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    self.find_node(key).map(|n| &self.nodes[n].value)
+  }
+
+  /**
+   * Returns if the key is in the tree
+   **/
+  pub fn contains_key<Q>(&self, key: &Q) -> bool
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    self.find_node(key).is_some()
+  }
+
+  /**
+   * Counts number of nodes
+   **/
+  pub fn count(&self) -> i32 {
+    self.len() as i32
+  }
+
+  /**
+   * Number of keys in the tree
+   **/
+  pub fn len(&self) -> usize {
+    self.nodes.len()
+  }
+
+  /**
+   * Counts number of leaf nodes
+   **/
+  pub fn count_leaves(&self) -> i32 {
+    let mut leaf_count = 0;
+    let mut stack: Vec<NodeId> = self.root.into_iter().collect();
+    while let Some(n) = stack.pop() {
+      let node = &self.nodes[n];
+      // Node has no children which means that it is a leaf
+      if node.left.is_none() && node.right.is_none() {
+        leaf_count += 1;
+      }
+      stack.extend(node.left);
+      stack.extend(node.right);
+    }
+    leaf_count
   }
 
   /**
    * Returns if the tree is empty or not
    **/
   pub fn is_empty(&self) -> bool {
-    match self.root {
-      None => true,
-      Some(_) => false,
-    }
+    self.root.is_none()
   }
 
   /**
    * Returns a inorder vector of the nodes
    **/
-  pub fn inorder_traversal(&self) -> Vec<T> {
-    match self.root {
-      None => vec![],
-      Some(ref root) => root.borrow().inorder_traversal(),
-    }
+  pub fn inorder_traversal(&self) -> Vec<K>
+  where
+    K: Clone,
+  {
+    self.iter().map(|(k, _)| k.clone()).collect()
   }
 
   /**
-      algorithmic idea drawn from https://www.baeldung.com/java-print-binary-tree-diagram
-  */
-  pub fn pretty_print(root: Child<T>) -> String {
-    match root {
-      None => {
-        return "".to_string();
-      }
-      Some(node) => {
-        let rc_sb = Rc::from(RefCell::from(String::from("")));
-        let n = node.borrow();
+   * Iterates over the entries in ascending key order, .rev() for descending
+   **/
+  pub fn iter(&self) -> Entries<InOrder<NodeRef<'_, K, V, A>>> {
+    Entries::new(InOrder::new(self.root_ref(), self.len()))
+  }
 
-        {
-          let mut sb = rc_sb.borrow_mut();
-          sb.push_str(n.key.clone().to_string().as_str());
+  /**
+   * Iterates over the entries, each before its left and then right subtree
+   **/
+  pub fn preorder(&self) -> Entries<PreOrder<NodeRef<'_, K, V, A>>> {
+    Entries::new(PreOrder::new(self.root_ref(), self.len()))
+  }
 
-          let color_string = match n.color {
-            NodeColor::Red => "R",
-            NodeColor::Black => "B",
-          };
+  /**
+   * Iterates over the entries, each after its left and then right subtree
+   **/
+  pub fn postorder(&self) -> Entries<PostOrder<NodeRef<'_, K, V, A>>> {
+    Entries::new(PostOrder::new(self.root_ref(), self.len()))
+  }
 
-          sb.push_str(color_string);
-        }
+  /**
+   * Iterates over the entries level by level starting at the root
+   **/
+  pub fn level_order(&self) -> Entries<LevelOrder<NodeRef<'_, K, V, A>>> {
+    Entries::new(LevelOrder::new(self.root_ref(), self.len()))
+  }
+  /**
+   * entries with keys inside range in ascending order, .rev() for descending
+   **/
+  pub fn range<Q, R>(&self, range: R) -> Entries<Range<NodeRef<'_, K, V, A>>>
+  where
+    K: Ord,
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+  {
+    Entries::new(Range::new(self.root_ref(), range))
+  }
 
-        let pointer_right = "└──";
-        let pointer_left;
+  /**
+   * number of keys inside range in O(log n), counted from the subtree sizes
+   **/
+  pub fn count_range<Q, R>(&self, range: R) -> usize
+  where
+    K: Ord,
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+  {
+    self.range(range).len()
+  }
 
-        if n.right.is_none() {
-          pointer_left = "└──";
-        } else {
-          pointer_left = "├──";
-        }
+  /**
+   * removes every key inside range, returns how many were removed
+   **/
+  pub fn remove_range<Q, R>(&mut self, range: R) -> usize
+  where
+    K: Ord,
+    K: borrow::Borrow<Q> + Clone,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+  {
+    let keys: Vec<K> = self.range(range).map(|(k, _)| k.clone()).collect();
+    for key in &keys {
+      self.remove::<K>(key);
+    }
+    keys.len()
+  }
 
-        Self::pretty_print_helper(
-          rc_sb.clone(),
-          "",
-          pointer_left,
-          n.left.clone(),
-          n.right.is_some(),
-        );
-        Self::pretty_print_helper(rc_sb.clone(), "", pointer_right, n.right.clone(), false);
+  /**
+   * Builds a tree from entries in strictly ascending key order in O(n), without
+   * any rotations or recoloring. Panics if the keys are out of order or repeat,
+   * collect() accepts entries in any order
+   **/
+  pub fn from_sorted_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self
+  where
+    K: Ord,
+  {
+    let entries: Vec<(K, V)> = iter.into_iter().collect();
+    assert!(
+      build::is_strictly_ascending(&entries),
+      "from_sorted_iter needs keys in strictly ascending order"
+    );
+    Self::from_sorted_entries(entries)
+  }
+
+  /**
+   * Moves the entries with keys from key onwards into a new tree, keeping the
+   * smaller ones. O(log n) plus moving the smaller of the two parts into an
+   * arena of its own
+   **/
+  pub fn split_off<Q>(&mut self, key: &Q) -> Self
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    let black_height = self.black_height(self.root);
+    let root = self.root.take();
+    let ((lower, _), (upper, _)) = self.split_nodes(root, black_height, key);
+    if self.size_of(lower) < self.size_of(upper) {
+      let lower = self.detach(lower);
+      self.root = upper;
+      std::mem::replace(self, lower)
+    } else {
+      self.root = lower;
+      self.detach(upper)
+    }
+  }
+
+  /**
+   * Splits the tree into the entries with keys smaller than key and the rest,
+   * see split_off
+   **/
+  pub fn split<Q>(mut self, key: &Q) -> (Self, Self)
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    let upper = self.split_off(key);
+    (self, upper)
+  }
+
+  /**
+   * Joins the entries of left, key with value and the entries of right into one
+   * tree in O(log n) plus moving the nodes of the smaller tree into the arena
+   * of the larger. Panics unless left < key < right for all of their keys
+   **/
+  pub fn join(left: Self, key: K, value: V, right: Self) -> Self
+  where
+    K: Ord,
+  {
+    assert!(
+      left.iter().next_back().is_none_or(|(max, _)| *max < key)
+        && right.iter().next().is_none_or(|(min, _)| key < *min),
+      "join needs every key of left below key and every key of right above it"
+    );
+    Self::join_trees(left, key, value, right)
+  }
+
+  /**
+   * Moves every entry of other into this tree, leaving other empty. Values from
+   * other replace those of equal keys. Takes O(log n) plus moving the nodes of
+   * the smaller tree when all keys of one tree are smaller than all keys of the
+   * other, otherwise the entries of other are inserted one by one
+   **/
+  pub fn append(&mut self, other: &mut Self)
+  where
+    K: Ord,
+  {
+    let other = std::mem::take(other);
+    let below = match (self.iter().next_back(), other.iter().next()) {
+      (Some((max, _)), Some((min, _))) => max < min,
+      _ => true,
+    };
+    let above = match (other.iter().next_back(), self.iter().next()) {
+      (Some((max, _)), Some((min, _))) => max < min,
+      _ => true,
+    };
 
-        return rc_sb.borrow_mut().clone();
+    if below {
+      let lower = std::mem::take(self);
+      *self = Self::concat(lower, other);
+    } else if above {
+      let upper = std::mem::take(self);
+      *self = Self::concat(other, upper);
+    } else {
+      for (key, value) in other {
+        self.insert(key, value);
       }
     }
   }
 
-  fn pretty_print_helper(
-    rc_sb: Rc<RefCell<String>>,
-    padding: &str,
-    pointer: &str,
-    node: Child<T>,
-    has_right: bool,
-  ) {
-    match node {
-      None => {}
-      Some(cur) => {
-        let n = cur.borrow();
-        {
-          let mut sb = rc_sb.borrow_mut();
-          sb.push_str("\n");
-          sb.push_str(padding);
-          sb.push_str(pointer);
-          sb.push_str(n.key.clone().to_string().as_str());
-
-          let color_string = match n.color {
-            NodeColor::Red => "R",
-            NodeColor::Black => "B",
-          };
-
-          sb.push_str(color_string);
-        }
+  /**
+   * Joins two trees where every key of lower is smaller than every key of upper
+   **/
+  fn concat(lower: Self, upper: Self) -> Self {
+    let mut upper = upper;
+    let min = match upper.root {
+      None => return lower,
+      Some(root) => upper.min_node(root),
+    };
+    let (key, value) = upper.remove_node(min);
+    Self::join_trees(lower, key, value, upper)
+  }
 
-        let mut psb = String::from(padding);
-        if has_right {
-          psb.push_str("│  ");
-        } else {
-          psb.push_str("   ");
-        }
+  /**
+   * Joins the trees left and right with key and value between them. The nodes
+   * of the smaller tree move into the arena of the larger
+   **/
+  fn join_trees(left: Self, key: K, value: V, right: Self) -> Self {
+    let (mut tree, lower, upper);
+    if left.len() >= right.len() {
+      tree = left;
+      lower = tree.root.take();
+      upper = tree.absorb(right);
+    } else {
+      tree = right;
+      upper = tree.root.take();
+      lower = tree.absorb(left);
+    }
+    let lower_height = tree.black_height(lower);
+    let upper_height = tree.black_height(upper);
+    let (root, _) = tree.join_nodes(lower, lower_height, key, value, upper, upper_height);
+    tree.root = root;
+    tree
+  }
+
+  /**
+   * Moves the subtree under node out of the arena from into this tree's arena
+   * below parent, returns the index of its root here
+   **/
+  fn adopt(&mut self, from: &mut Arena<TreeNode<K, V, A>>, node: Link, parent: Link) -> Link {
+    let mut n = from.free(node?);
+    let (left, right) = (n.left.take(), n.right.take());
+    n.parent = parent;
+    let id = self.nodes.alloc(n);
+    let left = self.adopt(from, left, Some(id));
+    let right = self.adopt(from, right, Some(id));
+    self.nodes[id].left = left;
+    self.nodes[id].right = right;
+    Some(id)
+  }
 
-        let both_pad = String::from(psb);
-        let pointer_right = "└──";
-        let pointer_left;
+  /**
+   * Moves the subtree under node into a tree of its own
+   **/
+  fn detach(&mut self, node: Link) -> Self {
+    let mut detached = Self {
+      nodes: Arena::with_capacity(self.size_of(node)),
+      root: None,
+    };
+    detached.root = detached.adopt(&mut self.nodes, node, None);
+    detached
+  }
 
-        if n.right.is_none() {
-          pointer_left = "└──";
-        } else {
-          pointer_left = "├──";
-        }
+  /**
+   * Moves every node of other into this tree's arena, returns the root they hang from
+   **/
+  fn absorb(&mut self, mut other: Self) -> Link {
+    let root = other.root.take();
+    self.adopt(&mut other.nodes, root, None)
+  }
+
+  /**
+   * Builds a tree from entries in strictly ascending key order, see build_sorted
+   **/
+  pub(crate) fn from_sorted_entries(entries: Vec<(K, V)>) -> Self {
+    let len = entries.len();
+    // Every level above this one is full, the nodes on it are colored red
+    let red_depth = (usize::BITS - (len + 1).leading_zeros() - 1) as usize;
+    let mut tree = Self {
+      nodes: Arena::with_capacity(len),
+      root: None,
+    };
+    tree.root = tree.build_sorted(&mut entries.into_iter(), len, 0, red_depth);
+    tree
+  }
+
+  /**
+   * Builds a subtree from the next n entries, which must be in strictly ascending
+   * key order. Halving the count at every level fills every level of the
+   * subtree but the deepest one, so with the nodes on that level red every path
+   * down holds the same number of black nodes and no red node has a red child
+   **/
+  fn build_sorted<I>(&mut self, entries: &mut I, n: usize, depth: usize, red_depth: usize) -> Link
+  where
+    I: Iterator<Item = (K, V)>,
+  {
+    if n == 0 {
+      return None;
+    }
+    let left = self.build_sorted(entries, n / 2, depth + 1, red_depth);
+    let (key, value) = entries.next().expect("fewer entries than the count given");
+    let right = self.build_sorted(entries, n - n / 2 - 1, depth + 1, red_depth);
+
+    let node = self.new_node(key, value);
+    if depth != red_depth {
+      self.nodes[node].color = NodeColor::Black;
+    }
+    self.set_children(node, left, right);
+    self.update_subtree(node);
+    Some(node)
+  }
+
+  /**
+   * Checks every invariant of the tree: keys in order, stored sizes right, a
+   * black root, no red node under a red one, the same number of black nodes on
+   * every path down, parent links leading to the real parent and len matching
+   * the nodes. Each broken invariant is reported with the key and path of the
+   * node that breaks it. Len is the number of nodes in the arena, so a node the
+   * root cannot reach shows up as a difference between the two
+   **/
+  pub fn validate(&self) -> Report<'_, K>
+  where
+    K: Ord,
+  {
+    self.validate_with(DuplicatePolicy::Reject, &Natural)
+  }
 
-        Self::pretty_print_helper(
-          rc_sb.clone(),
-          &*both_pad,
-          pointer_left,
-          n.left.clone(),
-          n.right.is_some(),
-        );
-
-        Self::pretty_print_helper(
-          rc_sb.clone(),
-          &*both_pad,
-          pointer_right,
-          n.right.clone(),
-          false,
-        );
+  /**
+   * Validates the tree in the order of cmp, where keys may repeat if
+   * duplicates is Count
+   **/
+  fn validate_with<C: Comparator<K>>(&self, duplicates: DuplicatePolicy, cmp: &C) -> Report<'_, K> {
+    let mut problems = Vec::new();
+    if let Some(root) = self.root {
+      let root = &self.nodes[root];
+      if root.color == NodeColor::Red {
+        problems.push(Problem {
+          key: &root.key,
+          path: Vec::new(),
+          violation: Violation::RedRoot,
+        });
       }
     }
+    let (_, nodes) = self.validate_node(
+      self.root,
+      None,
+      cmp,
+      duplicates,
+      &mut Vec::new(),
+      None,
+      None,
+      &mut problems,
+    );
+    Report {
+      len: self.len(),
+      nodes,
+      problems,
+    }
   }
-  pub fn get_height(&self) -> i32 {
-    match self.root {
-      None => 1,
-      Some(ref root) => root.borrow().get_height(),
+
+  /**
+   * Checks the subtree under node, which hangs below parent and whose keys must
+   * lie between lower and upper in the order of cmp, and adds every invariant it
+   * breaks to problems. Returns the black height and the actual size of the subtree
+   **/
+  #[allow(clippy::too_many_arguments)]
+  fn validate_node<'a, C: Comparator<K>>(
+    &'a self,
+    node: Link,
+    parent: Link,
+    cmp: &C,
+    duplicates: DuplicatePolicy,
+    path: &mut Vec<Side>,
+    lower: Option<&'a K>,
+    upper: Option<&'a K>,
+    problems: &mut Vec<Problem<'a, K>>,
+  ) -> (usize, usize) {
+    let (id, n) = match node {
+      None => return (0, 0),
+      Some(id) => (id, &self.nodes[id]),
+    };
+    // The problems of node go in front of those of its subtrees
+    let at = problems.len();
+    path.push(Side::Left);
+    let (left_black, left_size) = self.validate_node(
+      n.left,
+      Some(id),
+      cmp,
+      duplicates,
+      path,
+      lower,
+      Some(&n.key),
+      problems,
+    );
+    path.pop();
+    path.push(Side::Right);
+    let (right_black, right_size) = self.validate_node(
+      n.right,
+      Some(id),
+      cmp,
+      duplicates,
+      path,
+      Some(&n.key),
+      upper,
+      problems,
+    );
+    path.pop();
+
+    let size = 1 + left_size + right_size;
+    let mut found = Vec::new();
+    if !validate::in_bounds(&n.key, lower, upper, duplicates, cmp) {
+      found.push(Violation::OutOfOrder);
+    }
+    if n.size != size {
+      found.push(Violation::WrongSize {
+        stored: n.size,
+        actual: size,
+      });
+    }
+    let left = n.left.map(|l| &self.nodes[l].aggregate);
+    let right = n.right.map(|r| &self.nodes[r].aggregate);
+    if n.aggregate != augment::summarize(&n.key, &n.value, left, right) {
+      found.push(Violation::WrongAggregate);
+    }
+    if n.color == NodeColor::Red && self.color_of(parent) == NodeColor::Red {
+      found.push(Violation::RedUnderRed);
+    }
+    if left_black != right_black {
+      found.push(Violation::BlackHeightMismatch {
+        left: left_black,
+        right: right_black,
+      });
+    }
+    if n.parent != parent {
+      found.push(Violation::WrongParent);
+    }
+    problems.splice(
+      at..at,
+      found.into_iter().map(|violation| Problem {
+        key: &n.key,
+        path: path.clone(),
+        violation,
+      }),
+    );
+
+    let black = match n.color {
+      NodeColor::Red => 0,
+      NodeColor::Black => 1,
+    };
+    (black + left_black.max(right_black), size)
+  }
+
+  /**
+   * Writes the tree to writer in the binary snapshot format of
+   * tree_core::snapshot: a header, then every node in pre-order with its color
+   * and which children follow it, so the exact shape is kept
+   **/
+  pub fn save_to<W: Write>(&self, writer: W) -> Result<(), SnapshotError>
+  where
+    K: Codec,
+    V: Codec,
+  {
+    self.save_with(writer, DuplicatePolicy::Reject)
+  }
+
+  /**
+   * Writes the tree with duplicates as the policy in the header
+   **/
+  fn save_with<W: Write>(
+    &self,
+    mut writer: W,
+    duplicates: DuplicatePolicy,
+  ) -> Result<(), SnapshotError>
+  where
+    K: Codec,
+    V: Codec,
+  {
+    let header = Header {
+      len: self.len(),
+      duplicates,
+    };
+    snapshot::write_header(&mut writer, TreeKind::RedBlack, header)?;
+    if let Some(root) = self.root {
+      self.save_node(root, &mut writer)?;
     }
+    writer.flush()?;
+    Ok(())
   }
-}
 
-impl<T: Ord + Copy> TreeNode<T> {
-  fn new(key: T) -> TreeNode<T> {
-    return TreeNode {
-      color: NodeColor::Red,
-      key: key,
-      parent: None,
-      left: None,
-      right: None,
+  /**
+   * Writes node and then its subtrees in pre-order, each node as its tag, key and value
+   **/
+  fn save_node<W: Write>(&self, node: NodeId, writer: &mut W) -> Result<(), SnapshotError>
+  where
+    K: Codec,
+    V: Codec,
+  {
+    let node = &self.nodes[node];
+    let tag = NodeTag {
+      has_left: node.left.is_some(),
+      has_right: node.right.is_some(),
+      red: node.color == NodeColor::Red,
     };
+    tag.write(writer)?;
+    node.key.encode(writer)?;
+    node.value.encode(writer)?;
+    for child in [node.left, node.right].iter().copied().flatten() {
+      self.save_node(child, writer)?;
+    }
+    Ok(())
   }
 
-  fn count_leaves(&self) -> i32 {
-    // Node has no children which means that it is a leaf
-    if self.left.is_none() && self.right.is_none() {
-      return 1;
+  /**
+   * Reads a tree written by save_to. The tree is rejected unless its root is
+   * black, no red node has a red child, every path down holds the same number of
+   * black nodes and the keys ascend. Truncated or corrupt input is an error,
+   * never a panic
+   **/
+  pub fn load_from<R: Read>(reader: R) -> Result<Self, SnapshotError>
+  where
+    K: Ord + Codec,
+    V: Codec,
+  {
+    let (tree, duplicates) = Self::load_with(reader)?;
+    if duplicates == DuplicatePolicy::Count {
+      return snapshot::corrupt("a map cannot hold duplicate keys");
     }
+    Ok(tree)
+  }
 
-    let mut leaf_count = 0;
+  /**
+   * Reads a tree written by save_with and returns it with the policy from the
+   * header. Keys must ascend strictly unless the policy is Count
+   **/
+  fn load_with<R: Read>(mut reader: R) -> Result<(Self, DuplicatePolicy), SnapshotError>
+  where
+    K: Ord + Codec,
+    V: Codec,
+  {
+    let Header { len, duplicates } = snapshot::read_header(&mut reader, TreeKind::RedBlack)?;
+    let mut remaining = len;
+    let mut tree = Self::default();
+    tree.root = match len {
+      0 => None,
+      _ => Some(tree.load_node(&mut reader, &mut remaining, 1)?.0),
+    };
+    if remaining != 0 {
+      return snapshot::corrupt("fewer nodes than the header counts");
+    }
+    if tree.color_of(tree.root) == NodeColor::Red {
+      return snapshot::corrupt("root is red");
+    }
+
+    let mut keys = tree.iter().map(|(key, _)| key);
+    let mut prev = keys.next();
+    for key in keys {
+      let ordered = match duplicates {
+        DuplicatePolicy::Count => prev.is_none_or(|p| p <= key),
+        _ => prev.is_none_or(|p| p < key),
+      };
+      if !ordered {
+        return snapshot::corrupt("keys are not in ascending order");
+      }
+      prev = Some(key);
+    }
+    Ok((tree, duplicates))
+  }
 
-    // If the left child isn't none, traverse into it
-    if let Some(ref left) = self.left {
-      leaf_count += left.borrow().count_leaves();
+  /**
+   * Reads back a subtree written by save_node and returns it with its black
+   * height. At most remaining nodes are read, and the subtree is rejected if a
+   * red node has a red child or its two sides differ in black height
+   **/
+  fn load_node<R: Read>(
+    &mut self,
+    reader: &mut R,
+    remaining: &mut usize,
+    depth: usize,
+  ) -> Result<(NodeId, usize), SnapshotError>
+  where
+    K: Codec,
+    V: Codec,
+  {
+    if depth > snapshot::MAX_DEPTH {
+      return snapshot::corrupt("tree is deeper than any balanced tree");
     }
+    if *remaining == 0 {
+      return snapshot::corrupt("more nodes than the header counts");
+    }
+    *remaining -= 1;
+
+    let tag = NodeTag::read(reader)?;
+    let key = K::decode(reader)?;
+    let value = V::decode(reader)?;
+    let (left, left_height) = match tag.has_left {
+      true => {
+        let (l, h) = self.load_node(reader, remaining, depth + 1)?;
+        (Some(l), h)
+      }
+      false => (None, 0),
+    };
+    let (right, right_height) = match tag.has_right {
+      true => {
+        let (r, h) = self.load_node(reader, remaining, depth + 1)?;
+        (Some(r), h)
+      }
+      false => (None, 0),
+    };
 
-    // If the right child isn't none, traverse into it
-    if let Some(ref right) = self.right {
-      leaf_count += right.borrow().count_leaves();
+    if left_height != right_height {
+      return snapshot::corrupt("paths differ in black height");
+    }
+    let color = match tag.red {
+      true => NodeColor::Red,
+      false => NodeColor::Black,
+    };
+    if color == NodeColor::Red
+      && (self.color_of(left) == NodeColor::Red || self.color_of(right) == NodeColor::Red)
+    {
+      return snapshot::corrupt("red node has a red child");
     }
+    let black_height = match color {
+      NodeColor::Red => left_height,
+      NodeColor::Black => left_height + 1,
+    };
 
-    leaf_count
+    let node = self.new_node(key, value);
+    self.nodes[node].color = color;
+    self.set_children(node, left, right);
+    self.update_subtree(node);
+    Ok((node, black_height))
   }
 
-  fn inorder_traversal(&self) -> Vec<T> {
-    let mut node_values = vec![];
+  /**
+   * Joins left, a new node for key and value, and right into one tree and
+   * returns its root and black height. Every key of left must be smaller than
+   * key and every key of right larger, and both must be detached subtrees in
+   * this tree's arena. The node goes down the spine of the side with the larger
+   * black height to a black node with the black height of the other side,
+   * takes its place as a red node and the insertion fixup repairs any red node
+   * with a red parent, so the cost is the difference in black height
+   **/
+  fn join_nodes(
+    &mut self,
+    left: Link,
+    left_height: usize,
+    key: K,
+    value: V,
+    right: Link,
+    right_height: usize,
+  ) -> Piece {
+    // Both sides need a black root, which can add one to their black height
+    let left_height = left_height + self.blacken(left);
+    let right_height = right_height + self.blacken(right);
+    let node = self.new_node(key, value);
+
+    if left_height == right_height {
+      self.set_children(node, left, right);
+      self.nodes[node].color = NodeColor::Black;
+      self.update_subtree(node);
+      return (Some(node), left_height + 1);
+    }
+
+    let taller_left = left_height > right_height;
+    let (taller, shorter, shorter_height) = if taller_left {
+      (left, right, right_height)
+    } else {
+      (right, left, left_height)
+    };
+
+    // Walk down the inner spine to the first black node with the black height
+    // of the shorter side, the parent always exists since the taller root has
+    // a larger black height
+    let mut parent = taller.expect("The taller side of a join is never empty");
+    let mut height = left_height.max(right_height);
+    let spot = loop {
+      let child = if taller_left {
+        self.nodes[parent].right
+      } else {
+        self.nodes[parent].left
+      };
+      if self.nodes[parent].color == NodeColor::Black {
+        height -= 1;
+      }
+      match child {
+        Some(c) if self.nodes[c].color == NodeColor::Red || height > shorter_height => {
+          parent = c;
+        }
+        _ => break child,
+      }
+    };
 
-    // If the left child isn't none, traverse into it
-    if let Some(ref left) = self.left {
-      node_values.append(&mut left.borrow().inorder_traversal());
+    // The new node takes the place of spot with spot and the shorter side below it
+    if taller_left {
+      self.set_children(node, spot, shorter);
+      self.nodes[parent].right = Some(node);
+    } else {
+      self.set_children(node, shorter, spot);
+      self.nodes[parent].left = Some(node);
     }
+    self.nodes[node].parent = Some(parent);
+    self.update_subtrees_up(Some(node));
+
+    // The fixup rotates around the root of the taller side, which stands in
+    // for the root of the tree until it is done
+    let root = std::mem::replace(&mut self.root, taller);
+    self.check_property(node);
+    let joined = std::mem::replace(&mut self.root, root);
+
+    // The fixup can leave the root red, turning it black adds one to the black height
+    let mut height = left_height.max(right_height);
+    height += self.blacken(joined);
+    (joined, height)
+  }
+
+  /**
+   * Splits the tree under node with the given black height into the keys
+   * smaller than key and the rest, each with its black height. The nodes on
+   * the path to key are taken apart and the subtrees hanging off it are joined
+   * back together on the way up
+   **/
+  fn split_nodes<Q>(&mut self, node: Link, height: usize, key: &Q) -> (Piece, Piece)
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    let n = match node {
+      None => return ((None, 0), (None, 0)),
+      Some(n) => self.nodes.free(n),
+    };
 
-    // Push the current nodes values
-    node_values.push(self.key);
+    // The children become trees of their own
+    let child_height = match n.color {
+      NodeColor::Black => height - 1,
+      NodeColor::Red => height,
+    };
+    for c in [n.left, n.right].iter().copied().flatten() {
+      self.nodes[c].parent = None;
+    }
 
-    // If the right child isn't none, traverse into it
-    if let Some(ref right) = self.right {
-      node_values.append(&mut right.borrow().inorder_traversal());
+    if key.cmp(n.key.borrow()) == Ordering::Greater {
+      let (lower, upper) = self.split_nodes(n.right, child_height, key);
+      let lower = self.join_nodes(n.left, child_height, n.key, n.value, lower.0, lower.1);
+      (lower, upper)
+    } else {
+      let (lower, upper) = self.split_nodes(n.left, child_height, key);
+      let upper = self.join_nodes(upper.0, upper.1, n.key, n.value, n.right, child_height);
+      (lower, upper)
     }
+  }
+
+  /**
+   * Takes the tree apart into its entries in ascending key order
+   **/
+  fn into_entries(mut self) -> Vec<(K, V)> {
+    let order: Vec<NodeId> = InOrder::new(self.root_ref(), self.len())
+      .map(|n| n.id)
+      .collect();
+    order
+      .into_iter()
+      .map(|id| {
+        let n = self.nodes.free(id);
+        (n.key, n.value)
+      })
+      .collect()
+  }
+
+  /**
+   * Number of keys smaller than key, whether or not key is in the tree
+   **/
+  pub fn rank<Q>(&self, key: &Q) -> usize
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    order::rank(self.root_ref(), key)
+  }
+
+  /**
+   * The entry with the k-th smallest key counting from 1, None if k is 0 or past the end
+   **/
+  pub fn select(&self, k: usize) -> Option<(&K, &V)> {
+    k.checked_sub(1).and_then(|n| self.nth(n))
+  }
+
+  /**
+   * The entry at position n in ascending order counting from 0, like iter().nth(n)
+   **/
+  pub fn nth(&self, n: usize) -> Option<(&K, &V)> {
+    order::nth(self.root_ref(), n).map(NodeHandle::entry)
+  }
+
+  /**
+   * The entry in the middle, the lower one of the two middle entries for an even count
+   **/
+  pub fn median(&self) -> Option<(&K, &V)> {
+    self
+      .len()
+      .checked_sub(1)
+      .and_then(|last| self.nth(last / 2))
+  }
+
+  /**
+   * The entry at the p-th percentile by the nearest rank method, p goes from 0 to 100
+   **/
+  pub fn percentile(&self, p: f64) -> Option<(&K, &V)> {
+    order::percentile_index(self.len(), p).and_then(|n| self.nth(n))
+  }
+
+  /**
+   * Aggregate of every entry in the tree, None if it is empty
+   **/
+  pub fn aggregate(&self) -> Option<&A> {
+    self.root.map(|root| &self.nodes[root].aggregate)
+  }
+
+  /**
+   * Aggregate of the entries with keys inside range, None if there are none
+   **/
+  pub fn fold_range<Q, R>(&self, range: R) -> Option<A>
+  where
+    K: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+  {
+    augment::fold_range(self.root_ref(), range)
+  }
 
-    node_values
+  /**
+   * Renders the tree sideways with each key followed by its color
+   **/
+  pub fn pretty_print(&self) -> String
+  where
+    K: Display,
+  {
+    pretty::pretty_print(
+      self.root,
+      |&n| {
+        let node = &self.nodes[n];
+        let color_string = match node.color {
+          NodeColor::Red => "R",
+          NodeColor::Black => "B",
+        };
+        format!("{}{}", node.key, color_string)
+      },
+      |&n| (self.nodes[n].left, self.nodes[n].right),
+    )
   }
 
   pub fn get_height(&self) -> i32 {
-    let left_height = match self.left {
-      None => 0,
-      Some(ref left) => left.borrow().get_height(),
-    };
+    self.height_of(self.root)
+  }
 
-    let right_height = match self.right {
+  fn height_of(&self, node: Link) -> i32 {
+    match node {
       None => 0,
-      Some(ref right) => right.borrow().get_height(),
-    };
+      Some(n) => {
+        max(
+          self.height_of(self.nodes[n].left),
+          self.height_of(self.nodes[n].right),
+        ) + 1
+      }
+    }
+  }
 
-    max(left_height, right_height) + 1
+  /**
+   * Color of a link, empty links count as black
+   **/
+  fn color_of(&self, node: Link) -> NodeColor {
+    match node {
+      None => NodeColor::Black,
+      Some(n) => self.nodes[n].color.clone(),
+    }
+  }
+
+  /**
+   * Sets the color of a link, does nothing for empty links
+   **/
+  fn set_color(&mut self, node: Link, color: NodeColor) {
+    if let Some(n) = node {
+      self.nodes[n].color = color;
+    }
+  }
+
+  /**
+   * Gets the node with the smallest key in the subtree rooted at node
+   **/
+  fn min_node(&self, node: NodeId) -> NodeId {
+    let mut node = node;
+    while let Some(left) = self.nodes[node].left {
+      node = left;
+    }
+    node
+  }
+
+  /**
+   * Gets the node with the largest key in the subtree rooted at node
+   **/
+  fn max_node(&self, node: NodeId) -> NodeId {
+    let mut node = node;
+    while let Some(right) = self.nodes[node].right {
+      node = right;
+    }
+    node
+  }
+
+  /**
+   * Number of black nodes on each path from node down to an empty child
+   **/
+  fn black_height(&self, node: Link) -> usize {
+    let mut height = 0;
+    let mut current = node;
+    while let Some(n) = current {
+      if self.nodes[n].color == NodeColor::Black {
+        height += 1;
+      }
+      current = self.nodes[n].left;
+    }
+    height
+  }
+
+  /**
+   * Colors the root of a subtree black, returns 1 if that raised its black height
+   **/
+  fn blacken(&mut self, node: Link) -> usize {
+    match node {
+      Some(n) if self.nodes[n].color == NodeColor::Red => {
+        self.nodes[n].color = NodeColor::Black;
+        1
+      }
+      _ => 0,
+    }
+  }
+
+  /**
+   * Links left and right below node in both directions
+   **/
+  fn set_children(&mut self, node: NodeId, left: Link, right: Link) {
+    for c in [left, right].iter().copied().flatten() {
+      self.nodes[c].parent = Some(node);
+    }
+    self.nodes[node].left = left;
+    self.nodes[node].right = right;
+  }
+
+  /**
+   * Number of nodes in the subtree of a link, 0 for empty links
+   **/
+  fn size_of(&self, node: Link) -> usize {
+    match node {
+      None => 0,
+      Some(n) => self.nodes[n].size,
+    }
+  }
+
+  /**
+   * Recomputes the size and aggregate of node from its children
+   **/
+  fn update_subtree(&mut self, node: NodeId) {
+    let n = &self.nodes[node];
+    let left = n.left.map(|l| &self.nodes[l]);
+    let right = n.right.map(|r| &self.nodes[r]);
+    let aggregate = augment::summarize(
+      &n.key,
+      &n.value,
+      left.map(|l| &l.aggregate),
+      right.map(|r| &r.aggregate),
+    );
+    let size = 1 + self.size_of(n.left) + self.size_of(n.right);
+    let n = &mut self.nodes[node];
+    n.size = size;
+    n.aggregate = aggregate;
+  }
+
+  /**
+   * Recomputes the sizes and aggregates of node and each of its ancestors up to the root
+   **/
+  fn update_subtrees_up(&mut self, node: Link) {
+    let mut current = node;
+    while let Some(n) = current {
+      self.update_subtree(n);
+      current = self.nodes[n].parent;
+    }
+  }
+}
+
+impl<K, V, A> Default for RBTreeMap<K, V, A>
+where
+  A: Aggregate<K, V>,
+{
+  fn default() -> Self {
+    Self {
+      nodes: Arena::new(),
+      root: None,
+    }
+  }
+}
+
+/**
+ * Copies the arena, so the clone and the original never see each other's changes
+ **/
+impl<K, V, A> Clone for RBTreeMap<K, V, A>
+where
+  K: Clone,
+  V: Clone,
+  A: Aggregate<K, V>,
+{
+  fn clone(&self) -> Self {
+    Self {
+      nodes: self.nodes.clone(),
+      root: self.root,
+    }
+  }
+}
+
+impl<T> RBTree<T>
+where
+  T: Ord,
+{
+  /**
+   * Creates a new empty tree, trees with an aggregate are made with default()
+   **/
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /**
+   * Creates a new empty multiset, a tree that keeps every copy of a key
+   * inserted more than once
+   **/
+  pub fn multiset() -> Self {
+    Self::with_duplicates(DuplicatePolicy::Count)
+  }
+}
+
+/**
+ * A tree ordered by a comparator instead of Ord, see tree_core::compare
+ **/
+impl<T, C> RBTree<T, (), C>
+where
+  C: Comparator<T>,
+{
+  /**
+   * Creates a new empty tree ordered by cmp, e.g.
+   * RBTree::with_comparator(|a: &i32, b: &i32| b.cmp(a)) for a descending one
+   **/
+  pub fn with_comparator(cmp: C) -> Self {
+    Self::with_duplicates_and_comparator(DuplicatePolicy::default(), cmp)
+  }
+}
+
+/**
+ * Methods for any aggregate and comparator. Lookups on a tree with a
+ * comparator take a &T, as the comparator only knows how to order those
+ **/
+impl<T, A, C> RBTree<T, A, C>
+where
+  A: Aggregate<T, ()>,
+  C: Comparator<T>,
+{
+  /**
+   * Creates a new empty tree ordered by cmp that handles a key inserted again
+   * the way duplicates says
+   **/
+  pub fn with_duplicates_and_comparator(duplicates: DuplicatePolicy, cmp: C) -> Self {
+    Self {
+      map: RBTreeMap::default(),
+      duplicates,
+      cmp,
+    }
+  }
+
+  /**
+   * Returns what insert does with a key that is already in the tree
+   **/
+  pub fn duplicates(&self) -> DuplicatePolicy {
+    self.duplicates
+  }
+
+  /**
+   * Insert a key into the tree, returning whether it was added as a new entry.
+   * A key that is already in the tree is rejected, replaced or added again as
+   * the duplicate policy says, only the last counts as new
+   **/
+  pub fn insert(&mut self, key: T) -> bool {
+    match self.duplicates {
+      DuplicatePolicy::Count => {
+        self.map.insert_new(key, (), &self.cmp);
+        true
+      }
+      policy => match self.map.find_node_by(&key, &self.cmp) {
+        None => {
+          self.map.insert_new(key, (), &self.cmp);
+          true
+        }
+        Some(node) => {
+          if policy == DuplicatePolicy::Replace {
+            self.map.nodes[node].key = key;
+            // The aggregates from the node up depend on the key
+            self.map.update_subtrees_up(Some(node));
+          }
+          false
+        }
+      },
+    }
+  }
+
+  /**
+   * Deletes a node from the tree, returning the key it held or None if key is
+   * not in the tree. Takes out one copy of a key stored more than once
+   **/
+  pub fn delete<Q>(&mut self, key: &Q) -> Option<T>
+  where
+    T: borrow::Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+  {
+    let node = self.map.find_node_by(key, &self.cmp)?;
+    Some(self.map.remove_node(node).0)
+  }
+
+  /**
+   * Returns how many copies of key the tree holds, at most 1 unless it keeps
+   * duplicates. O(log n)
+   **/
+  pub fn count_of<Q>(&self, key: &Q) -> usize
+  where
+    T: borrow::Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+  {
+    order::count_by(self.map.root_ref(), key, &self.cmp)
+  }
+
+  /**
+   * Deletes one copy of key, same as delete
+   **/
+  pub fn remove_one<Q>(&mut self, key: &Q) -> Option<T>
+  where
+    T: borrow::Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+  {
+    self.delete(key)
+  }
+
+  /**
+   * Deletes every copy of key, returning how many there were
+   **/
+  pub fn remove_all<Q>(&mut self, key: &Q) -> usize
+  where
+    T: borrow::Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+  {
+    let mut removed = 0;
+    while self.delete(key).is_some() {
+      removed += 1;
+    }
+    removed
+  }
+
+  /**
+   * Finds a key in the tree
+   **/
+  pub fn find<Q>(&self, key: &Q) -> Option<&T>
+  where
+    T: borrow::Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+  {
+    self
+      .map
+      .find_node_by(key, &self.cmp)
+      .map(|n| &self.map.nodes[n].key)
+  }
+
+  /**
+   * Returns the smallest key, None if the tree is empty. O(log n)
+   **/
+  pub fn first(&self) -> Option<&T> {
+    order::first(self.map.root_ref()).map(|n| n.entry().0)
+  }
+
+  /**
+   * Returns the largest key, None if the tree is empty. O(log n)
+   **/
+  pub fn last(&self) -> Option<&T> {
+    order::last(self.map.root_ref()).map(|n| n.entry().0)
+  }
+
+  /**
+   * Returns the largest key less than or equal to key, whether or not key is
+   * in the tree
+   **/
+  pub fn floor<Q>(&self, key: &Q) -> Option<&T>
+  where
+    T: borrow::Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+  {
+    order::floor_by(self.map.root_ref(), key, &self.cmp).map(|n| n.entry().0)
+  }
+
+  /**
+   * Returns the smallest key greater than or equal to key, whether or not key
+   * is in the tree
+   **/
+  pub fn ceiling<Q>(&self, key: &Q) -> Option<&T>
+  where
+    T: borrow::Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+  {
+    order::ceiling_by(self.map.root_ref(), key, &self.cmp).map(|n| n.entry().0)
+  }
+
+  /**
+   * Returns the largest key strictly less than key, whether or not key is in
+   * the tree
+   **/
+  pub fn predecessor<Q>(&self, key: &Q) -> Option<&T>
+  where
+    T: borrow::Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+  {
+    order::predecessor_by(self.map.root_ref(), key, &self.cmp).map(|n| n.entry().0)
+  }
+
+  /**
+   * Returns the smallest key strictly greater than key, whether or not key is
+   * in the tree
+   **/
+  pub fn successor<Q>(&self, key: &Q) -> Option<&T>
+  where
+    T: borrow::Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+  {
+    order::successor_by(self.map.root_ref(), key, &self.cmp).map(|n| n.entry().0)
+  }
+
+  /**
+   * Deletes the smallest key and returns it, None if the tree is empty. O(log n)
+   **/
+  pub fn pop_first(&mut self) -> Option<T> {
+    let node = self.map.min_node(self.map.root?);
+    Some(self.map.remove_node(node).0)
+  }
+
+  /**
+   * Deletes the largest key and returns it, None if the tree is empty. O(log n)
+   **/
+  pub fn pop_last(&mut self) -> Option<T> {
+    let node = self.map.max_node(self.map.root?);
+    Some(self.map.remove_node(node).0)
+  }
+
+  /**
+   * Counts number of nodes
+   **/
+  pub fn count(&self) -> i32 {
+    self.map.count()
+  }
+
+  /**
+   * Counts number of leaf nodes
+   **/
+  pub fn count_leaves(&self) -> i32 {
+    self.map.count_leaves()
+  }
+
+  /**
+   * Returns if the tree is empty or not
+   **/
+  pub fn is_empty(&self) -> bool {
+    self.map.is_empty()
+  }
+
+  /**
+   * Returns a inorder vector of the nodes
+   **/
+  pub fn inorder_traversal(&self) -> Vec<T>
+  where
+    T: Clone,
+  {
+    self.map.inorder_traversal()
+  }
+
+  /**
+   * Iterates over the keys in ascending order, .rev() for descending
+   **/
+  pub fn iter(&self) -> Keys<InOrder<NodeRef<'_, T, (), A>>> {
+    Keys::new(InOrder::new(self.map.root_ref(), self.map.len()))
+  }
+
+  /**
+   * Iterates over the keys, each before its left and then right subtree
+   **/
+  pub fn preorder(&self) -> Keys<PreOrder<NodeRef<'_, T, (), A>>> {
+    Keys::new(PreOrder::new(self.map.root_ref(), self.map.len()))
+  }
+
+  /**
+   * Iterates over the keys, each after its left and then right subtree
+   **/
+  pub fn postorder(&self) -> Keys<PostOrder<NodeRef<'_, T, (), A>>> {
+    Keys::new(PostOrder::new(self.map.root_ref(), self.map.len()))
+  }
+
+  /**
+   * Iterates over the keys level by level starting at the root
+   **/
+  pub fn level_order(&self) -> Keys<LevelOrder<NodeRef<'_, T, (), A>>> {
+    Keys::new(LevelOrder::new(self.map.root_ref(), self.map.len()))
+  }
+
+  /**
+   * Checks every invariant of the tree, see RBTreeMap::validate. Equal keys
+   * are only in order if the tree keeps duplicates
+   **/
+  pub fn validate(&self) -> Report<'_, T> {
+    self.map.validate_with(self.duplicates, &self.cmp)
+  }
+
+  /**
+   * Number of keys smaller than key, whether or not key is in the tree
+   **/
+  pub fn rank<Q>(&self, key: &Q) -> usize
+  where
+    T: borrow::Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+  {
+    order::rank_by(self.map.root_ref(), key, &self.cmp)
+  }
+
+  /**
+   * The key with the k-th smallest key counting from 1, None if k is 0 or past the end
+   **/
+  pub fn select(&self, k: usize) -> Option<&T> {
+    k.checked_sub(1).and_then(|n| self.nth(n))
+  }
+
+  /**
+   * The key at position n in ascending order counting from 0, like iter().nth(n)
+   **/
+  pub fn nth(&self, n: usize) -> Option<&T> {
+    self.map.nth(n).map(|(key, _)| key)
+  }
+
+  /**
+   * The key in the middle, the lower one of the two middle keys for an even count
+   **/
+  pub fn median(&self) -> Option<&T> {
+    self.map.median().map(|(key, _)| key)
+  }
+
+  /**
+   * The key at the p-th percentile by the nearest rank method, p goes from 0 to 100
+   **/
+  pub fn percentile(&self, p: f64) -> Option<&T> {
+    self.map.percentile(p).map(|(key, _)| key)
+  }
+
+  /**
+   * Aggregate of every key in the tree, None if it is empty
+   **/
+  pub fn aggregate(&self) -> Option<&A> {
+    self.map.aggregate()
+  }
+
+  /**
+   * Renders the tree sideways with each key followed by its color
+   **/
+  pub fn pretty_print(&self) -> String
+  where
+    T: Display,
+  {
+    self.map.pretty_print()
+  }
+
+  pub fn get_height(&self) -> i32 {
+    self.map.get_height()
+  }
+}
+
+/**
+ * Methods that need the keys in their Ord order
+ **/
+impl<T, A> RBTree<T, A>
+where
+  T: Ord,
+  A: Aggregate<T, ()>,
+{
+  /**
+   * Creates a new empty tree that handles a key inserted again the way
+   * duplicates says
+   **/
+  pub fn with_duplicates(duplicates: DuplicatePolicy) -> Self {
+    Self::with_duplicates_and_comparator(duplicates, Natural)
+  }
+
+  /**
+   * keys with keys inside range in ascending order, .rev() for descending
+   **/
+  pub fn range<Q, R>(&self, range: R) -> Keys<Range<NodeRef<'_, T, (), A>>>
+  where
+    T: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+  {
+    Keys::new(Range::new(self.map.root_ref(), range))
+  }
+
+  /**
+   * number of keys inside range in O(log n), counted from the subtree sizes
+   **/
+  pub fn count_range<Q, R>(&self, range: R) -> usize
+  where
+    T: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+  {
+    self.range(range).len()
+  }
+
+  /**
+   * removes every key inside range, returns how many were removed
+   **/
+  pub fn remove_range<Q, R>(&mut self, range: R) -> usize
+  where
+    T: borrow::Borrow<Q> + Clone,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+  {
+    let keys: Vec<T> = self.range(range).cloned().collect();
+    for key in &keys {
+      self.delete::<T>(key);
+    }
+    keys.len()
+  }
+
+  /**
+   * Builds a tree from keys in strictly ascending order in O(n), without any
+   * rotations or recoloring. Panics if the keys are out of order or repeat,
+   * collect() accepts keys in any order
+   **/
+  pub fn from_sorted_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+    Self {
+      map: RBTreeMap::from_sorted_iter(iter.into_iter().map(|key| (key, ()))),
+      duplicates: DuplicatePolicy::default(),
+      cmp: Natural,
+    }
+  }
+
+  /**
+   * Moves the keys from key onwards into a new tree, keeping the smaller ones,
+   * see RBTreeMap::split_off
+   **/
+  pub fn split_off<Q>(&mut self, key: &Q) -> Self
+  where
+    T: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    Self {
+      map: self.map.split_off(key),
+      duplicates: self.duplicates,
+      cmp: Natural,
+    }
+  }
+
+  /**
+   * Splits the tree into the keys smaller than key and the rest, see split_off
+   **/
+  pub fn split<Q>(mut self, key: &Q) -> (Self, Self)
+  where
+    T: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    let upper = self.split_off(key);
+    (self, upper)
+  }
+
+  /**
+   * Joins the keys of left, pivot and the keys of right into one tree, see
+   * RBTreeMap::join. Panics unless left < pivot < right for all of their keys
+   **/
+  pub fn join(left: Self, pivot: T, right: Self) -> Self {
+    Self {
+      map: RBTreeMap::join(left.map, pivot, (), right.map),
+      duplicates: left.duplicates,
+      cmp: Natural,
+    }
+  }
+
+  /**
+   * Moves every key of other into this tree, leaving other empty. Takes
+   * O(log n) plus moving the nodes of the smaller tree when all keys of one
+   * tree are smaller than all keys of the other, otherwise the keys of other
+   * are inserted one by one
+   **/
+  pub fn append(&mut self, other: &mut Self) {
+    let overlap = match (self.iter().next(), self.iter().next_back()) {
+      (Some(min), Some(max)) => {
+        other.iter().next().is_some_and(|o| o <= max)
+          && other.iter().next_back().is_some_and(|o| min <= o)
+      }
+      _ => false,
+    };
+    // Inserting into the map keeps the stored key, which is only right when
+    // duplicates are rejected
+    if !overlap || self.duplicates == DuplicatePolicy::Reject {
+      self.map.append(&mut other.map);
+      return;
+    }
+    let other = std::mem::replace(other, Self::with_duplicates(other.duplicates));
+    for key in other {
+      self.insert(key);
+    }
+  }
+
+  /**
+   * Keys in self, other or both as a new tree. O(n + m)
+   **/
+  pub fn union(&self, other: &Self) -> Self
+  where
+    T: Clone,
+  {
+    self.merge(other, |item| Some(item.into_left()))
+  }
+
+  /**
+   * Keys in both self and other as a new tree. O(n + m)
+   **/
+  pub fn intersection(&self, other: &Self) -> Self
+  where
+    T: Clone,
+  {
+    self.merge(other, |item| match item {
+      MergeItem::Both(key, _) => Some(key),
+      _ => None,
+    })
+  }
+
+  /**
+   * Keys in self but not in other as a new tree. O(n + m)
+   **/
+  pub fn difference(&self, other: &Self) -> Self
+  where
+    T: Clone,
+  {
+    self.merge(other, |item| match item {
+      MergeItem::Left(key) => Some(key),
+      _ => None,
+    })
+  }
+
+  /**
+   * Keys in exactly one of self and other as a new tree. O(n + m)
+   **/
+  pub fn symmetric_difference(&self, other: &Self) -> Self
+  where
+    T: Clone,
+  {
+    self.merge(other, |item| match item {
+      MergeItem::Left(key) | MergeItem::Right(key) => Some(key),
+      MergeItem::Both(..) => None,
+    })
+  }
+
+  /**
+   * Returns if every key of self is in other
+   **/
+  pub fn is_subset(&self, other: &Self) -> bool {
+    self.len() <= other.len()
+      && Merge::new(self.iter(), other.iter()).all(|item| !matches!(item, MergeItem::Left(_)))
+  }
+
+  /**
+   * Returns if every key of other is in self
+   **/
+  pub fn is_superset(&self, other: &Self) -> bool {
+    other.is_subset(self)
+  }
+
+  /**
+   * Returns if self and other have no key in common
+   **/
+  pub fn is_disjoint(&self, other: &Self) -> bool {
+    Merge::new(self.iter(), other.iter()).all(|item| !matches!(item, MergeItem::Both(..)))
+  }
+
+  /**
+   * Walks both trees in order and builds a new tree from the keys that keep
+   * picks, without any rotations or recoloring
+   **/
+  fn merge<'a, F>(&'a self, other: &'a Self, keep: F) -> Self
+  where
+    T: Clone,
+    F: FnMut(MergeItem<&'a T>) -> Option<&'a T>,
+  {
+    let mut keys: Vec<(T, ())> = Merge::new(self.iter(), other.iter())
+      .filter_map(keep)
+      .map(|key| (key.clone(), ()))
+      .collect();
+    // A key other holds more than once must not repeat in a tree without duplicates
+    if self.duplicates != DuplicatePolicy::Count {
+      keys.dedup_by(|a, b| a.0 == b.0);
+    }
+    Self::from_sorted_entries_with(keys, self.duplicates)
+  }
+
+  /**
+   * Builds a tree from keys in strictly ascending order, see build_sorted
+   **/
+  pub(crate) fn from_sorted_entries(keys: Vec<(T, ())>) -> Self {
+    Self {
+      map: RBTreeMap::from_sorted_entries(keys),
+      duplicates: DuplicatePolicy::default(),
+      cmp: Natural,
+    }
+  }
+
+  /**
+   * Same as from_sorted_entries for a tree with the given duplicate policy,
+   * keys may repeat under Count
+   **/
+  pub(crate) fn from_sorted_entries_with(keys: Vec<(T, ())>, duplicates: DuplicatePolicy) -> Self {
+    let mut tree = Self::from_sorted_entries(keys);
+    tree.duplicates = duplicates;
+    tree
+  }
+
+  /**
+   * Writes the tree to writer in the binary snapshot format, see
+   * RBTreeMap::save_to. The header records the duplicate policy
+   **/
+  pub fn save_to<W: Write>(&self, writer: W) -> Result<(), SnapshotError>
+  where
+    T: Codec,
+  {
+    self.map.save_with(writer, self.duplicates)
+  }
+
+  /**
+   * Reads a tree written by save_to, see RBTreeMap::load_from
+   **/
+  pub fn load_from<R: Read>(reader: R) -> Result<Self, SnapshotError>
+  where
+    T: Codec,
+  {
+    let (map, duplicates) = RBTreeMap::load_with(reader)?;
+    Ok(Self {
+      map,
+      duplicates,
+      cmp: Natural,
+    })
+  }
+
+  /**
+   * Aggregate of the keys inside range, None if there are none
+   **/
+  pub fn fold_range<Q, R>(&self, range: R) -> Option<A>
+  where
+    T: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+  {
+    self.map.fold_range(range)
+  }
+}
+
+impl<T, A, C> Default for RBTree<T, A, C>
+where
+  A: Aggregate<T, ()>,
+  C: Comparator<T> + Default,
+{
+  fn default() -> Self {
+    Self::with_duplicates_and_comparator(DuplicatePolicy::default(), C::default())
+  }
+}
+
+/**
+ * Copies the arena, so the clone and the original never see each other's changes
+ **/
+impl<T, A, C> Clone for RBTree<T, A, C>
+where
+  T: Clone,
+  A: Aggregate<T, ()>,
+  C: Comparator<T> + Clone,
+{
+  fn clone(&self) -> Self {
+    Self {
+      map: self.map.clone(),
+      duplicates: self.duplicates,
+      cmp: self.cmp.clone(),
+    }
+  }
+}
+
+impl<T, A> SortedTree<T> for RBTree<T, A>
+where
+  T: Ord,
+  A: Aggregate<T, ()>,
+{
+  fn insert(&mut self, key: T) -> bool {
+    RBTree::insert(self, key)
+  }
+
+  fn remove<Q>(&mut self, key: &Q) -> Option<T>
+  where
+    T: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    self.delete(key)
+  }
+
+  fn contains<Q>(&self, key: &Q) -> bool
+  where
+    T: borrow::Borrow<Q>,
+    Q: Ord + ?Sized,
+  {
+    self.find(key).is_some()
+  }
+
+  fn len(&self) -> usize {
+    self.count() as usize
+  }
+
+  fn is_empty(&self) -> bool {
+    RBTree::is_empty(self)
+  }
+
+  fn height(&self) -> usize {
+    self.get_height() as usize
+  }
+
+  fn leaf_count(&self) -> usize {
+    self.count_leaves() as usize
+  }
+
+  fn inorder(&self) -> Vec<T>
+  where
+    T: Clone,
+  {
+    self.inorder_traversal()
+  }
+
+  fn render(&self) -> String
+  where
+    T: Display,
+  {
+    self.pretty_print()
+  }
+}
+
+impl<'a, K, V, A> Clone for NodeRef<'a, K, V, A> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<'a, K, V, A> Copy for NodeRef<'a, K, V, A> {}
+
+/**
+ * Shows the entry of the node, not the whole arena behind it
+ **/
+impl<'a, K: Debug, V: Debug, A> Debug for NodeRef<'a, K, V, A> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let n = &self.nodes[self.id];
+    f.debug_struct("NodeRef")
+      .field("key", &n.key)
+      .field("value", &n.value)
+      .finish()
+  }
+}
+
+/**
+ * Lets the shared traversals walk the nodes of the arena
+ **/
+impl<'a, K, V, A> NodeHandle for NodeRef<'a, K, V, A> {
+  type Key = &'a K;
+  type Value = &'a V;
+
+  fn left(self) -> Option<Self> {
+    self.nodes[self.id].left.map(|id| NodeRef { id, ..self })
+  }
+
+  fn right(self) -> Option<Self> {
+    self.nodes[self.id].right.map(|id| NodeRef { id, ..self })
+  }
+
+  fn entry(self) -> (&'a K, &'a V) {
+    let n = &self.nodes[self.id];
+    (&n.key, &n.value)
+  }
+
+  fn size(self) -> usize {
+    self.nodes[self.id].size
+  }
+}
+
+impl<'a, K, V, A> Augmented for NodeRef<'a, K, V, A> {
+  type Aggregate = &'a A;
+
+  fn aggregate(self) -> &'a A {
+    &self.nodes[self.id].aggregate
+  }
+}
+
+impl<'a, T, A: Aggregate<T, ()>, C: Comparator<T>> IntoIterator for &'a RBTree<T, A, C> {
+  type Item = &'a T;
+  type IntoIter = Keys<InOrder<NodeRef<'a, T, (), A>>>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
+
+impl<T, A: Aggregate<T, ()>, C> IntoIterator for RBTree<T, A, C> {
+  type Item = T;
+  type IntoIter = std::vec::IntoIter<T>;
+
+  /**
+   * Moves the keys out in ascending order
+   **/
+  fn into_iter(self) -> Self::IntoIter {
+    let keys: Vec<T> = self
+      .map
+      .into_entries()
+      .into_iter()
+      .map(|(k, _)| k)
+      .collect();
+    keys.into_iter()
+  }
+}
+
+/**
+ * Sorts and dedupes the keys unless they already ascend, then builds the tree in O(n)
+ **/
+impl<T: Ord, A: Aggregate<T, ()>> FromIterator<T> for RBTree<T, A> {
+  fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+    Self {
+      map: iter.into_iter().map(|key| (key, ())).collect(),
+      duplicates: DuplicatePolicy::default(),
+      cmp: Natural,
+    }
+  }
+}
+
+/**
+ * Large batches are merged with the keys of the tree and rebuilt in one
+ * O(n + m) pass, small ones are inserted one by one. Either way equal keys
+ * follow the duplicate policy the same as insert
+ **/
+impl<T: Ord, A: Aggregate<T, ()>> Extend<T> for RBTree<T, A> {
+  fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+    let mut keys: Vec<(T, ())> = iter.into_iter().map(|key| (key, ())).collect();
+    if !build::should_rebuild(self.map.len(), keys.len()) {
+      for (key, _) in keys {
+        self.insert(key);
+      }
+      return;
+    }
+    build::sort_stable(&mut keys);
+    let old = std::mem::take(&mut self.map).into_entries();
+    let duplicates = self.duplicates;
+    *self = Self::from_sorted_entries_with(build::merge_entries(old, keys, duplicates), duplicates);
+  }
+}
+
+impl<'a, K: Ord, V, A: Aggregate<K, V>> IntoIterator for &'a RBTreeMap<K, V, A> {
+  type Item = (&'a K, &'a V);
+  type IntoIter = Entries<InOrder<NodeRef<'a, K, V, A>>>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
+
+impl<K: Ord, V, A: Aggregate<K, V>> IntoIterator for RBTreeMap<K, V, A> {
+  type Item = (K, V);
+  type IntoIter = std::vec::IntoIter<(K, V)>;
+
+  /**
+   * Moves the entries out in ascending key order
+   **/
+  fn into_iter(self) -> Self::IntoIter {
+    self.into_entries().into_iter()
+  }
+}
+
+/**
+ * Sorts the entries unless their keys already ascend, the last entry of a key
+ * wins like with insert, then builds the tree in O(n)
+ **/
+impl<K: Ord, V, A: Aggregate<K, V>> FromIterator<(K, V)> for RBTreeMap<K, V, A> {
+  fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+    let mut entries: Vec<(K, V)> = iter.into_iter().collect();
+    build::sort_dedup(&mut entries);
+    Self::from_sorted_entries(entries)
+  }
+}
+
+/**
+ * Large batches are merged with the entries of the tree and rebuilt in one
+ * O(n + m) pass, small ones are inserted one by one. Values from the batch
+ * replace those of equal keys
+ **/
+impl<K: Ord, V, A: Aggregate<K, V>> Extend<(K, V)> for RBTreeMap<K, V, A> {
+  fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+    let mut entries: Vec<(K, V)> = iter.into_iter().collect();
+    if !build::should_rebuild(self.len(), entries.len()) {
+      for (key, value) in entries {
+        self.insert(key, value);
+      }
+      return;
+    }
+    build::sort_stable(&mut entries);
+    let old = std::mem::take(self).into_entries();
+    // Insert keeps the stored key and only swaps in the new value
+    *self = Self::from_sorted_entries(build::merge_values(old, entries));
+  }
+}
+
+/**
+ * The validator on trees that break exactly one invariant. A test builds a
+ * healthy tree, corrupts one field of one node and checks that validate()
+ * reports that node, its path and the broken rule and nothing else
+ **/
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Builds a node over the given children with parent links, size and aggregate right
+  fn node(
+    map: &mut RBTreeMap<i32, ()>,
+    key: i32,
+    color: NodeColor,
+    left: Link,
+    right: Link,
+  ) -> Link {
+    let id = map.new_node(key, ());
+    map.nodes[id].color = color;
+    map.set_children(id, left, right);
+    map.update_subtree(id);
+    Some(id)
+  }
+
+  // The node reached by following path down from the root
+  fn at<A>(map: &RBTreeMap<i32, (), A>, path: &[Side]) -> NodeId {
+    path.iter().fold(map.root.unwrap(), |id, side| {
+      match side {
+        Side::Left => map.nodes[id].left,
+        Side::Right => map.nodes[id].right,
+      }
+      .unwrap()
+    })
+  }
+
+  // Keys 1 to 7 as a perfect tree of black nodes with 4 at the root
+  fn perfect() -> RBTreeMap<i32, ()> {
+    let map = RBTreeMap::from_sorted_iter((1..=7).map(|key| (key, ())));
+    assert!(map.validate().is_valid());
+    map
+  }
+
+  fn problem(key: &i32, path: Vec<Side>, violation: Violation) -> Problem<'_, i32> {
+    Problem {
+      key,
+      path,
+      violation,
+    }
+  }
+
+  fn assert_only(map: &RBTreeMap<i32, ()>, expected: Vec<Problem<'_, i32>>) {
+    let report = map.validate();
+    assert_eq!(report.len, report.nodes);
+    assert_eq!(report.problems, expected);
+  }
+
+  #[test]
+  fn reports_a_red_root() {
+    let mut map = perfect();
+    let root = map.root.unwrap();
+    map.nodes[root].color = NodeColor::Red;
+    assert_only(&map, vec![problem(&4, vec![], Violation::RedRoot)]);
+  }
+
+  #[test]
+  fn reports_a_red_node_under_a_red_one() {
+    let mut map = RBTreeMap::default();
+    let one = node(&mut map, 1, NodeColor::Red, None, None);
+    let four = node(&mut map, 4, NodeColor::Red, None, None);
+    let three = node(&mut map, 3, NodeColor::Red, None, four);
+    map.root = node(&mut map, 2, NodeColor::Black, one, three);
+    assert_only(
+      &map,
+      vec![problem(
+        &4,
+        vec![Side::Right, Side::Right],
+        Violation::RedUnderRed,
+      )],
+    );
+  }
+
+  #[test]
+  fn reports_unequal_black_heights() {
+    let mut map = perfect();
+    let id = at(&map, &[Side::Left, Side::Right]);
+    map.nodes[id].color = NodeColor::Red;
+    // Node 2 has one black node fewer on its right, the root sees 2 on each side
+    assert_only(
+      &map,
+      vec![problem(
+        &2,
+        vec![Side::Left],
+        Violation::BlackHeightMismatch { left: 1, right: 0 },
+      )],
+    );
+  }
+
+  #[test]
+  fn reports_a_wrong_size() {
+    let mut map = perfect();
+    let id = at(&map, &[Side::Right]);
+    map.nodes[id].size = 9;
+    assert_only(
+      &map,
+      vec![problem(
+        &6,
+        vec![Side::Right],
+        Violation::WrongSize {
+          stored: 9,
+          actual: 3,
+        },
+      )],
+    );
+  }
+
+  #[derive(Clone, Debug, PartialEq)]
+  struct Sum(i64);
+
+  impl Aggregate<i32, ()> for Sum {
+    fn from_entry(key: &i32, _: &()) -> Self {
+      Sum(*key as i64)
+    }
+
+    fn combine(&self, right: &Self) -> Self {
+      Sum(self.0 + right.0)
+    }
+  }
+
+  #[test]
+  fn reports_a_stale_aggregate() {
+    let mut map: RBTreeMap<i32, (), Sum> =
+      RBTreeMap::from_sorted_iter((1..=7).map(|key| (key, ())));
+    assert!(map.validate().is_valid());
+    // A node is checked against the aggregates stored on its children, so a
+    // stale one below the root would show up on its parent as well
+    let root = map.root.unwrap();
+    map.nodes[root].aggregate = Sum(27);
+    let report = map.validate();
+    assert_eq!(
+      report.problems,
+      vec![problem(&4, vec![], Violation::WrongAggregate)]
+    );
+  }
+
+  #[test]
+  fn reports_swapped_keys() {
+    let mut map = perfect();
+    let parent = at(&map, &[Side::Left]);
+    let child = at(&map, &[Side::Left, Side::Left]);
+    map.nodes[parent].key = 1;
+    map.nodes[child].key = 2;
+    // 1 still lies between the bounds of its position, 2 is above its parent
+    assert_only(
+      &map,
+      vec![problem(
+        &2,
+        vec![Side::Left, Side::Left],
+        Violation::OutOfOrder,
+      )],
+    );
+  }
+
+  #[test]
+  fn reports_a_parent_link_to_the_wrong_node() {
+    let mut map = perfect();
+    let root = map.root.unwrap();
+    let id = at(&map, &[Side::Left, Side::Right]);
+    map.nodes[id].parent = Some(root);
+    assert_only(
+      &map,
+      vec![problem(
+        &3,
+        vec![Side::Left, Side::Right],
+        Violation::WrongParent,
+      )],
+    );
+  }
+
+  #[test]
+  fn reports_a_missing_parent_link() {
+    let mut map = perfect();
+    let id = at(&map, &[Side::Right]);
+    map.nodes[id].parent = None;
+    assert_only(
+      &map,
+      vec![problem(&6, vec![Side::Right], Violation::WrongParent)],
+    );
+  }
+
+  #[test]
+  fn reports_a_root_with_a_parent() {
+    let mut map = perfect();
+    let root = map.root.unwrap();
+    let leaf = at(&map, &[Side::Right, Side::Left]);
+    map.nodes[root].parent = Some(leaf);
+    assert_only(&map, vec![problem(&4, vec![], Violation::WrongParent)]);
+  }
+
+  #[test]
+  fn reports_a_dangling_parent_link() {
+    let mut map = perfect();
+    let id = at(&map, &[Side::Left, Side::Left]);
+    // Points at a slot the arena has freed again
+    let freed = map.new_node(0, ());
+    map.nodes.free(freed);
+    map.nodes[id].parent = Some(freed);
+    assert_only(
+      &map,
+      vec![problem(
+        &1,
+        vec![Side::Left, Side::Left],
+        Violation::WrongParent,
+      )],
+    );
+  }
+
+  #[test]
+  fn reports_a_repeated_key_unless_the_tree_counts_duplicates() {
+    let mut tree: RBTree<i32> = RBTree::from_sorted_iter(1..=7);
+    let id = at(&tree.map, &[Side::Right, Side::Left]);
+    tree.map.nodes[id].key = 4;
+    assert_eq!(
+      tree.validate().problems,
+      vec![problem(
+        &4,
+        vec![Side::Right, Side::Left],
+        Violation::OutOfOrder,
+      )]
+    );
+
+    tree.duplicates = DuplicatePolicy::Count;
+    assert!(tree.validate().is_valid());
+  }
+
+  #[test]
+  fn reports_every_broken_rule_of_a_node_in_order() {
+    let mut map = perfect();
+    let id = at(&map, &[Side::Right, Side::Right]);
+    map.nodes[id].key = 0;
+    map.nodes[id].size = 2;
+    map.nodes[id].parent = None;
+    assert_only(
+      &map,
+      vec![
+        problem(&0, vec![Side::Right, Side::Right], Violation::OutOfOrder),
+        problem(
+          &0,
+          vec![Side::Right, Side::Right],
+          Violation::WrongSize {
+            stored: 2,
+            actual: 1,
+          },
+        ),
+        problem(&0, vec![Side::Right, Side::Right], Violation::WrongParent),
+      ],
+    );
   }
 }
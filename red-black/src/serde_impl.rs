@@ -0,0 +1,58 @@
+use crate::rbt::{RBTree, RBTreeMap};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tree_core::{serde_sorted, Aggregate};
+
+/**
+ * A tree serializes as {duplicates, keys}, its duplicate policy and then the
+ * sequence of its keys in ascending order
+ **/
+impl<T: Ord + Serialize, A: Aggregate<T, ()>> Serialize for RBTree<T, A> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serde_sorted::serialize_keys(serializer, self.duplicates(), self)
+  }
+}
+
+/**
+ * Rejects keys that are out of order, or repeat outside a Count tree, then
+ * builds the tree with fresh colors in O(n) whatever shape it had when it was
+ * serialized
+ **/
+impl<'de, T, A> Deserialize<'de> for RBTree<T, A>
+where
+  T: Ord + Deserialize<'de>,
+  A: Aggregate<T, ()>,
+{
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let (duplicates, keys) = serde_sorted::deserialize_keys(deserializer)?;
+    Ok(RBTree::from_sorted_entries_with(keys, duplicates))
+  }
+}
+
+/**
+ * A map serializes as a map with its keys in ascending order
+ **/
+impl<K, V, A> Serialize for RBTreeMap<K, V, A>
+where
+  K: Ord + Serialize,
+  V: Serialize,
+  A: Aggregate<K, V>,
+{
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(self.iter())
+  }
+}
+
+/**
+ * Rejects keys that are out of order or repeat, then builds the tree with fresh
+ * colors in O(n) whatever shape it had when it was serialized
+ **/
+impl<'de, K, V, A> Deserialize<'de> for RBTreeMap<K, V, A>
+where
+  K: Ord + Deserialize<'de>,
+  V: Deserialize<'de>,
+  A: Aggregate<K, V>,
+{
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    serde_sorted::deserialize_entries(deserializer).map(RBTreeMap::from_sorted_entries)
+  }
+}
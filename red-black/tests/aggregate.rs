@@ -0,0 +1,221 @@
+// Aggregates against brute force: random sequences of insert, delete, split
+// and join are applied to an RBTree or RBTreeMap with an aggregate and to a
+// BTreeSet or BTreeMap, and after every step fold_range over a grid of ranges
+// must equal folding the entries of the model one by one. The aggregate keeps
+// the first and last key, so combining in the wrong order shows up too
+
+use proptest::prelude::*;
+use rbt::{Aggregate, RBTree, RBTreeMap};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+
+#[derive(Clone, Debug, PartialEq)]
+struct Span {
+  sum: i64,
+  first: i32,
+  last: i32,
+}
+
+impl Span {
+  fn join(&self, right: &Self) -> Self {
+    Span {
+      sum: self.sum + right.sum,
+      first: self.first,
+      last: right.last,
+    }
+  }
+}
+
+// Keys sum up in a set
+impl Aggregate<i32, ()> for Span {
+  fn from_entry(key: &i32, _: &()) -> Self {
+    Span {
+      sum: *key as i64,
+      first: *key,
+      last: *key,
+    }
+  }
+
+  fn combine(&self, right: &Self) -> Self {
+    self.join(right)
+  }
+}
+
+// Values sum up in a map
+impl Aggregate<i32, i32> for Span {
+  fn from_entry(key: &i32, value: &i32) -> Self {
+    Span {
+      sum: *value as i64,
+      first: *key,
+      last: *key,
+    }
+  }
+
+  fn combine(&self, right: &Self) -> Self {
+    self.join(right)
+  }
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+  Insert(i32, i32),
+  Delete(i32),
+  /// Splits at the key and joins the halves back with the smallest key of
+  /// the upper half as the pivot
+  SplitJoin(i32),
+  /// Splits at the key and drops the upper half
+  Truncate(i32),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+  prop_oneof![
+      6 => (0..64, -100..100).prop_map(|(key, value)| Op::Insert(key, value)),
+      3 => (0..64).prop_map(Op::Delete),
+      2 => (0..64).prop_map(Op::SplitJoin),
+      1 => (32..80).prop_map(Op::Truncate),
+  ]
+}
+
+// Every pair of bounds from a grid around the keys, with lower <= upper
+fn ranges() -> Vec<(Bound<i32>, Bound<i32>)> {
+  let points = (-8..=72).step_by(8);
+  let bounds = || {
+    points
+      .clone()
+      .flat_map(|p| vec![Included(p), Excluded(p)])
+      .chain(Some(Unbounded))
+  };
+  let mut ranges = Vec::new();
+  for lower in bounds() {
+    for upper in bounds() {
+      let ordered = match (lower, upper) {
+        (Included(l), Included(u)) => l <= u,
+        (Included(l), Excluded(u)) | (Excluded(l), Included(u)) | (Excluded(l), Excluded(u)) => {
+          l < u
+        }
+        _ => true,
+      };
+      if ordered {
+        ranges.push((lower, upper));
+      }
+    }
+  }
+  ranges
+}
+
+fn check_set(tree: &RBTree<i32, Span>, model: &BTreeSet<i32>) -> Result<(), TestCaseError> {
+  let report = tree.validate();
+  prop_assert!(report.is_valid(), "{}", report);
+  let fold = |range| {
+    model
+      .range(range)
+      .map(|key| <Span as Aggregate<i32, ()>>::from_entry(key, &()))
+      .reduce(|a, b| a.join(&b))
+  };
+  prop_assert_eq!(tree.aggregate().cloned(), fold((Unbounded, Unbounded)));
+  for range in ranges() {
+    prop_assert_eq!(tree.fold_range(range), fold(range), "{:?}", range);
+  }
+  Ok(())
+}
+
+fn check_map(
+  map: &RBTreeMap<i32, i32, Span>,
+  model: &BTreeMap<i32, i32>,
+) -> Result<(), TestCaseError> {
+  let report = map.validate();
+  prop_assert!(report.is_valid(), "{}", report);
+  let fold = |range| {
+    model
+      .range(range)
+      .map(|(key, value)| Span::from_entry(key, value))
+      .reduce(|a, b| a.join(&b))
+  };
+  prop_assert_eq!(map.aggregate().cloned(), fold((Unbounded, Unbounded)));
+  for range in ranges() {
+    prop_assert_eq!(map.fold_range(range), fold(range), "{:?}", range);
+  }
+  Ok(())
+}
+
+fn run_set(ops: &[Op]) -> Result<(), TestCaseError> {
+  let mut tree: RBTree<i32, Span> = RBTree::default();
+  let mut model = BTreeSet::new();
+  for op in ops {
+    match *op {
+      Op::Insert(key, _) => {
+        tree.insert(key);
+        model.insert(key);
+      }
+      Op::Delete(key) => {
+        tree.delete(&key);
+        model.remove(&key);
+      }
+      Op::SplitJoin(key) => {
+        let mut upper = tree.split_off(&key);
+        let lower = std::mem::take(&mut tree);
+        check_set(&lower, &model.range(..key).copied().collect())?;
+        check_set(&upper, &model.range(key..).copied().collect())?;
+        tree = match upper.pop_first() {
+          Some(pivot) => RBTree::join(lower, pivot, upper),
+          None => lower,
+        };
+      }
+      Op::Truncate(key) => {
+        tree.split_off(&key);
+        model.split_off(&key);
+      }
+    }
+    check_set(&tree, &model)?;
+  }
+  Ok(())
+}
+
+fn run_map(ops: &[Op]) -> Result<(), TestCaseError> {
+  let mut map: RBTreeMap<i32, i32, Span> = RBTreeMap::default();
+  let mut model = BTreeMap::new();
+  for op in ops {
+    match *op {
+      Op::Insert(key, value) => {
+        map.insert(key, value);
+        model.insert(key, value);
+      }
+      Op::Delete(key) => {
+        map.remove(&key);
+        model.remove(&key);
+      }
+      Op::SplitJoin(key) => {
+        let mut upper = map.split_off(&key);
+        let lower = std::mem::take(&mut map);
+        check_map(&lower, &model.range(..key).map(|(&k, &v)| (k, v)).collect())?;
+        check_map(&upper, &model.range(key..).map(|(&k, &v)| (k, v)).collect())?;
+        let pivot = upper.iter().next().map(|(&k, &v)| (k, v));
+        map = match pivot {
+          Some((key, value)) => {
+            upper.remove(&key);
+            RBTreeMap::join(lower, key, value, upper)
+          }
+          None => lower,
+        };
+      }
+      Op::Truncate(key) => {
+        map.split_off(&key);
+        model.split_off(&key);
+      }
+    }
+    check_map(&map, &model)?;
+  }
+  Ok(())
+}
+
+proptest! {
+    #[test]
+    fn set_fold_range_matches_brute_force(ops in prop::collection::vec(op(), 0..80)) {
+        run_set(&ops)?;
+    }
+
+    #[test]
+    fn map_fold_range_matches_brute_force(ops in prop::collection::vec(op(), 0..80)) {
+        run_map(&ops)?;
+    }
+}
@@ -0,0 +1,140 @@
+// Trees of owned String and Vec<u8> keys, looked up, ranged over, split and
+// deleted through &str and &[u8] without building an owned key first. The
+// results must match a BTreeSet or BTreeMap asked the same way
+
+use rbt::{RBTree, RBTreeMap};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound::{Excluded, Included, Unbounded};
+
+const WORDS: [&str; 8] = [
+  "apple", "banana", "cherry", "date", "fig", "grape", "kiwi", "lemon",
+];
+
+fn words() -> (RBTree<String>, BTreeSet<String>) {
+  let tree: RBTree<String> = WORDS.iter().map(|w| w.to_string()).collect();
+  let model: BTreeSet<String> = WORDS.iter().map(|w| w.to_string()).collect();
+  (tree, model)
+}
+
+fn bytes() -> (RBTreeMap<Vec<u8>, usize>, BTreeMap<Vec<u8>, usize>) {
+  let entries = || {
+    WORDS
+      .iter()
+      .enumerate()
+      .map(|(i, w)| (w.as_bytes().to_vec(), i))
+  };
+  (entries().collect(), entries().collect())
+}
+
+#[test]
+fn string_set_is_searched_by_str() {
+  let (tree, model) = words();
+  for &word in WORDS.iter().chain(["", "blueberry", "zucchini"].iter()) {
+    assert_eq!(tree.find(word).is_some(), model.contains(word), "{}", word);
+    assert_eq!(tree.count_of(word), model.contains(word) as usize);
+    assert_eq!(
+      tree.rank(word),
+      model.range::<str, _>((Unbounded, Excluded(word))).count()
+    );
+  }
+  assert_eq!(tree.floor("coconut").map(String::as_str), Some("cherry"));
+  assert_eq!(tree.ceiling("coconut").map(String::as_str), Some("date"));
+  assert_eq!(tree.predecessor("date").map(String::as_str), Some("cherry"));
+  assert_eq!(tree.successor("date").map(String::as_str), Some("fig"));
+}
+
+#[test]
+fn string_set_is_ranged_over_by_str() {
+  let (tree, model) = words();
+  let bounds = [
+    (Included("banana"), Excluded("grape")),
+    (Excluded("banana"), Included("grape")),
+    (Included("c"), Unbounded),
+    (Unbounded, Excluded("d")),
+  ];
+  for &range in bounds.iter() {
+    assert!(
+      tree.range::<str, _>(range).eq(model.range::<str, _>(range)),
+      "{:?}",
+      range
+    );
+    assert_eq!(
+      tree.count_range::<str, _>(range),
+      model.range::<str, _>(range).count()
+    );
+  }
+}
+
+#[test]
+fn string_set_is_deleted_and_split_by_str() {
+  let (mut tree, mut model) = words();
+  assert_eq!(tree.delete("fig"), model.take("fig"));
+  assert_eq!(tree.delete("fig"), None);
+  assert_eq!(
+    tree.remove_range::<str, _>((Included("b"), Excluded("d"))),
+    2
+  );
+  model.retain(|w| !("b".."d").contains(&w.as_str()));
+
+  let upper = tree.split_off("grape");
+  let model_upper = model.split_off("grape");
+  assert!(tree.iter().eq(model.iter()));
+  assert!(upper.iter().eq(model_upper.iter()));
+  assert!(tree.validate().is_valid() && upper.validate().is_valid());
+}
+
+#[test]
+fn string_map_is_read_and_changed_by_str() {
+  let mut map: RBTreeMap<String, usize> = WORDS.iter().map(|w| (w.to_string(), w.len())).collect();
+  assert_eq!(map.get("cherry"), Some(&6));
+  assert_eq!(map.get("cherries"), None);
+  assert!(map.contains_key("kiwi"));
+  *map.get_mut("kiwi").unwrap() += 10;
+  assert_eq!(map.get("kiwi"), Some(&14));
+  assert_eq!(map.rank("date"), 3);
+  assert_eq!(map.remove("apple"), Some(5));
+  assert!(!map.contains_key("apple"));
+  assert_eq!(
+    map
+      .range::<str, _>((Included("f"), Excluded("l")))
+      .map(|(k, v)| (k.as_str(), *v))
+      .collect::<Vec<_>>(),
+    vec![("fig", 3), ("grape", 5), ("kiwi", 14)]
+  );
+  assert!(map.validate().is_valid());
+}
+
+#[test]
+fn byte_map_is_read_by_slice() {
+  let (map, model) = bytes();
+  for &word in WORDS.iter().chain(["", "apricot"].iter()) {
+    let key: &[u8] = word.as_bytes();
+    assert_eq!(map.get(key), model.get(key));
+    assert_eq!(map.contains_key(key), model.contains_key(key));
+    assert_eq!(
+      map.rank(key),
+      model.range::<[u8], _>((Unbounded, Excluded(key))).count()
+    );
+  }
+  let range = (Excluded(&b"cherry"[..]), Included(&b"kiwi"[..]));
+  assert!(map
+    .range::<[u8], _>(range)
+    .eq(model.range::<[u8], _>(range)));
+  assert_eq!(map.count_range::<[u8], _>(range), 4);
+}
+
+#[test]
+fn byte_map_is_changed_by_slice() {
+  let (mut map, mut model) = bytes();
+  assert_eq!(map.remove(&b"date"[..]), model.remove(&b"date"[..]));
+  assert_eq!(map.remove(&b"date"[..]), None);
+  let range = (Included(&b"a"[..]), Excluded(&b"c"[..]));
+  assert_eq!(map.remove_range::<[u8], _>(range), 2);
+  model.retain(|k, _| !(&b"a"[..]..&b"c"[..]).contains(&k.as_slice()));
+
+  let upper = map.split_off(&b"g"[..]);
+  let model_upper = model.split_off(&b"g"[..]);
+  assert!(map.iter().eq(model.iter()));
+  assert!(upper.iter().eq(model_upper.iter()));
+  assert!(map.validate().is_valid() && upper.validate().is_valid());
+}
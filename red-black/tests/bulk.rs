@@ -0,0 +1,189 @@
+// Bulk operations against inserting one key at a time, or against BTreeSet
+// and BTreeMap, with validate() after each of them
+
+use proptest::prelude::*;
+use rbt::{DuplicatePolicy, RBTree, RBTreeMap};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+
+// Compares by key only, so two of them can be equal without being identical
+#[derive(Clone, Debug)]
+struct Tagged(i32, u32);
+
+impl PartialEq for Tagged {
+  fn eq(&self, other: &Self) -> bool {
+    self.0 == other.0
+  }
+}
+
+impl Eq for Tagged {}
+
+impl PartialOrd for Tagged {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Tagged {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.0.cmp(&other.0)
+  }
+}
+
+const POLICIES: [DuplicatePolicy; 3] = [
+  DuplicatePolicy::Reject,
+  DuplicatePolicy::Replace,
+  DuplicatePolicy::Count,
+];
+
+fn tagged(keys: &[i32], first_tag: u32) -> Vec<Tagged> {
+  (first_tag..)
+    .zip(keys)
+    .map(|(tag, &key)| Tagged(key, tag))
+    .collect()
+}
+
+fn identities(tree: &RBTree<Tagged>) -> Vec<(i32, u32)> {
+  tree.iter().map(|key| (key.0, key.1)).collect()
+}
+
+#[test]
+fn extend_keeps_stored_keys_on_reject() {
+  let mut tree = RBTree::with_duplicates(DuplicatePolicy::Reject);
+  tree.insert(Tagged(1, 0));
+  tree.extend(vec![Tagged(1, 1)]);
+  assert_eq!(identities(&tree), [(1, 0)]);
+
+  // Large enough to be merged and rebuilt instead of inserted
+  tree.extend(tagged(&[1, 2, 2, 3, 1], 10));
+  assert_eq!(identities(&tree), [(1, 0), (2, 11), (3, 13)]);
+}
+
+#[test]
+fn extend_replaces_stored_keys_on_replace() {
+  let mut tree = RBTree::with_duplicates(DuplicatePolicy::Replace);
+  tree.insert(Tagged(1, 0));
+  tree.extend(tagged(&[1, 2, 2, 3, 1], 10));
+  assert_eq!(identities(&tree), [(1, 14), (2, 12), (3, 13)]);
+}
+
+#[test]
+fn extend_keeps_every_copy_on_count() {
+  let mut tree = RBTree::with_duplicates(DuplicatePolicy::Count);
+  tree.insert(Tagged(1, 0));
+  tree.extend(tagged(&[1, 2, 2, 3, 1], 10));
+  assert_eq!(
+    identities(&tree),
+    [(1, 0), (1, 10), (1, 14), (2, 11), (2, 12), (3, 13)]
+  );
+}
+
+proptest! {
+    #[test]
+    fn extend_matches_inserting_one_by_one(
+        stored in prop::collection::vec(0..40i32, 0..100),
+        added in prop::collection::vec(0..40i32, 0..100),
+    ) {
+        for &policy in POLICIES.iter() {
+            let mut tree = RBTree::with_duplicates(policy);
+            for key in tagged(&stored, 0) {
+                tree.insert(key);
+            }
+            let mut expected = tree.clone();
+            for key in tagged(&added, 1000) {
+                expected.insert(key);
+            }
+            tree.extend(tagged(&added, 1000));
+            prop_assert!(tree.validate().is_valid());
+            prop_assert_eq!(tree.count(), expected.count());
+            prop_assert_eq!(identities(&tree), identities(&expected));
+        }
+    }
+
+    #[test]
+    fn map_extend_matches_inserting_one_by_one(
+        stored in prop::collection::vec(0..40i32, 0..100),
+        added in prop::collection::vec(0..40i32, 0..100),
+    ) {
+        let mut map = RBTreeMap::new();
+        for (tag, key) in (0u32..).zip(&stored) {
+            map.insert(Tagged(*key, tag), tag);
+        }
+        let mut expected = map.clone();
+        for (tag, key) in (1000u32..).zip(&added) {
+            expected.insert(Tagged(*key, tag), tag);
+        }
+        map.extend((1000u32..).zip(&added).map(|(tag, key)| (Tagged(*key, tag), tag)));
+        prop_assert!(map.validate().is_valid());
+        let entries = |map: &RBTreeMap<Tagged, u32>| -> Vec<_> {
+            map.iter().map(|(key, value)| (key.0, key.1, *value)).collect()
+        };
+        prop_assert_eq!(entries(&map), entries(&expected));
+    }
+
+    #[test]
+    fn from_sorted_iter_matches_btreeset(keys in prop::collection::btree_set(0..1000i32, 0..200)) {
+        let tree: RBTree<i32> = RBTree::from_sorted_iter(keys.iter().copied());
+        prop_assert!(tree.validate().is_valid());
+        prop_assert!(tree.iter().eq(keys.iter()));
+        let map: RBTreeMap<i32, i32> = RBTreeMap::from_sorted_iter(keys.iter().map(|&key| (key, -key)));
+        prop_assert!(map.validate().is_valid());
+        prop_assert!(map.iter().map(|(&k, &v)| (k, v)).eq(keys.iter().map(|&key| (key, -key))));
+    }
+
+    #[test]
+    fn collect_matches_btreeset(keys in prop::collection::vec(0..60i32, 0..200)) {
+        let tree: RBTree<i32> = keys.iter().copied().collect();
+        let model: BTreeSet<i32> = keys.iter().copied().collect();
+        prop_assert!(tree.validate().is_valid());
+        prop_assert!(tree.iter().eq(model.iter()));
+        let pairs = keys.iter().enumerate().map(|(value, &key)| (key, value));
+        let map: RBTreeMap<i32, usize> = pairs.clone().collect();
+        let model: BTreeMap<i32, usize> = pairs.collect();
+        prop_assert!(map.validate().is_valid());
+        prop_assert!(map.iter().eq(model.iter()));
+    }
+
+    #[test]
+    fn extend_matches_btreeset(
+        stored in prop::collection::vec(0..60i32, 0..100),
+        added in prop::collection::vec(0..60i32, 0..100),
+    ) {
+        let mut tree: RBTree<i32> = stored.iter().copied().collect();
+        let mut model: BTreeSet<i32> = stored.iter().copied().collect();
+        tree.extend(added.iter().copied());
+        model.extend(added.iter().copied());
+        prop_assert!(tree.validate().is_valid());
+        prop_assert!(tree.iter().eq(model.iter()));
+    }
+}
+
+#[test]
+fn builds_empty_and_single_key_trees() {
+  assert!(RBTree::<i32>::from_sorted_iter(None).is_empty());
+  assert!(RBTreeMap::<i32, i32>::from_sorted_iter(None).is_empty());
+  assert!(std::iter::empty::<i32>()
+    .collect::<RBTree<i32>>()
+    .is_empty());
+  let tree: RBTree<i32> = RBTree::from_sorted_iter(Some(7));
+  assert!(tree.validate().is_valid());
+  assert_eq!(tree.iter().collect::<Vec<_>>(), [&7]);
+}
+
+#[test]
+#[should_panic(expected = "from_sorted_iter needs keys in strictly ascending order")]
+fn from_sorted_iter_rejects_unsorted_keys() {
+  let _: RBTree<i32> = RBTree::from_sorted_iter(vec![1, 3, 2]);
+}
+
+#[test]
+#[should_panic(expected = "from_sorted_iter needs keys in strictly ascending order")]
+fn from_sorted_iter_rejects_repeated_keys() {
+  let _: RBTree<i32> = RBTree::from_sorted_iter(vec![1, 2, 2, 3]);
+}
+
+#[test]
+#[should_panic(expected = "from_sorted_iter needs keys in strictly ascending order")]
+fn map_from_sorted_iter_rejects_unsorted_keys() {
+  let _: RBTreeMap<i32, char> = RBTreeMap::from_sorted_iter(vec![(2, 'b'), (1, 'a')]);
+}
@@ -0,0 +1,125 @@
+use rbt::{Aggregate, RBTree, RBTreeMap};
+
+#[derive(Clone, Debug, PartialEq)]
+struct Sum(i64);
+
+impl Aggregate<i32, ()> for Sum {
+  fn from_entry(key: &i32, _: &()) -> Self {
+    Sum(*key as i64)
+  }
+
+  fn combine(&self, right: &Self) -> Self {
+    Sum(self.0 + right.0)
+  }
+}
+
+fn keys(tree: &RBTree<i32>) -> Vec<i32> {
+  tree.iter().copied().collect()
+}
+
+#[test]
+fn clone_has_the_same_keys_and_shape() {
+  let mut tree = RBTree::new();
+  for key in [5, 3, 8, 1, 4, 7, 9, 2, 6].iter() {
+    tree.insert(*key);
+  }
+  let copy = tree.clone();
+
+  assert_eq!(keys(&copy), keys(&tree));
+  assert_eq!(copy.count(), tree.count());
+  assert_eq!(copy.get_height(), tree.get_height());
+  assert_eq!(copy.pretty_print(), tree.pretty_print());
+}
+
+#[test]
+fn changing_the_clone_leaves_the_original_alone() {
+  let mut tree = RBTree::new();
+  for key in 0..20 {
+    tree.insert(key);
+  }
+  let before = tree.pretty_print();
+
+  let mut copy = tree.clone();
+  for key in 20..40 {
+    copy.insert(key);
+  }
+  for key in (0..20).step_by(2) {
+    copy.delete(&key);
+  }
+
+  assert_eq!(keys(&tree), (0..20).collect::<Vec<_>>());
+  assert_eq!(tree.count(), 20);
+  assert_eq!(tree.pretty_print(), before);
+  assert_eq!(copy.find(&25), Some(&25));
+  assert_eq!(copy.find(&4), None);
+}
+
+#[test]
+fn changing_the_original_leaves_the_clone_alone() {
+  let mut tree = RBTree::new();
+  for key in 0..20 {
+    tree.insert(key);
+  }
+  let copy = tree.clone();
+
+  tree.remove_range(5..15);
+  tree.insert(100);
+
+  assert_eq!(keys(&copy), (0..20).collect::<Vec<_>>());
+  assert_eq!(copy.count(), 20);
+  assert_eq!(copy.find(&100), None);
+}
+
+#[test]
+fn clone_links_its_nodes_to_their_own_parents() {
+  let mut tree = RBTree::new();
+  for key in 0..64 {
+    tree.insert(key);
+  }
+  let mut copy = tree.clone();
+  drop(tree);
+
+  // removals walk up through the parent links, which must point into the copy
+  for key in 0..60 {
+    copy.delete(&key);
+  }
+  assert_eq!(keys(&copy), vec![60, 61, 62, 63]);
+}
+
+#[test]
+fn clone_keeps_its_own_aggregates() {
+  let mut tree: RBTree<i32, Sum> = RBTree::default();
+  for key in 1..=10 {
+    tree.insert(key);
+  }
+  let mut copy = tree.clone();
+  copy.insert(100);
+  copy.delete(&1);
+
+  assert_eq!(tree.aggregate(), Some(&Sum(55)));
+  assert_eq!(copy.aggregate(), Some(&Sum(154)));
+  assert_eq!(tree.fold_range(..=3), Some(Sum(6)));
+  assert_eq!(copy.fold_range(..=3), Some(Sum(5)));
+}
+
+#[test]
+fn map_clone_has_its_own_values() {
+  let mut map = RBTreeMap::new();
+  map.insert(1, "one");
+  map.insert(2, "two");
+
+  let mut copy = map.clone();
+  copy.insert(1, "uno");
+  if let Some(value) = copy.get_mut(&2) {
+    *value = "dos";
+  }
+  copy.insert(3, "tres");
+
+  assert_eq!(map.get(&1), Some(&"one"));
+  assert_eq!(map.get(&2), Some(&"two"));
+  assert_eq!(map.get(&3), None);
+  assert_eq!(map.len(), 2);
+  assert_eq!(copy.get(&1), Some(&"uno"));
+  assert_eq!(copy.get(&2), Some(&"dos"));
+  assert_eq!(copy.len(), 3);
+}
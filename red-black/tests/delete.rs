@@ -0,0 +1,138 @@
+// Delete-heavy workloads for the red-black delete and its fix-up. Trees are
+// filled and then emptied again in many orders, so every sibling case of
+// fix_delete runs on both sides, including removals of black leaves where the
+// child taking the node's place is empty. validate() runs after every delete
+
+use proptest::prelude::*;
+use rbt::{RBTree, RBTreeMap};
+use std::collections::{BTreeMap, BTreeSet};
+
+fn check(tree: &RBTree<i32>, model: &BTreeSet<i32>) -> Result<(), TestCaseError> {
+  let report = tree.validate();
+  prop_assert!(report.is_valid(), "{}\n{}", report, tree.pretty_print());
+  prop_assert!(tree.iter().eq(model.iter()));
+  Ok(())
+}
+
+// Inserts keys in the given order, then deletes them in the other given order
+fn fill_and_empty(inserts: &[i32], deletes: &[i32]) -> Result<(), TestCaseError> {
+  let mut tree = RBTree::new();
+  let mut model = BTreeSet::new();
+  for &key in inserts {
+    tree.insert(key);
+    model.insert(key);
+  }
+  check(&tree, &model)?;
+  for &key in deletes {
+    prop_assert_eq!(tree.delete(&key), model.take(&key), "delete {}", key);
+    check(&tree, &model)?;
+  }
+  Ok(())
+}
+
+#[test]
+fn empties_trees_in_fixed_orders() {
+  for n in [1, 2, 3, 7, 8, 31, 64, 255, 300] {
+    let ascending: Vec<i32> = (0..n).collect();
+    let descending: Vec<i32> = (0..n).rev().collect();
+    // Alternates between both ends, then works out from the middle
+    let ends: Vec<i32> = (0..n / 2)
+      .flat_map(|i| [i, n - 1 - i])
+      .chain((n % 2 == 1).then_some(n / 2))
+      .collect();
+    let middle: Vec<i32> = ends.iter().rev().copied().collect();
+    for inserts in [&ascending, &descending, &ends] {
+      for deletes in [&ascending, &descending, &ends, &middle] {
+        fill_and_empty(inserts, deletes).unwrap();
+      }
+    }
+  }
+}
+
+#[test]
+fn deletes_missing_keys_without_changing_the_tree() {
+  let keys: Vec<i32> = (0..100).map(|key| key * 2).collect();
+  let missing: Vec<i32> = (-1..200).step_by(2).collect();
+  fill_and_empty(&keys, &missing).unwrap();
+}
+
+#[test]
+fn removes_map_entries_with_their_values() {
+  let mut map = RBTreeMap::new();
+  let mut model = BTreeMap::new();
+  for key in 0..200 {
+    map.insert(key * 7 % 200, key);
+    model.insert(key * 7 % 200, key);
+  }
+  for key in (0..200).rev().step_by(3).chain(0..200) {
+    assert_eq!(map.remove(&key), model.remove(&key), "remove {}", key);
+    assert!(map.validate().is_valid());
+    assert!(map.iter().eq(model.iter()));
+  }
+  assert!(map.is_empty());
+}
+
+// A fix-up that stopped after recoloring the sibling left the black heights
+// uneven, and the later deletes of this sequence lost keys that were still in
+// the tree
+#[test]
+fn deletes_keep_every_other_key() {
+  let inserts = [1, 10, 3, 7, 2, 14, 11, 15, 9, 4, 13, 0, 12, 6, 5, 8];
+  let deletes = [1, 15, 6, 2, 4, 14, 13, 0, 10, 3, 11];
+  let mut tree = RBTree::new();
+  for &key in inserts.iter() {
+    tree.insert(key);
+  }
+  for (step, &key) in deletes.iter().enumerate() {
+    tree.delete(&key);
+    for &other in inserts.iter() {
+      let deleted = deletes[..=step].contains(&other);
+      assert_eq!(
+        tree.find(&other).is_some(),
+        !deleted,
+        "key {} after deleting {:?}",
+        other,
+        &deletes[..=step]
+      );
+    }
+  }
+}
+
+#[test]
+fn empty_tree_has_height_0() {
+  let mut tree = RBTree::new();
+  assert_eq!(tree.get_height(), 0);
+  tree.insert(1);
+  assert_eq!(tree.get_height(), 1);
+  tree.delete(&1);
+  assert_eq!(tree.get_height(), 0);
+}
+
+proptest! {
+  #[test]
+  fn empties_random_trees_in_random_orders(
+    (inserts, deletes) in prop::collection::btree_set(0..1000i32, 0..300)
+      .prop_flat_map(|keys| {
+        let keys: Vec<i32> = keys.into_iter().collect();
+        (Just(keys.clone()).prop_shuffle(), Just(keys).prop_shuffle())
+      })
+  ) {
+    fill_and_empty(&inserts, &deletes)?;
+  }
+
+  #[test]
+  fn mostly_deletes_on_few_keys(keys in prop::collection::vec(0..24i32, 0..600)) {
+    // Two deletes for every insert keep the tree small and often empty, where
+    // the root and its children are removed and recolored most often
+    let mut tree = RBTree::new();
+    let mut model = BTreeSet::new();
+    for (step, &key) in keys.iter().enumerate() {
+      if step % 3 == 0 {
+        prop_assert_eq!(tree.insert(key), model.insert(key));
+      } else {
+        prop_assert_eq!(tree.delete(&key), model.take(&key));
+      }
+      check(&tree, &model)?;
+    }
+  }
+}
@@ -0,0 +1,154 @@
+// Brute-force tests for IntervalTree: random inserts and removes go to the tree
+// and to a BTreeMap of the same intervals, and after every step overlapping,
+// containing and any_overlap must find exactly what a scan of the model finds.
+// Coordinates come from a small range so intervals often touch at an endpoint,
+// and removes often take out the interval that held the largest end of a
+// subtree, which has to be recomputed on the way up
+
+use proptest::prelude::*;
+use rbt::{Interval, IntervalTree};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+#[derive(Clone, Debug)]
+enum Op {
+  Insert(i32, i32),
+  Remove(i32, i32),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+  prop_oneof![
+    3 => (0..40i32, 1..12i32).prop_map(|(start, len)| Op::Insert(start, start + len)),
+    2 => (0..40i32, 1..12i32).prop_map(|(start, len)| Op::Remove(start, start + len)),
+  ]
+}
+
+type Model = BTreeMap<(i32, i32), usize>;
+
+// What the tree must return for query, an empty query overlaps nothing
+fn scan_overlapping(model: &Model, query: &Range<i32>) -> Vec<(i32, i32, usize)> {
+  model
+    .iter()
+    .filter(|(&(start, end), _)| query.start < query.end && start < query.end && query.start < end)
+    .map(|(&(start, end), &value)| (start, end, value))
+    .collect()
+}
+
+fn scan_containing(model: &Model, point: i32) -> Vec<(i32, i32, usize)> {
+  model
+    .iter()
+    .filter(|(&(start, end), _)| start <= point && point < end)
+    .map(|(&(start, end), &value)| (start, end, value))
+    .collect()
+}
+
+fn check(tree: &IntervalTree<i32, usize>, model: &Model) -> Result<(), TestCaseError> {
+  prop_assert_eq!(tree.len(), model.len());
+  for start in -1..55 {
+    for width in [0, 1, 4, 13] {
+      let query = start..start + width;
+      let found: Vec<_> = tree
+        .overlapping(query.clone())
+        .map(|(interval, &value)| (interval.start, interval.end, value))
+        .collect();
+      let expected = scan_overlapping(model, &query);
+      prop_assert_eq!(&found, &expected, "overlapping {:?}", query);
+      prop_assert_eq!(
+        tree.any_overlap(query.clone()),
+        !expected.is_empty(),
+        "any_overlap {:?}",
+        query
+      );
+    }
+    let found: Vec<_> = tree
+      .containing(start)
+      .map(|(interval, &value)| (interval.start, interval.end, value))
+      .collect();
+    prop_assert_eq!(found, scan_containing(model, start), "containing {}", start);
+  }
+  Ok(())
+}
+
+fn run(ops: &[Op]) -> Result<(), TestCaseError> {
+  let mut tree = IntervalTree::new();
+  let mut model = Model::new();
+  for (step, op) in ops.iter().enumerate() {
+    match *op {
+      Op::Insert(start, end) => {
+        prop_assert_eq!(
+          tree.insert(start..end, step),
+          model.insert((start, end), step),
+          "step {}: {:?}",
+          step,
+          op
+        );
+      }
+      Op::Remove(start, end) => {
+        prop_assert_eq!(
+          tree.remove(start..end),
+          model.remove(&(start, end)),
+          "step {}: {:?}",
+          step,
+          op
+        );
+      }
+    }
+    check(&tree, &model)?;
+  }
+  Ok(())
+}
+
+proptest! {
+  #[test]
+  fn queries_match_a_scan(ops in prop::collection::vec(op(), 0..80)) {
+    run(&ops)?;
+  }
+}
+
+// The values of the intervals found, in order
+fn values<'a>(found: impl Iterator<Item = (&'a Interval<i32>, &'a char)>) -> String {
+  found.map(|(_, &value)| value).collect()
+}
+
+#[test]
+fn touching_intervals_do_not_overlap() {
+  let mut tree = IntervalTree::new();
+  tree.insert(0..5, 'a');
+  tree.insert(5..10, 'b');
+  tree.insert(10..15, 'c');
+
+  assert_eq!(values(tree.containing(5)), "b");
+  assert_eq!(values(tree.containing(4)), "a");
+  assert_eq!(values(tree.containing(15)), "");
+  assert_eq!(values(tree.overlapping(0..5)), "a");
+  assert_eq!(values(tree.overlapping(4..6)), "ab");
+  assert_eq!(values(tree.overlapping(15..20)), "");
+  assert_eq!(values(tree.overlapping(7..7)), "");
+  assert!(!tree.any_overlap(15..20));
+  assert!(!tree.any_overlap(7..7));
+  assert!(tree.any_overlap(14..15));
+}
+
+// One long interval among many short ones holds the largest end of every
+// subtree above it. Once it is removed, no query past the short ones may find
+// anything, whichever position it had in the tree
+#[test]
+fn removing_the_longest_interval_lowers_the_max_end() {
+  for long_start in 0..64 {
+    let mut tree = IntervalTree::new();
+    let mut model = Model::new();
+    for start in 0..64 {
+      tree.insert(start..start + 1, 0);
+      model.insert((start, start + 1), 0);
+    }
+    tree.insert(long_start..1000, 1);
+    assert!(tree.any_overlap(500..600));
+    assert_eq!(tree.containing(999).count(), 1);
+
+    tree.remove(long_start..1000);
+    assert!(!tree.any_overlap(500..600));
+    assert_eq!(tree.containing(999).count(), 0);
+    assert_eq!(tree.overlapping(64..1000).count(), 0);
+    check(&tree, &model).unwrap();
+  }
+}
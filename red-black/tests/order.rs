@@ -0,0 +1,169 @@
+// Order statistics against a sorted model: select, nth, rank, median and
+// percentile of an RBTree or RBTreeMap must give the same keys as indexing into
+// the keys of a BTreeSet or BTreeMap, on fresh trees and after deletes
+
+use proptest::prelude::*;
+use rbt::{RBTree, RBTreeMap};
+use std::collections::{BTreeMap, BTreeSet};
+
+// The index the nearest rank method picks for the p-th percentile of len keys
+fn nearest_rank(len: usize, p: f64) -> usize {
+  ((p / 100.0 * len as f64).ceil() as usize).max(1) - 1
+}
+
+fn check_set(tree: &RBTree<i32>, model: &BTreeSet<i32>) -> Result<(), TestCaseError> {
+  let keys: Vec<&i32> = model.iter().collect();
+  let len = keys.len();
+  for (n, &key) in keys.iter().enumerate() {
+    prop_assert_eq!(tree.nth(n), Some(key));
+    prop_assert_eq!(tree.select(n + 1), Some(key));
+    prop_assert_eq!(tree.rank(key), n);
+  }
+  prop_assert_eq!(tree.nth(len), None);
+  prop_assert_eq!(tree.select(0), None);
+  prop_assert_eq!(tree.select(len + 1), None);
+
+  prop_assert_eq!(tree.median(), len.checked_sub(1).map(|last| keys[last / 2]));
+  prop_assert_eq!(tree.percentile(0.0), keys.first().copied());
+  prop_assert_eq!(tree.percentile(100.0), keys.last().copied());
+  for &p in [1.0, 25.0, 50.0, 62.5, 99.9].iter() {
+    prop_assert_eq!(
+      tree.percentile(p),
+      keys.get(nearest_rank(len, p)).copied(),
+      "percentile {}",
+      p
+    );
+  }
+  Ok(())
+}
+
+fn check_map(map: &RBTreeMap<i32, i32>, model: &BTreeMap<i32, i32>) -> Result<(), TestCaseError> {
+  let entries: Vec<(&i32, &i32)> = model.iter().collect();
+  let len = entries.len();
+  for (n, &(key, value)) in entries.iter().enumerate() {
+    prop_assert_eq!(map.nth(n), Some((key, value)));
+    prop_assert_eq!(map.select(n + 1), Some((key, value)));
+    prop_assert_eq!(map.rank(key), n);
+  }
+  prop_assert_eq!(map.nth(len), None);
+  prop_assert_eq!(map.select(0), None);
+  prop_assert_eq!(map.select(len + 1), None);
+
+  prop_assert_eq!(
+    map.median(),
+    len.checked_sub(1).map(|last| entries[last / 2])
+  );
+  prop_assert_eq!(map.percentile(0.0), entries.first().copied());
+  prop_assert_eq!(map.percentile(100.0), entries.last().copied());
+  for &p in [1.0, 25.0, 50.0, 62.5, 99.9].iter() {
+    prop_assert_eq!(
+      map.percentile(p),
+      entries.get(nearest_rank(len, p)).copied(),
+      "percentile {}",
+      p
+    );
+  }
+  Ok(())
+}
+
+#[test]
+fn median_takes_the_middle_key_of_an_odd_count() {
+  let tree: RBTree<i32> = (1..=5).collect();
+  assert_eq!(tree.median(), Some(&3));
+  let map: RBTreeMap<i32, char> = vec![(1, 'a'), (2, 'b'), (3, 'c')].into_iter().collect();
+  assert_eq!(map.median(), Some((&2, &'b')));
+}
+
+#[test]
+fn median_takes_the_lower_middle_key_of_an_even_count() {
+  let tree: RBTree<i32> = (1..=6).collect();
+  assert_eq!(tree.median(), Some(&3));
+  let map: RBTreeMap<i32, char> = vec![(1, 'a'), (2, 'b')].into_iter().collect();
+  assert_eq!(map.median(), Some((&1, &'a')));
+}
+
+#[test]
+fn empty_trees_have_no_order_statistics() {
+  let tree: RBTree<i32> = RBTree::new();
+  assert_eq!(tree.select(1), None);
+  assert_eq!(tree.median(), None);
+  assert_eq!(tree.percentile(0.0), None);
+  assert_eq!(tree.percentile(100.0), None);
+  let map: RBTreeMap<i32, i32> = RBTreeMap::new();
+  assert_eq!(map.nth(0), None);
+  assert_eq!(map.median(), None);
+}
+
+#[test]
+#[should_panic(expected = "percentile must be between 0 and 100")]
+fn percentile_below_0_panics() {
+  let tree: RBTree<i32> = (1..=5).collect();
+  tree.percentile(-0.5);
+}
+
+#[test]
+#[should_panic(expected = "percentile must be between 0 and 100")]
+fn percentile_above_100_panics() {
+  let tree: RBTree<i32> = (1..=5).collect();
+  tree.percentile(100.5);
+}
+
+#[test]
+#[should_panic(expected = "percentile must be between 0 and 100")]
+fn percentile_of_nan_panics() {
+  let tree: RBTree<i32> = (1..=5).collect();
+  tree.percentile(f64::NAN);
+}
+
+#[test]
+#[should_panic(expected = "percentile must be between 0 and 100")]
+fn percentile_of_nan_panics_on_an_empty_map() {
+  let map: RBTreeMap<i32, i32> = RBTreeMap::new();
+  map.percentile(f64::NAN);
+}
+
+proptest! {
+    #[test]
+    fn set_matches_sorted_keys_after_deletes(
+        inserts in prop::collection::vec(-500..500i32, 0..300),
+        deletes in prop::collection::vec(-500..500i32, 0..300),
+    ) {
+        let mut tree = RBTree::new();
+        let mut model = BTreeSet::new();
+        for &key in &inserts {
+            tree.insert(key);
+            model.insert(key);
+        }
+        check_set(&tree, &model)?;
+        for (step, &key) in deletes.iter().enumerate() {
+            tree.delete(&key);
+            model.remove(&key);
+            if step % 16 == 0 {
+                check_set(&tree, &model)?;
+            }
+        }
+        check_set(&tree, &model)?;
+    }
+
+    #[test]
+    fn map_matches_sorted_entries_after_deletes(
+        inserts in prop::collection::vec((-500..500i32, any::<i32>()), 0..300),
+        deletes in prop::collection::vec(-500..500i32, 0..300),
+    ) {
+        let mut map = RBTreeMap::new();
+        let mut model = BTreeMap::new();
+        for &(key, value) in &inserts {
+            map.insert(key, value);
+            model.insert(key, value);
+        }
+        check_map(&map, &model)?;
+        for (step, &key) in deletes.iter().enumerate() {
+            map.remove(&key);
+            model.remove(&key);
+            if step % 16 == 0 {
+                check_map(&map, &model)?;
+            }
+        }
+        check_map(&map, &model)?;
+    }
+}
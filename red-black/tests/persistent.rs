@@ -0,0 +1,129 @@
+// Model-based tests for the persistent trees: every operation is applied to a
+// random earlier version, not only the latest, and the new version is kept
+// next to a BTreeSet or BTreeMap of what it should hold. After every step all
+// versions so far must still match their model and pass validate(), which is
+// what path copying promises, including Kahrs' deletion
+
+use proptest::prelude::*;
+use rbt::{PersistentRBTree, PersistentRBTreeMap};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Clone, Debug)]
+enum Op {
+  Insert(i32),
+  Delete(i32),
+}
+
+// The version an op starts from is picked by index modulo the versions so far
+fn step(keys: std::ops::Range<i32>) -> impl Strategy<Value = (usize, Op)> {
+  let op = prop_oneof![
+      3 => keys.clone().prop_map(Op::Insert),
+      2 => keys.prop_map(Op::Delete),
+  ];
+  (any::<usize>(), op)
+}
+
+fn run_set(steps: &[(usize, Op)]) -> Result<(), TestCaseError> {
+  let mut history = vec![(PersistentRBTree::new(), BTreeSet::new())];
+  for (at, (from, op)) in steps.iter().enumerate() {
+    let (tree, model) = &history[from % history.len()];
+    let mut model = model.clone();
+    let tree = match *op {
+      Op::Insert(key) => {
+        model.insert(key);
+        tree.insert(key)
+      }
+      Op::Delete(key) => {
+        let deleted = tree.delete(&key);
+        // Deleting a key that is not there hands back the same tree
+        prop_assert_eq!(deleted.ptr_eq(tree), !model.remove(&key));
+        deleted
+      }
+    };
+    history.push((tree, model));
+    for (version, (tree, model)) in history.iter().enumerate() {
+      let report = tree.validate();
+      prop_assert!(
+        report.is_valid(),
+        "step {}, version {}\n{}",
+        at,
+        version,
+        report
+      );
+      prop_assert!(
+        tree.iter().eq(model.iter()),
+        "step {}, version {}\n{}",
+        at,
+        version,
+        tree.pretty_print()
+      );
+      prop_assert_eq!(tree.len(), model.len());
+    }
+  }
+  Ok(())
+}
+
+// Values are the step that wrote them, so a version that sees a later write shows up
+fn run_map(steps: &[(usize, Op)]) -> Result<(), TestCaseError> {
+  let mut history = vec![(PersistentRBTreeMap::new(), BTreeMap::new())];
+  for (at, (from, op)) in steps.iter().enumerate() {
+    let (map, model) = &history[from % history.len()];
+    let mut model = model.clone();
+    let map = match *op {
+      Op::Insert(key) => {
+        model.insert(key, at);
+        map.insert(key, at)
+      }
+      Op::Delete(key) => {
+        model.remove(&key);
+        map.remove(&key)
+      }
+    };
+    history.push((map, model));
+    for (version, (map, model)) in history.iter().enumerate() {
+      let report = map.validate();
+      prop_assert!(
+        report.is_valid(),
+        "step {}, version {}\n{}",
+        at,
+        version,
+        report
+      );
+      prop_assert!(
+        map.iter().eq(model.iter()),
+        "step {}, version {}",
+        at,
+        version
+      );
+      for key in model.keys() {
+        prop_assert_eq!(map.get(key), model.get(key));
+      }
+    }
+  }
+  Ok(())
+}
+
+proptest! {
+    #[test]
+    fn set_versions_match_their_btreeset(steps in prop::collection::vec(step(0..32), 0..120)) {
+        run_set(&steps)?;
+    }
+
+    #[test]
+    fn map_versions_match_their_btreemap(steps in prop::collection::vec(step(0..32), 0..120)) {
+        run_map(&steps)?;
+    }
+}
+
+// A straight line of versions growing and then shrinking, each one checked
+// against the keys it had when it was made. Step i starts from version i,
+// the one made by the step before it
+#[test]
+fn old_versions_survive_every_later_change() {
+  let ops = (0..100)
+    .map(Op::Insert)
+    .chain((0..100).map(|key| Op::Delete(key * 7 % 100)));
+  let steps: Vec<(usize, Op)> = ops.enumerate().collect();
+  run_set(&steps).unwrap();
+  run_map(&steps).unwrap();
+}
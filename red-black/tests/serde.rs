@@ -0,0 +1,113 @@
+// Serde round trips through serde_json, run with --features serde
+#![cfg(feature = "serde")]
+
+use rbt::{DuplicatePolicy, RBTree, RBTreeMap};
+
+fn round_trip(tree: &RBTree<i32>) -> RBTree<i32> {
+  let json = serde_json::to_string(tree).unwrap();
+  serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn multiset_round_trips_with_its_policy() {
+  let mut tree = RBTree::with_duplicates(DuplicatePolicy::Count);
+  for key in [2, 1, 3, 2, 1, 2] {
+    tree.insert(key);
+  }
+  let json = serde_json::to_string(&tree).unwrap();
+  assert_eq!(json, r#"{"duplicates":"Count","keys":[1,1,2,2,2,3]}"#);
+
+  let copy = round_trip(&tree);
+  assert!(copy.validate().is_valid());
+  assert_eq!(copy.duplicates(), DuplicatePolicy::Count);
+  assert_eq!(copy.iter().collect::<Vec<_>>(), [&1, &1, &2, &2, &2, &3]);
+  assert_eq!(copy.count_of(&2), 3);
+  assert!(copy.iter().eq(tree.iter()));
+}
+
+#[test]
+fn set_round_trips_with_its_policy() {
+  for policy in [DuplicatePolicy::Reject, DuplicatePolicy::Replace] {
+    let mut tree = RBTree::with_duplicates(policy);
+    for key in 0..100 {
+      tree.insert(key * 7 % 100);
+    }
+    let copy = round_trip(&tree);
+    assert!(copy.validate().is_valid());
+    assert_eq!(copy.duplicates(), policy);
+    assert!(copy.iter().eq(tree.iter()));
+  }
+  assert!(round_trip(&RBTree::new()).is_empty());
+}
+
+#[test]
+fn repeated_keys_need_a_count_policy() {
+  let json = r#"{"duplicates":"Reject","keys":[1,1,2]}"#;
+  assert!(serde_json::from_str::<RBTree<i32>>(json).is_err());
+  let json = r#"{"duplicates":"Count","keys":[1,1,2]}"#;
+  assert_eq!(serde_json::from_str::<RBTree<i32>>(json).unwrap().count(), 3);
+  // Count still needs the keys in order
+  let json = r#"{"duplicates":"Count","keys":[2,1,1]}"#;
+  assert!(serde_json::from_str::<RBTree<i32>>(json).is_err());
+}
+
+#[test]
+fn map_round_trips() {
+  let map: RBTreeMap<i32, String> = (0..50).map(|key| (key, key.to_string())).collect();
+  let json = serde_json::to_string(&map).unwrap();
+  let copy: RBTreeMap<i32, String> = serde_json::from_str(&json).unwrap();
+  assert!(copy.validate().is_valid());
+  assert!(copy.iter().eq(map.iter()));
+}
+
+#[test]
+fn sets_reject_unsorted_keys() {
+  for policy in ["Reject", "Replace", "Count"] {
+    for keys in ["[2,1]", "[3,1,2]", "[1,2,4,3]", "[5,4,3,2,1]"] {
+      let json = format!(r#"{{"duplicates":"{}","keys":{}}}"#, policy, keys);
+      let error = serde_json::from_str::<RBTree<i32>>(&json).unwrap_err();
+      assert!(
+        error.to_string().contains("ascending order"),
+        "{}: {}",
+        json,
+        error
+      );
+    }
+  }
+  // The sequence form of the struct is checked the same way
+  assert!(serde_json::from_str::<RBTree<i32>>(r#"["Reject",[1,3,2]]"#).is_err());
+  assert!(serde_json::from_str::<RBTree<i32>>(r#"["Reject",[1,2,3]]"#).is_ok());
+}
+
+#[test]
+fn sets_reject_repeated_keys_under_reject_and_replace() {
+  for policy in ["Reject", "Replace"] {
+    let json = format!(r#"{{"duplicates":"{}","keys":[1,2,3,3]}}"#, policy);
+    let error = serde_json::from_str::<RBTree<i32>>(&json).unwrap_err();
+    assert!(
+      error.to_string().contains("strictly ascending"),
+      "{}",
+      error
+    );
+  }
+}
+
+#[test]
+fn maps_reject_unsorted_or_repeated_keys() {
+  for json in [
+    r#"{"2":"b","1":"a"}"#,
+    r#"{"1":"a","3":"c","2":"b"}"#,
+    r#"{"1":"a","1":"b"}"#,
+  ] {
+    let error = serde_json::from_str::<RBTreeMap<i32, String>>(json).unwrap_err();
+    assert!(
+      error.to_string().contains("strictly ascending"),
+      "{}: {}",
+      json,
+      error
+    );
+  }
+  let map: RBTreeMap<i32, String> = serde_json::from_str(r#"{"1":"a","2":"b"}"#).unwrap();
+  assert!(map.validate().is_valid());
+  assert_eq!(map.len(), 2);
+}
@@ -0,0 +1,243 @@
+// Snapshot tests: trees are saved and loaded back with the same shape and
+// colors, and every way a file can be cut short, damaged, foreign or describe
+// a broken tree is a SnapshotError rather than a panic or a bad tree
+
+use proptest::prelude::*;
+use rbt::{DuplicatePolicy, RBTree, RBTreeMap};
+use tree_core::snapshot::{self, Codec, Header, NodeTag, SnapshotError, TreeKind};
+
+fn save(tree: &RBTree<i32>) -> Vec<u8> {
+  let mut bytes = Vec::new();
+  tree.save_to(&mut bytes).unwrap();
+  bytes
+}
+
+// A tree whose shape and colors came from inserts and deletes rather than a
+// bulk build
+fn shuffled_tree(policy: DuplicatePolicy) -> RBTree<i32> {
+  let mut tree = RBTree::with_duplicates(policy);
+  for key in 0..60 {
+    tree.insert(key * 37 % 50);
+  }
+  for key in (0..50).step_by(3) {
+    tree.delete(&key);
+  }
+  tree
+}
+
+// The header of a version 1 red-black snapshot with len keys
+fn header(len: usize, duplicates: DuplicatePolicy) -> Vec<u8> {
+  let mut bytes = Vec::new();
+  let header = Header { len, duplicates };
+  snapshot::write_header(&mut bytes, TreeKind::RedBlack, header).unwrap();
+  bytes
+}
+
+// Appends one node of a set as save_to writes it
+fn node(bytes: &mut Vec<u8>, has_left: bool, has_right: bool, red: bool, key: i32) {
+  let tag = NodeTag {
+    has_left,
+    has_right,
+    red,
+  };
+  tag.write(bytes).unwrap();
+  key.encode(bytes).unwrap();
+}
+
+fn corrupt(bytes: &[u8]) -> bool {
+  matches!(
+    RBTree::<i32>::load_from(bytes),
+    Err(SnapshotError::Corrupt(_))
+  )
+}
+
+#[test]
+fn round_trip_keeps_shape_colors_and_policy() {
+  for &policy in [
+    DuplicatePolicy::Reject,
+    DuplicatePolicy::Replace,
+    DuplicatePolicy::Count,
+  ]
+  .iter()
+  {
+    let tree = shuffled_tree(policy);
+    let bytes = save(&tree);
+    let copy = RBTree::<i32>::load_from(&bytes[..]).unwrap();
+    assert!(copy.validate().is_valid());
+    assert_eq!(copy.duplicates(), policy);
+    // The pretty print shows the color of every node
+    assert_eq!(copy.pretty_print(), tree.pretty_print());
+    assert!(copy.preorder().eq(tree.preorder()));
+    assert_eq!(save(&copy), bytes);
+  }
+
+  let empty = save(&RBTree::new());
+  assert!(RBTree::<i32>::load_from(&empty[..]).unwrap().is_empty());
+}
+
+#[test]
+fn map_round_trip_keeps_values() {
+  let mut map = RBTreeMap::new();
+  for key in 0..40 {
+    map.insert(key * 7 % 40, key.to_string());
+  }
+  let mut bytes = Vec::new();
+  map.save_to(&mut bytes).unwrap();
+  let copy = RBTreeMap::<i32, String>::load_from(&bytes[..]).unwrap();
+  assert!(copy.validate().is_valid());
+  assert!(copy.iter().eq(map.iter()));
+  assert_eq!(copy.pretty_print(), map.pretty_print());
+}
+
+#[test]
+fn maps_do_not_load_multisets() {
+  let bytes = save(&shuffled_tree(DuplicatePolicy::Count));
+  assert!(matches!(
+    RBTreeMap::<i32, ()>::load_from(&bytes[..]),
+    Err(SnapshotError::Corrupt(_))
+  ));
+}
+
+#[test]
+fn every_truncation_is_truncated() {
+  let bytes = save(&shuffled_tree(DuplicatePolicy::Count));
+  for end in 0..bytes.len() {
+    let result = RBTree::<i32>::load_from(&bytes[..end]);
+    assert!(
+      matches!(result, Err(SnapshotError::Truncated)),
+      "cut at {} of {}: {:?}",
+      end,
+      bytes.len(),
+      result.map(|tree| tree.pretty_print())
+    );
+  }
+}
+
+#[test]
+fn flipped_bits_never_load_a_broken_tree() {
+  let bytes = save(&shuffled_tree(DuplicatePolicy::Reject));
+  for at in 0..bytes.len() {
+    for bit in 0..8 {
+      let mut damaged = bytes.clone();
+      damaged[at] ^= 1 << bit;
+      // A flipped key can still make a valid tree, anything else is an error
+      if let Ok(tree) = RBTree::<i32>::load_from(&damaged[..]) {
+        assert!(tree.validate().is_valid(), "bit {} of byte {}", bit, at);
+        assert_eq!(tree.iter().count(), tree.count() as usize);
+      }
+    }
+  }
+}
+
+#[test]
+fn foreign_files_are_rejected_by_their_header() {
+  let bytes = save(&shuffled_tree(DuplicatePolicy::Reject));
+
+  let mut magic = bytes.clone();
+  magic[0] = b'X';
+  assert!(matches!(
+    RBTree::<i32>::load_from(&magic[..]),
+    Err(SnapshotError::BadMagic)
+  ));
+
+  // The version is the u16 after the magic
+  let mut version = bytes.clone();
+  version[4..6].copy_from_slice(&2u16.to_le_bytes());
+  assert!(matches!(
+    RBTree::<i32>::load_from(&version[..]),
+    Err(SnapshotError::UnsupportedVersion(2))
+  ));
+
+  // The tree kind is the byte after the version, 1 is AVL
+  let mut kind = bytes.clone();
+  kind[6] = 1;
+  assert!(matches!(
+    RBTree::<i32>::load_from(&kind[..]),
+    Err(SnapshotError::WrongKind {
+      expected: TreeKind::RedBlack,
+      found: 1
+    })
+  ));
+
+  let mut policy = bytes;
+  policy[7] = 9;
+  assert!(corrupt(&policy));
+}
+
+#[test]
+fn readable_files_that_break_the_invariants_are_corrupt() {
+  let reject = DuplicatePolicy::Reject;
+
+  let mut red_root = header(1, reject);
+  node(&mut red_root, false, false, true, 1);
+  assert!(corrupt(&red_root));
+
+  // A black 1 with a red 2 that has a red 3 under it
+  let mut red_under_red = header(3, reject);
+  node(&mut red_under_red, false, true, false, 1);
+  node(&mut red_under_red, false, true, true, 2);
+  node(&mut red_under_red, false, false, true, 3);
+  assert!(corrupt(&red_under_red));
+
+  // A black 2 with a black 1 on its left and nothing on its right
+  let mut black_height = header(2, reject);
+  node(&mut black_height, true, false, false, 2);
+  node(&mut black_height, false, false, false, 1);
+  assert!(corrupt(&black_height));
+
+  // Balanced and well colored but 3 hangs left of 2
+  let mut unordered = header(3, reject);
+  node(&mut unordered, true, true, false, 2);
+  node(&mut unordered, false, false, true, 3);
+  node(&mut unordered, false, false, true, 1);
+  assert!(corrupt(&unordered));
+
+  // A repeated key is only in order for a tree that keeps duplicates
+  let mut repeated = header(2, reject);
+  node(&mut repeated, false, true, false, 1);
+  node(&mut repeated, false, false, true, 1);
+  assert!(corrupt(&repeated));
+  let mut counted = header(2, DuplicatePolicy::Count);
+  counted.extend_from_slice(&repeated[header(2, reject).len()..]);
+  assert_eq!(RBTree::<i32>::load_from(&counted[..]).unwrap().count(), 2);
+
+  // The header counts one node more or one less than follow
+  let mut short = header(2, reject);
+  node(&mut short, false, false, false, 1);
+  assert!(corrupt(&short));
+  let mut long = header(1, reject);
+  node(&mut long, false, true, false, 1);
+  node(&mut long, false, false, true, 2);
+  assert!(corrupt(&long));
+
+  // Tag bits that mean nothing
+  let mut tag = header(1, reject);
+  tag.extend_from_slice(&[0x80, 1, 0, 0, 0]);
+  assert!(corrupt(&tag));
+}
+
+#[test]
+fn deep_chains_are_rejected_before_the_stack_runs_out() {
+  let depth = snapshot::MAX_DEPTH + 1;
+  let mut bytes = header(depth, DuplicatePolicy::Reject);
+  for key in 0..depth {
+    node(&mut bytes, false, key + 1 < depth, false, key as i32);
+  }
+  assert!(corrupt(&bytes));
+}
+
+proptest! {
+  #[test]
+  fn random_trees_round_trip(keys in prop::collection::vec(-500..500i32, 0..200)) {
+    let mut tree = RBTree::new();
+    for key in keys {
+      if !tree.insert(key) {
+        tree.delete(&key);
+      }
+    }
+    let bytes = save(&tree);
+    let copy = RBTree::<i32>::load_from(&bytes[..]).unwrap();
+    prop_assert_eq!(copy.pretty_print(), tree.pretty_print());
+    prop_assert_eq!(save(&copy), bytes);
+  }
+}
@@ -0,0 +1,152 @@
+// Split, split_off, join and append against BTreeSet and BTreeMap, with
+// validate() on every tree they leave behind. Keys come from a small range so
+// split points land on keys, between them and past both ends
+
+use proptest::prelude::*;
+use rbt::{RBTree, RBTreeMap};
+use std::collections::BTreeSet;
+
+fn tree_of(keys: &BTreeSet<i32>) -> RBTree<i32> {
+  let mut tree = RBTree::new();
+  for &key in keys {
+    tree.insert(key);
+  }
+  tree
+}
+
+fn check(tree: &RBTree<i32>, model: &BTreeSet<i32>) -> Result<(), TestCaseError> {
+  let report = tree.validate();
+  prop_assert!(report.is_valid(), "{}", report);
+  prop_assert!(tree.iter().eq(model.iter()), "{}", tree.pretty_print());
+  prop_assert_eq!(tree.count() as usize, model.len());
+  Ok(())
+}
+
+fn keys() -> impl Strategy<Value = BTreeSet<i32>> {
+  prop::collection::btree_set(0..100i32, 0..80)
+}
+
+proptest! {
+    #[test]
+    fn split_off_matches_btreeset(model in keys(), at in -5..105i32) {
+        let mut tree = tree_of(&model);
+        let mut model = model;
+        let upper = tree.split_off(&at);
+        let model_upper = model.split_off(&at);
+        check(&tree, &model)?;
+        check(&upper, &model_upper)?;
+    }
+
+    #[test]
+    fn split_matches_btreeset(model in keys(), at in -5..105i32) {
+        let (lower, upper) = tree_of(&model).split(&at);
+        check(&lower, &model.range(..at).copied().collect())?;
+        check(&upper, &model.range(at..).copied().collect())?;
+    }
+
+    #[test]
+    fn join_matches_btreeset(model in keys(), pivot in 0..100i32) {
+        let mut lower = model.clone();
+        let upper = lower.split_off(&(pivot + 1));
+        lower.remove(&pivot);
+        let joined = RBTree::join(tree_of(&lower), pivot, tree_of(&upper));
+        let mut model = model;
+        model.insert(pivot);
+        check(&joined, &model)?;
+    }
+
+    #[test]
+    fn append_matches_btreeset(left in keys(), right in keys(), shift in -120..120i32) {
+        // A shift far enough apart makes the trees disjoint in either order
+        let mut right: BTreeSet<i32> = right.into_iter().map(|key| key + shift).collect();
+        let mut tree = tree_of(&left);
+        let mut other = tree_of(&right);
+        let mut model = left;
+        tree.append(&mut other);
+        model.append(&mut right);
+        check(&tree, &model)?;
+        check(&other, &right)?;
+    }
+
+    #[test]
+    fn map_split_join_and_append_match_btreemap(
+        entries in prop::collection::btree_map(0..100i32, any::<u8>(), 0..80),
+        at in -5..105i32,
+    ) {
+        let mut map: RBTreeMap<i32, u8> = entries.iter().map(|(&k, &v)| (k, v)).collect();
+        let mut model = entries.clone();
+        let mut upper = map.split_off(&at);
+        let mut model_upper = model.split_off(&at);
+        prop_assert!(map.validate().is_valid() && upper.validate().is_valid());
+        prop_assert!(map.iter().eq(model.iter()));
+        prop_assert!(upper.iter().eq(model_upper.iter()));
+
+        map.append(&mut upper);
+        model.append(&mut model_upper);
+        prop_assert!(map.validate().is_valid() && upper.is_empty());
+        prop_assert!(map.iter().eq(model.iter()));
+
+        let (lower, mut upper) = map.split(&at);
+        upper.remove(&at);
+        let joined = RBTreeMap::join(lower, at, 7, upper);
+        model.insert(at, 7);
+        prop_assert!(joined.validate().is_valid());
+        prop_assert!(joined.iter().eq(model.iter()));
+    }
+}
+
+#[test]
+fn splits_at_the_ends_and_of_empty_trees() {
+  let model: BTreeSet<i32> = (0..50).collect();
+  for &at in &[-1, 0, 49, 50] {
+    let (lower, upper) = tree_of(&model).split(&at);
+    check(&lower, &model.range(..at).copied().collect()).unwrap();
+    check(&upper, &model.range(at..).copied().collect()).unwrap();
+  }
+  let (lower, upper) = RBTree::<i32>::new().split(&3);
+  assert!(lower.is_empty() && upper.is_empty());
+}
+
+#[test]
+fn joins_trees_of_very_different_heights() {
+  for small in 0..20 {
+    let lower: BTreeSet<i32> = (0..small).collect();
+    let upper: BTreeSet<i32> = (1000..2000).collect();
+    let joined = RBTree::join(tree_of(&lower), 500, tree_of(&upper));
+    let mut model = lower.clone();
+    model.insert(500);
+    model.extend(&upper);
+    check(&joined, &model).unwrap();
+
+    let joined = RBTree::join(
+      tree_of(&upper),
+      5000,
+      tree_of(&lower.iter().map(|k| k + 6000).collect()),
+    );
+    let mut model = upper.clone();
+    model.insert(5000);
+    model.extend(lower.iter().map(|k| k + 6000));
+    check(&joined, &model).unwrap();
+  }
+}
+
+#[test]
+fn appends_identical_and_empty_trees() {
+  let model: BTreeSet<i32> = (0..30).collect();
+  let mut tree = tree_of(&model);
+  tree.append(&mut tree_of(&model));
+  check(&tree, &model).unwrap();
+  tree.append(&mut RBTree::new());
+  check(&tree, &model).unwrap();
+  let mut empty = RBTree::new();
+  empty.append(&mut tree);
+  check(&empty, &model).unwrap();
+  check(&tree, &BTreeSet::new()).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "join needs every key of left below")]
+fn join_rejects_keys_on_the_wrong_side() {
+  let left: BTreeSet<i32> = (0..10).collect();
+  RBTree::join(tree_of(&left), 5, RBTree::new());
+}
@@ -0,0 +1,132 @@
+use std::convert::TryFrom;
+use std::ops::{Index, IndexMut};
+
+/// Index of a node inside an [`Arena`]
+pub type NodeId = u32;
+
+/// Link from one node to another, `None` where a pointer tree would have a
+/// null child or parent
+pub type Link = Option<NodeId>;
+
+#[derive(Clone, Debug)]
+enum Slot<N> {
+    Occupied(N),
+    // holds the next slot of the free list
+    Vacant(Link),
+}
+
+/// Node storage for the arena-backed trees. Nodes live in one `Vec` and refer
+/// to each other by index, slots of removed nodes are threaded onto a free
+/// list and handed out again by the next `alloc`.
+#[derive(Clone, Debug)]
+pub struct Arena<N> {
+    slots: Vec<Slot<N>>,
+    free: Link,
+    len: usize,
+}
+
+impl<N> Arena<N> {
+    /// Creates an empty arena
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: None,
+            len: 0,
+        }
+    }
+
+    /// Creates an empty arena with room for `capacity` nodes
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            free: None,
+            len: 0,
+        }
+    }
+
+    /// Stores `node`, reusing a freed slot if there is one, and returns its id
+    pub fn alloc(&mut self, node: N) -> NodeId {
+        self.len += 1;
+        match self.free {
+            Some(id) => {
+                let slot = std::mem::replace(&mut self.slots[id as usize], Slot::Occupied(node));
+                match slot {
+                    Slot::Vacant(next) => self.free = next,
+                    Slot::Occupied(_) => panic!("free list points at an occupied slot"),
+                }
+                id
+            }
+            None => {
+                let id = NodeId::try_from(self.slots.len()).expect("arena is full");
+                self.slots.push(Slot::Occupied(node));
+                id
+            }
+        }
+    }
+
+    /// Removes the node `id` and returns it, its slot goes onto the free list
+    pub fn free(&mut self, id: NodeId) -> N {
+        let slot = std::mem::replace(&mut self.slots[id as usize], Slot::Vacant(self.free));
+        match slot {
+            Slot::Occupied(node) => {
+                self.free = Some(id);
+                self.len -= 1;
+                node
+            }
+            Slot::Vacant(_) => panic!("node {} was already freed", id),
+        }
+    }
+
+    /// Returns the node `id`, `None` if its slot is free or out of bounds
+    pub fn get(&self, id: NodeId) -> Option<&N> {
+        match self.slots.get(id as usize) {
+            Some(Slot::Occupied(node)) => Some(node),
+            _ => None,
+        }
+    }
+
+    /// Returns the node `id` mutably, `None` if its slot is free or out of bounds
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut N> {
+        match self.slots.get_mut(id as usize) {
+            Some(Slot::Occupied(node)) => Some(node),
+            _ => None,
+        }
+    }
+
+    /// Number of nodes stored
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the arena holds no nodes
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Drops every node and the free list, keeping the allocation
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.free = None;
+        self.len = 0;
+    }
+}
+
+impl<N> Default for Arena<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N> Index<NodeId> for Arena<N> {
+    type Output = N;
+
+    fn index(&self, id: NodeId) -> &N {
+        self.get(id).expect("node id does not refer to a live node")
+    }
+}
+
+impl<N> IndexMut<NodeId> for Arena<N> {
+    fn index_mut(&mut self, id: NodeId) -> &mut N {
+        self.get_mut(id).expect("node id does not refer to a live node")
+    }
+}
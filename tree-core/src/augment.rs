@@ -0,0 +1,101 @@
+use crate::traverse::{after_start, before_end, check_range, NodeHandle};
+use std::borrow::Borrow;
+use std::ops::RangeBounds;
+
+/// A summary of the entries in a subtree that the trees keep on every node,
+/// like a sum of weights or the largest value. It is built from the summary
+/// of a single entry and a way to join the summaries of two runs of entries
+/// that sit next to each other in key order, so `combine` has to be
+/// associative but need not be commutative.
+///
+/// The trees recompute it on every node whose subtree changes during
+/// insertion, deletion and rotations, so reading the summary of a whole tree
+/// is O(1) and `fold_range` is O(log n). `validate` compares the stored
+/// summary of every node with one recomputed from its entry and children,
+/// which is what `PartialEq` is needed for.
+pub trait Aggregate<K, V>: Clone + PartialEq {
+    /// The summary of a subtree holding only this entry
+    fn from_entry(key: &K, value: &V) -> Self;
+
+    /// The summary of the entries of `self` followed by those of `right`
+    fn combine(&self, right: &Self) -> Self;
+}
+
+/// No aggregate, the default for trees that do not need one
+impl<K, V> Aggregate<K, V> for () {
+    fn from_entry(_: &K, _: &V) -> Self {}
+
+    fn combine(&self, _: &Self) -> Self {}
+}
+
+/// A node handle that also exposes the aggregate of its subtree
+pub trait Augmented: NodeHandle {
+    type Aggregate;
+
+    /// The aggregate of the subtree rooted at this node
+    fn aggregate(self) -> Self::Aggregate;
+}
+
+/// The aggregate of a node from its entry and the aggregates of its children
+pub fn summarize<K, V, A>(key: &K, value: &V, left: Option<&A>, right: Option<&A>) -> A
+where
+    A: Aggregate<K, V>,
+{
+    let entry = A::from_entry(key, value);
+    let with_left = match left {
+        Some(l) => l.combine(&entry),
+        None => entry,
+    };
+    match right {
+        Some(r) => with_left.combine(r),
+        None => with_left,
+    }
+}
+
+/// Combines the entries under `root` with keys in `range`, `None` if there
+/// are none. Whole subtrees inside the range contribute their stored
+/// aggregate, so only the paths to the two ends of the range are walked.
+/// Panics like `BTreeMap::range` on a range that starts after it ends.
+pub fn fold_range<'a, N, K, V, A, Q, R>(root: Option<N>, range: R) -> Option<A>
+where
+    N: Augmented<Aggregate = &'a A> + NodeHandle<Key = &'a K, Value = &'a V>,
+    K: Borrow<Q> + 'a,
+    V: 'a,
+    A: Aggregate<K, V> + 'a,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    check_range(&range);
+    fold(root, &range, true, true)
+}
+
+/// Folds the subtree under `node`, the flags say which ends of the range can
+/// still cut through it
+fn fold<'a, N, K, V, A, Q, R>(node: Option<N>, range: &R, start: bool, end: bool) -> Option<A>
+where
+    N: Augmented<Aggregate = &'a A> + NodeHandle<Key = &'a K, Value = &'a V>,
+    K: Borrow<Q> + 'a,
+    V: 'a,
+    A: Aggregate<K, V> + 'a,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    let n = node?;
+    if !start && !end {
+        return Some(n.aggregate().clone());
+    }
+
+    let (key, value) = n.entry();
+    if start && !after_start(key.borrow(), range) {
+        return fold(n.right(), range, start, end);
+    }
+    if end && !before_end(key.borrow(), range) {
+        return fold(n.left(), range, start, end);
+    }
+
+    // the node is inside the range, everything right of it is past the start
+    // and everything left of it is before the end
+    let left = fold(n.left(), range, start, false);
+    let right = fold(n.right(), range, false, end);
+    Some(summarize(key, value, left.as_ref(), right.as_ref()))
+}
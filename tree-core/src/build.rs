@@ -0,0 +1,103 @@
+use crate::DuplicatePolicy;
+
+/// True if the keys of `entries` are in strictly ascending order
+pub fn is_strictly_ascending<K: Ord, V>(entries: &[(K, V)]) -> bool {
+    entries.windows(2).all(|pair| pair[0].0 < pair[1].0)
+}
+
+/// Sorts `entries` by key and keeps only the last entry of every key, which
+/// is what inserting them one by one would leave in a tree. Input that is
+/// already strictly ascending is only checked, in O(n).
+pub fn sort_dedup<K: Ord, V>(entries: &mut Vec<(K, V)>) {
+    if is_strictly_ascending(entries) {
+        return;
+    }
+    // the sort is stable, so the last entry of a key ends up last in its run
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries.reverse();
+    entries.dedup_by(|a, b| a.0 == b.0);
+    entries.reverse();
+}
+
+/// Sorts `entries` by key, keeping entries of equal keys in the order they
+/// came in. Input that is already ascending is only checked, in O(n).
+pub fn sort_stable<K: Ord, V>(entries: &mut [(K, V)]) {
+    if !entries.windows(2).all(|pair| pair[0].0 <= pair[1].0) {
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+}
+
+/// Merges two ascending runs of entries into what inserting the entries of
+/// `newer` one by one, in their order, leaves in a tree that holds `older`
+/// and follows `duplicates`. `Reject` keeps the first entry of a key,
+/// `Replace` the last one and `Count` all of them, those of `older` first.
+pub fn merge_entries<K: Ord, V>(
+    older: Vec<(K, V)>,
+    newer: Vec<(K, V)>,
+    duplicates: DuplicatePolicy,
+) -> Vec<(K, V)> {
+    merge_runs(older, newer, |merged, mut run| match duplicates {
+        DuplicatePolicy::Reject => merged.extend(run.into_iter().next()),
+        DuplicatePolicy::Replace => merged.extend(run.pop()),
+        DuplicatePolicy::Count => merged.extend(run),
+    })
+}
+
+/// Same as `merge_entries` for a map whose insert keeps the stored key and
+/// swaps in the new value, like `BTreeMap::insert`. A key ends up with the
+/// key of its first entry and the value of its last one.
+pub fn merge_values<K: Ord, V>(older: Vec<(K, V)>, newer: Vec<(K, V)>) -> Vec<(K, V)> {
+    merge_runs(older, newer, |merged, mut run| {
+        if let Some((_, value)) = run.pop() {
+            let key = run.into_iter().next().map(|(key, _)| key);
+            merged.extend(key.map(|key| (key, value)));
+        }
+    })
+}
+
+/// Merges two ascending runs of entries, handing every key that is in more
+/// than one entry to `resolve` as the run of its entries, those of `older`
+/// first
+fn merge_runs<K: Ord, V, F>(older: Vec<(K, V)>, newer: Vec<(K, V)>, mut resolve: F) -> Vec<(K, V)>
+where
+    F: FnMut(&mut Vec<(K, V)>, Vec<(K, V)>),
+{
+    let mut merged = Vec::with_capacity(older.len() + newer.len());
+    let mut older = older.into_iter().peekable();
+    let mut newer = newer.into_iter().peekable();
+    loop {
+        // on equal keys the entry of older comes first
+        let entry = match (older.peek(), newer.peek()) {
+            (Some(o), Some(n)) if n.0 < o.0 => newer.next(),
+            (Some(_), _) => older.next(),
+            (None, _) => newer.next(),
+        };
+        let entry = match entry {
+            Some(entry) => entry,
+            None => break,
+        };
+        let repeats = |next: Option<&(K, V)>| next.is_some_and(|next| next.0 == entry.0);
+        if !repeats(older.peek()) && !repeats(newer.peek()) {
+            merged.push(entry);
+            continue;
+        }
+        // both runs ascend, so the other entries of the key are next in them
+        let mut run = vec![entry];
+        while older.peek().is_some_and(|next| next.0 == run[0].0) {
+            run.extend(older.next());
+        }
+        while newer.peek().is_some_and(|next| next.0 == run[0].0) {
+            run.extend(newer.next());
+        }
+        resolve(&mut merged, run);
+    }
+    merged
+}
+
+/// True if adding `added` entries to a tree of `len` by rebuilding it from
+/// its merged entries in O(n + m) beats inserting them one by one in
+/// O(m log n)
+pub fn should_rebuild(len: usize, added: usize) -> bool {
+    let log = (usize::BITS - len.leading_zeros()) as usize;
+    added.saturating_mul(log) >= len
+}
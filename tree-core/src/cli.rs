@@ -0,0 +1,71 @@
+use crate::SortedTree;
+use std::io::{stdin, stdout, Write};
+
+/// Runs the interactive shell shared by the `avl` and `rbt` binaries on `tree`
+/// until the user types `close`
+pub fn run<S: SortedTree<i32>>(mut tree: S) {
+    loop {
+        let mut s = String::new();
+        print!("insert/delete/print/height/num_leaves/is_empty/inorder: ");
+        stdout().flush().expect("Failed to flush stdout");
+        stdin().read_line(&mut s).expect("Incorrect Command");
+
+        s = String::from(s.trim());
+
+        let mut input_iter = s.split(' ');
+        let input = input_iter.next().unwrap();
+
+        match input {
+            "insert" => {
+                let val = input_iter.next();
+
+                if val.is_none() {
+                    println!("\nInvalid Command, try again\n");
+                    continue;
+                }
+                let val_int: i32 = val.unwrap().parse().unwrap();
+                if !tree.insert(val_int) {
+                    println!("\nKey already exists in the tree\n");
+                }
+            }
+            "height" => {
+                println!("\nTree Height: {}\n", tree.height());
+            }
+            "num_leaves" => {
+                println!("\nNumber of leaves: {}\n", tree.leaf_count());
+            }
+            "is_empty" => {
+                println!(
+                    "\nTree is {}empty\n",
+                    if tree.is_empty() { "" } else { "not " }
+                );
+            }
+            "inorder" => {
+                println!("\nInorder Traversal {:?}\n", tree.inorder());
+            }
+            "delete" => {
+                let val = input_iter.next();
+
+                if val.is_none() {
+                    println!("Invalid Command, try again");
+                    continue;
+                }
+
+                let val_int: i32 = val.unwrap().parse().unwrap();
+
+                if tree.remove(&val_int).is_none() {
+                    println!("\nKey is not in the tree\n");
+                }
+            }
+            "print" => {
+                println!("Tree Pretty Printed: \n{}", tree.render());
+            }
+            "close" => {
+                return;
+            }
+            _ => {
+                println!("\nInvalid Command\n");
+            }
+        };
+    }
+}
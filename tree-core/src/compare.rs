@@ -0,0 +1,34 @@
+use std::cmp::Ordering;
+
+/// Decides the order of the keys in a tree. Trees order their keys with
+/// `Natural`, the `Ord` of the key, unless they are made with another
+/// comparator, and route every comparison in insertion, deletion and search
+/// through it.
+///
+/// Any `Fn(&T, &T) -> Ordering` closure is a comparator, so a descending tree
+/// or one ordered by a field needs no wrapper type around its keys. The
+/// comparator must be a total order and must not change while the tree holds
+/// keys.
+pub trait Comparator<T: ?Sized> {
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
+
+/// The order of `Ord`, the comparator of trees made without one. Lookups on
+/// such trees take any borrowed form of the key, like `BTreeSet`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Natural;
+
+impl<T: Ord + ?Sized> Comparator<T> for Natural {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+impl<T: ?Sized, F> Comparator<T> for F
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
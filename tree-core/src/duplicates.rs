@@ -0,0 +1,13 @@
+/// What inserting a key that is already in a set does
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum DuplicatePolicy {
+    /// Keeps the stored key and drops the new one, like `BTreeSet::insert`
+    #[default]
+    Reject,
+    /// Swaps the new key in for the stored one, like `BTreeSet::replace`. Only
+    /// differs from `Reject` for keys that compare equal but are not identical.
+    Replace,
+    /// Keeps both, so every key is stored as often as it was inserted and a
+    /// delete takes out one copy
+    Count,
+}
@@ -0,0 +1,21 @@
+pub mod arena;
+pub mod augment;
+pub mod build;
+pub mod cli;
+pub mod compare;
+pub mod duplicates;
+pub mod merge;
+pub mod order;
+pub mod pretty;
+#[cfg(feature = "serde")]
+pub mod serde_sorted;
+pub mod snapshot;
+pub mod traverse;
+pub mod validate;
+mod sorted_tree;
+
+pub use arena::Arena;
+pub use augment::Aggregate;
+pub use compare::{Comparator, Natural};
+pub use duplicates::DuplicatePolicy;
+pub use sorted_tree::SortedTree;
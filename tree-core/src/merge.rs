@@ -0,0 +1,90 @@
+use std::cmp::Ordering;
+use std::iter::{FusedIterator, Peekable};
+
+/// Where a key of a `Merge` was found
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeItem<T> {
+    /// Only in the left sequence
+    Left(T),
+    /// Only in the right sequence
+    Right(T),
+    /// In both, with the item of each side
+    Both(T, T),
+}
+
+impl<T> MergeItem<T> {
+    /// The item of the left side if there is one, otherwise the right one
+    pub fn into_left(self) -> T {
+        match self {
+            MergeItem::Left(item) | MergeItem::Right(item) | MergeItem::Both(item, _) => item,
+        }
+    }
+}
+
+/// Walks two strictly ascending sequences side by side in one pass, yielding
+/// every distinct item once in ascending order together with the side it came
+/// from. The set operations of the trees are filters over it, so they take
+/// O(n + m) comparisons and stop as soon as the answer is known.
+pub struct Merge<L: Iterator, R: Iterator> {
+    left: Peekable<L>,
+    right: Peekable<R>,
+}
+
+impl<T, L, R> Merge<L, R>
+where
+    T: Ord,
+    L: Iterator<Item = T>,
+    R: Iterator<Item = T>,
+{
+    /// Merges `left` and `right`, both of which must be strictly ascending
+    pub fn new(left: L, right: R) -> Self {
+        Self {
+            left: left.peekable(),
+            right: right.peekable(),
+        }
+    }
+}
+
+impl<T, L, R> Iterator for Merge<L, R>
+where
+    T: Ord,
+    L: Iterator<Item = T>,
+    R: Iterator<Item = T>,
+{
+    type Item = MergeItem<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let order = match (self.left.peek(), self.right.peek()) {
+            (None, None) => return None,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(l), Some(r)) => l.cmp(r),
+        };
+        match order {
+            Ordering::Less => self.left.next().map(MergeItem::Left),
+            Ordering::Greater => self.right.next().map(MergeItem::Right),
+            Ordering::Equal => match (self.left.next(), self.right.next()) {
+                (Some(l), Some(r)) => Some(MergeItem::Both(l, r)),
+                _ => None,
+            },
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (left_low, left_high) = self.left.size_hint();
+        let (right_low, right_high) = self.right.size_hint();
+        let high = match (left_high, right_high) {
+            (Some(l), Some(r)) => l.checked_add(r),
+            _ => None,
+        };
+        (left_low.max(right_low), high)
+    }
+}
+
+impl<T, L, R> FusedIterator for Merge<L, R>
+where
+    T: Ord,
+    L: FusedIterator<Item = T>,
+    R: FusedIterator<Item = T>,
+{
+}
@@ -0,0 +1,197 @@
+use crate::compare::{Comparator, Natural};
+use crate::traverse::NodeHandle;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+
+/// Number of keys under `root` that are smaller than `key`, found along a
+/// single root-to-leaf path using the subtree sizes. Keys equal to `key` may
+/// sit on both sides of one that is found when a tree keeps duplicates, so
+/// the walk carries on to the left past it.
+pub fn rank<'a, N, K, Q>(root: Option<N>, key: &Q) -> usize
+where
+    N: NodeHandle<Key = &'a K>,
+    K: Borrow<Q> + 'a,
+    Q: Ord + ?Sized,
+{
+    rank_by(root, key, &Natural)
+}
+
+/// `rank` in the order of `cmp`
+pub fn rank_by<'a, N, K, Q, C>(root: Option<N>, key: &Q, cmp: &C) -> usize
+where
+    N: NodeHandle<Key = &'a K>,
+    K: Borrow<Q> + 'a,
+    Q: ?Sized,
+    C: Comparator<Q>,
+{
+    count_below(root, |k| cmp.compare(k.borrow(), key) == Ordering::Less)
+}
+
+/// Number of keys under `root` equal to `key` in the order of `cmp`, 0 or 1
+/// unless the tree keeps duplicates. Takes two root-to-leaf paths.
+pub fn count_by<'a, N, K, Q, C>(root: Option<N>, key: &Q, cmp: &C) -> usize
+where
+    N: NodeHandle<Key = &'a K>,
+    K: Borrow<Q> + 'a,
+    Q: ?Sized,
+    C: Comparator<Q>,
+{
+    count_below(root, |k| cmp.compare(k.borrow(), key) != Ordering::Greater)
+        - rank_by(root, key, cmp)
+}
+
+/// Number of keys under `root` for which `below` holds, where `below` holds
+/// for a prefix of the keys in ascending order
+fn count_below<'a, N, K, F>(root: Option<N>, below: F) -> usize
+where
+    N: NodeHandle<Key = &'a K>,
+    K: 'a,
+    F: Fn(&K) -> bool,
+{
+    let mut count = 0;
+    let mut node = root;
+    while let Some(n) = node {
+        if below(n.entry().0) {
+            count += n.left().map_or(0, NodeHandle::size) + 1;
+            node = n.right();
+        } else {
+            node = n.left();
+        }
+    }
+    count
+}
+
+/// The node at 0-based position `index` in ascending key order, `None` past
+/// the end
+pub fn nth<N: NodeHandle>(root: Option<N>, index: usize) -> Option<N> {
+    let mut index = index;
+    let mut node = root;
+    while let Some(n) = node {
+        let left_size = n.left().map_or(0, NodeHandle::size);
+        match index.cmp(&left_size) {
+            Ordering::Less => node = n.left(),
+            Ordering::Equal => return Some(n),
+            Ordering::Greater => {
+                index -= left_size + 1;
+                node = n.right();
+            }
+        }
+    }
+    None
+}
+
+/// The node with the smallest key under `root`, `None` for an empty tree
+pub fn first<N: NodeHandle>(root: Option<N>) -> Option<N> {
+    let mut node = root?;
+    while let Some(left) = node.left() {
+        node = left;
+    }
+    Some(node)
+}
+
+/// The node with the largest key under `root`, `None` for an empty tree
+pub fn last<N: NodeHandle>(root: Option<N>) -> Option<N> {
+    let mut node = root?;
+    while let Some(right) = node.right() {
+        node = right;
+    }
+    Some(node)
+}
+
+/// The node with the largest key that is not greater than `key` in the order
+/// of `cmp`, the last of them if several are equal to `key`
+pub fn floor_by<'a, N, K, Q, C>(root: Option<N>, key: &Q, cmp: &C) -> Option<N>
+where
+    N: NodeHandle<Key = &'a K>,
+    K: Borrow<Q> + 'a,
+    Q: ?Sized,
+    C: Comparator<Q>,
+{
+    last_below(root, |k| cmp.compare(k.borrow(), key) != Ordering::Greater)
+}
+
+/// The node with the smallest key that is not less than `key` in the order of
+/// `cmp`, the first of them if several are equal to `key`
+pub fn ceiling_by<'a, N, K, Q, C>(root: Option<N>, key: &Q, cmp: &C) -> Option<N>
+where
+    N: NodeHandle<Key = &'a K>,
+    K: Borrow<Q> + 'a,
+    Q: ?Sized,
+    C: Comparator<Q>,
+{
+    first_above(root, |k| cmp.compare(k.borrow(), key) == Ordering::Less)
+}
+
+/// The node with the largest key less than `key` in the order of `cmp`
+pub fn predecessor_by<'a, N, K, Q, C>(root: Option<N>, key: &Q, cmp: &C) -> Option<N>
+where
+    N: NodeHandle<Key = &'a K>,
+    K: Borrow<Q> + 'a,
+    Q: ?Sized,
+    C: Comparator<Q>,
+{
+    last_below(root, |k| cmp.compare(k.borrow(), key) == Ordering::Less)
+}
+
+/// The node with the smallest key greater than `key` in the order of `cmp`
+pub fn successor_by<'a, N, K, Q, C>(root: Option<N>, key: &Q, cmp: &C) -> Option<N>
+where
+    N: NodeHandle<Key = &'a K>,
+    K: Borrow<Q> + 'a,
+    Q: ?Sized,
+    C: Comparator<Q>,
+{
+    first_above(root, |k| cmp.compare(k.borrow(), key) != Ordering::Greater)
+}
+
+/// The last node for which `below` holds, where `below` holds for a prefix of
+/// the keys in ascending order. Takes a single root-to-leaf path.
+fn last_below<'a, N, K, F>(root: Option<N>, below: F) -> Option<N>
+where
+    N: NodeHandle<Key = &'a K>,
+    K: 'a,
+    F: Fn(&K) -> bool,
+{
+    let mut found = None;
+    let mut node = root;
+    while let Some(n) = node {
+        if below(n.entry().0) {
+            found = Some(n);
+            node = n.right();
+        } else {
+            node = n.left();
+        }
+    }
+    found
+}
+
+/// The first node for which `below` does not hold, see `last_below`
+fn first_above<'a, N, K, F>(root: Option<N>, below: F) -> Option<N>
+where
+    N: NodeHandle<Key = &'a K>,
+    K: 'a,
+    F: Fn(&K) -> bool,
+{
+    let mut found = None;
+    let mut node = root;
+    while let Some(n) = node {
+        if below(n.entry().0) {
+            node = n.right();
+        } else {
+            found = Some(n);
+            node = n.left();
+        }
+    }
+    found
+}
+
+/// 0-based position of the `p`th percentile out of `len` keys by the nearest
+/// rank method, `None` for no keys. Panics if `p` is not within 0 to 100.
+pub fn percentile_index(len: usize, p: f64) -> Option<usize> {
+    assert!((0.0..=100.0).contains(&p), "percentile must be between 0 and 100");
+    if len == 0 {
+        return None;
+    }
+    let rank = (p / 100.0 * len as f64).ceil() as usize;
+    Some(rank.max(1) - 1)
+}
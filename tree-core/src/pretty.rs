@@ -0,0 +1,65 @@
+/**
+    algorithmic idea drawn from https://www.baeldung.com/java-print-binary-tree-diagram
+
+    Renders the tree rooted at `root` sideways, one node per line, with the left
+    child drawn above the right one. `label` formats a single node and `children`
+    returns its (left, right) children.
+*/
+pub fn pretty_print<N, L, C>(root: Option<N>, label: L, children: C) -> String
+where
+    L: Fn(&N) -> String,
+    C: Fn(&N) -> (Option<N>, Option<N>),
+{
+    match root {
+        None => "".to_string(),
+        Some(node) => {
+            let mut sb = label(&node);
+            let (left, right) = children(&node);
+
+            let pointer_right = "└──";
+            let pointer_left = if right.is_none() { "└──" } else { "├──" };
+
+            let has_right = right.is_some();
+            pretty_print_helper(&mut sb, "", pointer_left, left, has_right, &label, &children);
+            pretty_print_helper(&mut sb, "", pointer_right, right, false, &label, &children);
+
+            sb
+        }
+    }
+}
+
+// helpers the pretty printer draw recursively
+fn pretty_print_helper<N, L, C>(
+    sb: &mut String,
+    padding: &str,
+    pointer: &str,
+    node: Option<N>,
+    has_right: bool,
+    label: &L,
+    children: &C,
+) where
+    L: Fn(&N) -> String,
+    C: Fn(&N) -> (Option<N>, Option<N>),
+{
+    if let Some(node) = node {
+        sb.push('\n');
+        sb.push_str(padding);
+        sb.push_str(pointer);
+        sb.push_str(&label(&node));
+
+        let mut both_pad = String::from(padding);
+        if has_right {
+            both_pad.push_str("│  ");
+        } else {
+            both_pad.push_str("   ");
+        }
+
+        let (left, right) = children(&node);
+        let pointer_right = "└──";
+        let pointer_left = if right.is_none() { "└──" } else { "├──" };
+
+        let has_right = right.is_some();
+        pretty_print_helper(sb, &both_pad, pointer_left, left, has_right, label, children);
+        pretty_print_helper(sb, &both_pad, pointer_right, right, false, label, children);
+    }
+}
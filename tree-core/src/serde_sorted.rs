@@ -0,0 +1,289 @@
+use crate::{build, DuplicatePolicy};
+use serde::de::{
+    Deserialize, Deserializer, EnumAccess, Error, MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+
+// the size hint comes from the input, so it only reserves this much up front
+const MAX_PREALLOCATED: usize = 4096;
+
+const POLICIES: &[&str] = &["Reject", "Replace", "Count"];
+const SET_FIELDS: &[&str] = &["duplicates", "keys"];
+
+// the duplicate policy of a set and its keys, ready for the tree to be built from
+type Set<T> = (DuplicatePolicy, Vec<(T, ())>);
+
+/// Writes a set as `{duplicates, keys}`, its duplicate policy and then its
+/// keys in ascending order, so a multiset comes back as a multiset
+pub fn serialize_keys<S, I>(
+    serializer: S,
+    duplicates: DuplicatePolicy,
+    keys: I,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    I: IntoIterator + Copy,
+    I::Item: Serialize,
+{
+    let mut set = serializer.serialize_struct("Set", 2)?;
+    set.serialize_field("duplicates", &duplicates)?;
+    set.serialize_field("keys", &Sequence(keys))?;
+    set.end()
+}
+
+/// Reads a set written by `serialize_keys`. The keys are checked instead of
+/// trusted: they have to ascend strictly, or only not descend when the policy
+/// is `Count`, and the tree is then built balanced from the keys that passed
+pub fn deserialize_keys<'de, D, T>(deserializer: D) -> Result<Set<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Ord,
+{
+    deserializer.deserialize_struct("Set", SET_FIELDS, SetVisitor(PhantomData))
+}
+
+/// Reads a map whose keys are in strictly ascending order
+pub fn deserialize_entries<'de, D, K, V>(deserializer: D) -> Result<Vec<(K, V)>, D::Error>
+where
+    D: Deserializer<'de>,
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+{
+    deserializer.deserialize_map(EntriesVisitor(PhantomData))
+}
+
+/// Fails unless the keys of `entries` are in the order `duplicates` allows,
+/// strictly ascending, or non-decreasing for `Count`
+fn check_order<K: Ord, V, E: Error>(
+    entries: &[(K, V)],
+    duplicates: DuplicatePolicy,
+) -> Result<(), E> {
+    if duplicates != DuplicatePolicy::Count {
+        if build::is_strictly_ascending(entries) {
+            return Ok(());
+        }
+        return Err(E::custom("keys are not in strictly ascending order"));
+    }
+    if entries.windows(2).all(|pair| pair[0].0 <= pair[1].0) {
+        Ok(())
+    } else {
+        Err(E::custom("keys are not in ascending order"))
+    }
+}
+
+impl Serialize for DuplicatePolicy {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let index = match self {
+            DuplicatePolicy::Reject => 0,
+            DuplicatePolicy::Replace => 1,
+            DuplicatePolicy::Count => 2,
+        };
+        serializer.serialize_unit_variant("DuplicatePolicy", index, POLICIES[index as usize])
+    }
+}
+
+impl<'de> Deserialize<'de> for DuplicatePolicy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_enum("DuplicatePolicy", POLICIES, PolicyVisitor)
+    }
+}
+
+/// Serializes the items of an iterator that can be walked again as a sequence
+struct Sequence<I>(I);
+
+impl<I> Serialize for Sequence<I>
+where
+    I: IntoIterator + Copy,
+    I::Item: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0)
+    }
+}
+
+struct PolicyVisitor;
+
+impl<'de> Visitor<'de> for PolicyVisitor {
+    type Value = DuplicatePolicy;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a duplicate policy")
+    }
+
+    fn visit_enum<E: EnumAccess<'de>>(self, data: E) -> Result<Self::Value, E::Error> {
+        let (Policy(policy), variant) = data.variant()?;
+        variant.unit_variant()?;
+        Ok(policy)
+    }
+}
+
+/// The name or index of a `DuplicatePolicy` variant
+struct Policy(DuplicatePolicy);
+
+impl<'de> Deserialize<'de> for Policy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_identifier(PolicyNameVisitor)
+    }
+}
+
+struct PolicyNameVisitor;
+
+impl<'de> Visitor<'de> for PolicyNameVisitor {
+    type Value = Policy;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("Reject, Replace or Count")
+    }
+
+    fn visit_u64<E: Error>(self, index: u64) -> Result<Self::Value, E> {
+        match index {
+            0 => Ok(Policy(DuplicatePolicy::Reject)),
+            1 => Ok(Policy(DuplicatePolicy::Replace)),
+            2 => Ok(Policy(DuplicatePolicy::Count)),
+            _ => Err(E::custom(format!("unknown duplicate policy {}", index))),
+        }
+    }
+
+    fn visit_str<E: Error>(self, name: &str) -> Result<Self::Value, E> {
+        match name {
+            "Reject" => Ok(Policy(DuplicatePolicy::Reject)),
+            "Replace" => Ok(Policy(DuplicatePolicy::Replace)),
+            "Count" => Ok(Policy(DuplicatePolicy::Count)),
+            _ => Err(E::unknown_variant(name, POLICIES)),
+        }
+    }
+}
+
+/// A field of the `{duplicates, keys}` struct a set is written as
+enum SetField {
+    Duplicates,
+    Keys,
+}
+
+impl<'de> Deserialize<'de> for SetField {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_identifier(SetFieldVisitor)
+    }
+}
+
+struct SetFieldVisitor;
+
+impl<'de> Visitor<'de> for SetFieldVisitor {
+    type Value = SetField;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("duplicates or keys")
+    }
+
+    fn visit_u64<E: Error>(self, index: u64) -> Result<Self::Value, E> {
+        match index {
+            0 => Ok(SetField::Duplicates),
+            1 => Ok(SetField::Keys),
+            _ => Err(E::custom(format!("unknown field index {}", index))),
+        }
+    }
+
+    fn visit_str<E: Error>(self, name: &str) -> Result<Self::Value, E> {
+        match name {
+            "duplicates" => Ok(SetField::Duplicates),
+            "keys" => Ok(SetField::Keys),
+            _ => Err(E::unknown_field(name, SET_FIELDS)),
+        }
+    }
+}
+
+struct SetVisitor<T>(PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for SetVisitor<T>
+where
+    T: Deserialize<'de> + Ord,
+{
+    type Value = Set<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a set with a duplicate policy and its keys in ascending order")
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<Self::Value, S::Error> {
+        let duplicates = seq
+            .next_element()?
+            .ok_or_else(|| S::Error::invalid_length(0, &self))?;
+        let Keys(keys) = seq
+            .next_element()?
+            .ok_or_else(|| S::Error::invalid_length(1, &self))?;
+        check_order(&keys, duplicates)?;
+        Ok((duplicates, keys))
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+        let mut duplicates = None;
+        let mut keys = None;
+        while let Some(field) = map.next_key()? {
+            match field {
+                SetField::Duplicates if duplicates.is_some() => {
+                    return Err(M::Error::duplicate_field("duplicates"))
+                }
+                SetField::Duplicates => duplicates = Some(map.next_value()?),
+                SetField::Keys if keys.is_some() => return Err(M::Error::duplicate_field("keys")),
+                SetField::Keys => keys = Some(map.next_value::<Keys<T>>()?.0),
+            }
+        }
+        let duplicates = duplicates.ok_or_else(|| M::Error::missing_field("duplicates"))?;
+        let keys = keys.ok_or_else(|| M::Error::missing_field("keys"))?;
+        // the order a set allows depends on its policy, which may come after the keys
+        check_order(&keys, duplicates)?;
+        Ok((duplicates, keys))
+    }
+}
+
+/// The keys of a set as they were read, before their order is checked
+struct Keys<T>(Vec<(T, ())>);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Keys<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(KeysVisitor(PhantomData))
+    }
+}
+
+struct KeysVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for KeysVisitor<T> {
+    type Value = Keys<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of keys")
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<Self::Value, S::Error> {
+        let mut keys = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(MAX_PREALLOCATED));
+        while let Some(key) = seq.next_element()? {
+            keys.push((key, ()));
+        }
+        Ok(Keys(keys))
+    }
+}
+
+struct EntriesVisitor<K, V>(PhantomData<(K, V)>);
+
+impl<'de, K, V> Visitor<'de> for EntriesVisitor<K, V>
+where
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+{
+    type Value = Vec<(K, V)>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map with keys in strictly ascending order")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(MAX_PREALLOCATED));
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        check_order(&entries, DuplicatePolicy::Reject)?;
+        Ok(entries)
+    }
+}
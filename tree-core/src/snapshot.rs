@@ -0,0 +1,309 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::DuplicatePolicy;
+
+/// First bytes of every snapshot
+pub const MAGIC: [u8; 4] = *b"TFSN";
+
+/// Version of the format written by `write_header`. Readers reject any other
+/// version instead of guessing at its layout.
+pub const VERSION: u16 = 1;
+
+/// Deepest tree a snapshot may describe. A balanced tree this deep would need
+/// more nodes than fit in memory, so anything deeper is corrupt, and the limit
+/// keeps a hostile file from overflowing the stack while it is read.
+pub const MAX_DEPTH: usize = 128;
+
+/// Which tree wrote a snapshot, since each records a different shape
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TreeKind {
+    AVL,
+    RedBlack,
+}
+
+impl TreeKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            TreeKind::AVL => 1,
+            TreeKind::RedBlack => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(TreeKind::AVL),
+            2 => Some(TreeKind::RedBlack),
+            _ => None,
+        }
+    }
+}
+
+/// Everything that can go wrong while saving or loading a snapshot
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The reader or writer failed
+    Io(io::Error),
+    /// The input ended in the middle of the snapshot
+    Truncated,
+    /// The input does not start with `MAGIC`, so it is not a snapshot
+    BadMagic,
+    /// The snapshot was written by a version of the format this one cannot read
+    UnsupportedVersion(u16),
+    /// The snapshot holds a different kind of tree than the one loading it
+    WrongKind { expected: TreeKind, found: u8 },
+    /// The bytes are readable but do not describe a valid tree
+    Corrupt(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "snapshot i/o failed: {}", e),
+            SnapshotError::Truncated => write!(f, "snapshot is truncated"),
+            SnapshotError::BadMagic => write!(f, "not a tree snapshot"),
+            SnapshotError::UnsupportedVersion(v) => {
+                write!(f, "snapshot version {} is not supported", v)
+            }
+            SnapshotError::WrongKind { expected, found } => write!(
+                f,
+                "snapshot holds tree kind {}, expected {:?}",
+                found, expected
+            ),
+            SnapshotError::Corrupt(reason) => write!(f, "snapshot is corrupt: {}", reason),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SnapshotError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Running out of input is a truncated snapshot, not an i/o failure
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            SnapshotError::Truncated
+        } else {
+            SnapshotError::Io(e)
+        }
+    }
+}
+
+/// Shorthand for a `SnapshotError::Corrupt`
+pub fn corrupt<T>(reason: impl Into<String>) -> Result<T, SnapshotError> {
+    Err(SnapshotError::Corrupt(reason.into()))
+}
+
+/// A key or value that can be written to and read back from a snapshot.
+/// Integers are little endian and strings are a length followed by UTF-8.
+pub trait Codec: Sized {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<(), SnapshotError>;
+
+    fn decode<R: Read>(reader: &mut R) -> Result<Self, SnapshotError>;
+}
+
+macro_rules! int_codec {
+    ($($t:ty),*) => {
+        $(
+            impl Codec for $t {
+                fn encode<W: Write>(&self, writer: &mut W) -> Result<(), SnapshotError> {
+                    writer.write_all(&self.to_le_bytes())?;
+                    Ok(())
+                }
+
+                fn decode<R: Read>(reader: &mut R) -> Result<Self, SnapshotError> {
+                    let mut bytes = [0; std::mem::size_of::<$t>()];
+                    reader.read_exact(&mut bytes)?;
+                    Ok(<$t>::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+int_codec!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+// the platform sized integers are stored as 64 bits so snapshots move between platforms
+impl Codec for usize {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<(), SnapshotError> {
+        (*self as u64).encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<Self, SnapshotError> {
+        let n = u64::decode(reader)?;
+        usize::try_from(n).or_else(|_| corrupt("usize out of range for this platform"))
+    }
+}
+
+impl Codec for isize {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<(), SnapshotError> {
+        (*self as i64).encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<Self, SnapshotError> {
+        let n = i64::decode(reader)?;
+        isize::try_from(n).or_else(|_| corrupt("isize out of range for this platform"))
+    }
+}
+
+impl Codec for bool {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<(), SnapshotError> {
+        (*self as u8).encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<Self, SnapshotError> {
+        match u8::decode(reader)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => corrupt(format!("{} is not a bool", b)),
+        }
+    }
+}
+
+impl Codec for char {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<(), SnapshotError> {
+        (*self as u32).encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<Self, SnapshotError> {
+        let n = u32::decode(reader)?;
+        char::from_u32(n).map_or_else(|| corrupt(format!("{:#x} is not a char", n)), Ok)
+    }
+}
+
+impl Codec for String {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<(), SnapshotError> {
+        (self.len() as u64).encode(writer)?;
+        writer.write_all(self.as_bytes())?;
+        Ok(())
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<Self, SnapshotError> {
+        let len = u64::decode(reader)?;
+        // read through take so a corrupt length cannot allocate more than the input holds
+        let mut bytes = Vec::new();
+        reader.take(len).read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) < len {
+            return Err(SnapshotError::Truncated);
+        }
+        String::from_utf8(bytes).or_else(|_| corrupt("string is not UTF-8"))
+    }
+}
+
+/// The value of set entries, stored as nothing at all
+impl Codec for () {
+    fn encode<W: Write>(&self, _: &mut W) -> Result<(), SnapshotError> {
+        Ok(())
+    }
+
+    fn decode<R: Read>(_: &mut R) -> Result<Self, SnapshotError> {
+        Ok(())
+    }
+}
+
+/// What a snapshot says about the tree before its nodes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    /// Number of entries, so the number of nodes that follow
+    pub len: usize,
+    /// The duplicate policy of the tree, `Reject` for maps
+    pub duplicates: DuplicatePolicy,
+}
+
+/// Writes the magic, the format version, the tree kind, the duplicate policy
+/// and the entry count
+pub fn write_header<W: Write>(
+    writer: &mut W,
+    kind: TreeKind,
+    header: Header,
+) -> Result<(), SnapshotError> {
+    writer.write_all(&MAGIC)?;
+    VERSION.encode(writer)?;
+    kind.to_byte().encode(writer)?;
+    let duplicates: u8 = match header.duplicates {
+        DuplicatePolicy::Reject => 0,
+        DuplicatePolicy::Replace => 1,
+        DuplicatePolicy::Count => 2,
+    };
+    duplicates.encode(writer)?;
+    header.len.encode(writer)
+}
+
+/// Reads and checks the header
+pub fn read_header<R: Read>(reader: &mut R, kind: TreeKind) -> Result<Header, SnapshotError> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(SnapshotError::BadMagic);
+    }
+    let version = u16::decode(reader)?;
+    if version != VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+    let found = u8::decode(reader)?;
+    if TreeKind::from_byte(found) != Some(kind) {
+        return Err(SnapshotError::WrongKind {
+            expected: kind,
+            found,
+        });
+    }
+    let duplicates = match u8::decode(reader)? {
+        0 => DuplicatePolicy::Reject,
+        1 => DuplicatePolicy::Replace,
+        2 => DuplicatePolicy::Count,
+        b => return corrupt(format!("unknown duplicate policy {}", b)),
+    };
+    Ok(Header {
+        len: usize::decode(reader)?,
+        duplicates,
+    })
+}
+
+const HAS_LEFT: u8 = 0b001;
+const HAS_RIGHT: u8 = 0b010;
+const RED: u8 = 0b100;
+
+/// The byte in front of every node in pre-order, saying which children follow
+/// it and, for red-black trees, whether it is red
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeTag {
+    pub has_left: bool,
+    pub has_right: bool,
+    pub red: bool,
+}
+
+impl NodeTag {
+    pub fn write<W: Write>(self, writer: &mut W) -> Result<(), SnapshotError> {
+        let mut byte = 0;
+        if self.has_left {
+            byte |= HAS_LEFT;
+        }
+        if self.has_right {
+            byte |= HAS_RIGHT;
+        }
+        if self.red {
+            byte |= RED;
+        }
+        byte.encode(writer)
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, SnapshotError> {
+        let byte = u8::decode(reader)?;
+        if byte & !(HAS_LEFT | HAS_RIGHT | RED) != 0 {
+            return corrupt(format!("unknown node tag {:#04x}", byte));
+        }
+        Ok(NodeTag {
+            has_left: byte & HAS_LEFT != 0,
+            has_right: byte & HAS_RIGHT != 0,
+            red: byte & RED != 0,
+        })
+    }
+}
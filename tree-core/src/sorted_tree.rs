@@ -0,0 +1,49 @@
+use std::borrow::Borrow;
+use std::fmt::Display;
+
+/// The operations every balanced tree in this repo supports, so that callers
+/// (the CLIs, the benchmarks) can be written once and pick the balancing
+/// strategy through a type parameter.
+pub trait SortedTree<T: Ord>: Default {
+    /// Inserts `key`, returns whether it was added as a new entry. A key that is
+    /// already present is left unchanged unless the tree was made with another
+    /// `DuplicatePolicy`.
+    fn insert(&mut self, key: T) -> bool;
+
+    /// Removes `key` and returns the key that was stored, or `None` and leaves
+    /// the tree unchanged if it is not present
+    fn remove<Q>(&mut self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized;
+
+    /// Returns whether `key` is stored in the tree
+    fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized;
+
+    /// Number of keys in the tree
+    fn len(&self) -> usize;
+
+    /// Returns whether the tree holds no keys
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of nodes on the longest root-to-leaf path, 0 for an empty tree
+    fn height(&self) -> usize;
+
+    /// Number of nodes without children
+    fn leaf_count(&self) -> usize;
+
+    /// The keys in ascending order
+    fn inorder(&self) -> Vec<T>
+    where
+        T: Clone;
+
+    /// Renders the tree sideways, one node per line
+    fn render(&self) -> String
+    where
+        T: Display;
+}
@@ -0,0 +1,480 @@
+use std::borrow::Borrow;
+use std::collections::VecDeque;
+use std::iter::FusedIterator;
+use std::ops::{Bound, RangeBounds};
+
+/// A borrowed handle to a tree node, giving the traversals in this module
+/// access to its children and entry. Each tree implements it for whatever
+/// cheap `Copy` handle its storage has, a `&Node` or an arena index.
+pub trait NodeHandle: Copy {
+    type Key;
+    type Value;
+
+    /// The left child, if any
+    fn left(self) -> Option<Self>;
+
+    /// The right child, if any
+    fn right(self) -> Option<Self>;
+
+    /// The key and value stored in the node
+    fn entry(self) -> (Self::Key, Self::Value);
+
+    /// Number of nodes in the subtree rooted at this node
+    fn size(self) -> usize;
+}
+
+/// Pushes `node` and the left spine under it onto `stack`
+fn push_left<N: NodeHandle>(stack: &mut Vec<N>, mut node: Option<N>) {
+    while let Some(n) = node {
+        stack.push(n);
+        node = n.left();
+    }
+}
+
+/// Pushes `node` and the right spine under it onto `stack`
+fn push_right<N: NodeHandle>(stack: &mut Vec<N>, mut node: Option<N>) {
+    while let Some(n) = node {
+        stack.push(n);
+        node = n.right();
+    }
+}
+
+/// Panics like `BTreeMap::range` if `range` starts after it ends, or starts
+/// where it ends with both bounds excluded
+pub(crate) fn check_range<Q, R>(range: &R)
+where
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    match (range.start_bound(), range.end_bound()) {
+        (Bound::Excluded(s), Bound::Excluded(e)) if s == e => {
+            panic!("range start and end are equal and excluded")
+        }
+        (Bound::Included(s), Bound::Included(e))
+        | (Bound::Included(s), Bound::Excluded(e))
+        | (Bound::Excluded(s), Bound::Included(e))
+        | (Bound::Excluded(s), Bound::Excluded(e))
+            if s > e =>
+        {
+            panic!("range start is greater than range end")
+        }
+        _ => {}
+    }
+}
+
+/// Returns whether `key` is at or past the start of `range`
+pub(crate) fn after_start<Q, R>(key: &Q, range: &R) -> bool
+where
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    match range.start_bound() {
+        Bound::Included(s) => key >= s,
+        Bound::Excluded(s) => key > s,
+        Bound::Unbounded => true,
+    }
+}
+
+/// Returns whether `key` is at or before the end of `range`
+pub(crate) fn before_end<Q, R>(key: &Q, range: &R) -> bool
+where
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    match range.end_bound() {
+        Bound::Included(e) => key <= e,
+        Bound::Excluded(e) => key < e,
+        Bound::Unbounded => true,
+    }
+}
+
+/// Visits the nodes in ascending key order from the front and in descending
+/// order from the back, holding at most two root-to-leaf paths
+#[derive(Clone, Debug)]
+pub struct InOrder<N> {
+    front: Vec<N>,
+    back: Vec<N>,
+    len: usize,
+}
+
+impl<N: NodeHandle> InOrder<N> {
+    /// Starts a traversal of the `len` nodes under `root`
+    pub fn new(root: Option<N>, len: usize) -> Self {
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+        push_left(&mut front, root);
+        push_right(&mut back, root);
+        Self { front, back, len }
+    }
+}
+
+impl<N: NodeHandle> Iterator for InOrder<N> {
+    type Item = N;
+
+    fn next(&mut self) -> Option<N> {
+        // the two ends meet once len nodes have been handed out
+        if self.len == 0 {
+            return None;
+        }
+        let node = self.front.pop()?;
+        push_left(&mut self.front, node.right());
+        self.len -= 1;
+        Some(node)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<N: NodeHandle> DoubleEndedIterator for InOrder<N> {
+    fn next_back(&mut self) -> Option<N> {
+        if self.len == 0 {
+            return None;
+        }
+        let node = self.back.pop()?;
+        push_right(&mut self.back, node.left());
+        self.len -= 1;
+        Some(node)
+    }
+}
+
+impl<N: NodeHandle> ExactSizeIterator for InOrder<N> {}
+
+impl<N: NodeHandle> FusedIterator for InOrder<N> {}
+
+/// Visits every node before its left subtree and then its right subtree
+#[derive(Clone, Debug)]
+pub struct PreOrder<N> {
+    stack: Vec<N>,
+    len: usize,
+}
+
+impl<N: NodeHandle> PreOrder<N> {
+    /// Starts a traversal of the `len` nodes under `root`
+    pub fn new(root: Option<N>, len: usize) -> Self {
+        Self {
+            stack: root.into_iter().collect(),
+            len,
+        }
+    }
+}
+
+impl<N: NodeHandle> Iterator for PreOrder<N> {
+    type Item = N;
+
+    fn next(&mut self) -> Option<N> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.right());
+        self.stack.extend(node.left());
+        self.len -= 1;
+        Some(node)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<N: NodeHandle> ExactSizeIterator for PreOrder<N> {}
+
+impl<N: NodeHandle> FusedIterator for PreOrder<N> {}
+
+/// Visits every node after its left subtree and then its right subtree
+#[derive(Clone, Debug)]
+pub struct PostOrder<N> {
+    // the flag is set once the node's children have been pushed
+    stack: Vec<(N, bool)>,
+    len: usize,
+}
+
+impl<N: NodeHandle> PostOrder<N> {
+    /// Starts a traversal of the `len` nodes under `root`
+    pub fn new(root: Option<N>, len: usize) -> Self {
+        Self {
+            stack: root.into_iter().map(|n| (n, false)).collect(),
+            len,
+        }
+    }
+}
+
+impl<N: NodeHandle> Iterator for PostOrder<N> {
+    type Item = N;
+
+    fn next(&mut self) -> Option<N> {
+        loop {
+            let (node, expanded) = self.stack.pop()?;
+            if expanded {
+                self.len -= 1;
+                return Some(node);
+            }
+            self.stack.push((node, true));
+            self.stack.extend(node.right().map(|n| (n, false)));
+            self.stack.extend(node.left().map(|n| (n, false)));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<N: NodeHandle> ExactSizeIterator for PostOrder<N> {}
+
+impl<N: NodeHandle> FusedIterator for PostOrder<N> {}
+
+/// Visits the nodes level by level from the root, left to right within a level
+#[derive(Clone, Debug)]
+pub struct LevelOrder<N> {
+    queue: VecDeque<N>,
+    len: usize,
+}
+
+impl<N: NodeHandle> LevelOrder<N> {
+    /// Starts a traversal of the `len` nodes under `root`
+    pub fn new(root: Option<N>, len: usize) -> Self {
+        Self {
+            queue: root.into_iter().collect(),
+            len,
+        }
+    }
+}
+
+impl<N: NodeHandle> Iterator for LevelOrder<N> {
+    type Item = N;
+
+    fn next(&mut self) -> Option<N> {
+        let node = self.queue.pop_front()?;
+        self.queue.extend(node.left());
+        self.queue.extend(node.right());
+        self.len -= 1;
+        Some(node)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<N: NodeHandle> ExactSizeIterator for LevelOrder<N> {}
+
+impl<N: NodeHandle> FusedIterator for LevelOrder<N> {}
+
+/// Visits the nodes whose keys fall inside a range in ascending order from
+/// the front and descending order from the back, descending only along the
+/// paths to the two ends of the range
+#[derive(Clone, Debug)]
+pub struct Range<N> {
+    front: Vec<N>,
+    back: Vec<N>,
+    len: usize,
+}
+
+impl<'a, N, K> Range<N>
+where
+    N: NodeHandle<Key = &'a K>,
+    K: Ord + 'a,
+{
+    /// Starts a traversal of the nodes under `root` with keys in `range`.
+    /// Panics like `BTreeMap::range` if the range starts after it ends, or
+    /// starts where it ends with both bounds excluded.
+    pub fn new<Q, R>(root: Option<N>, range: R) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        check_range(&range);
+
+        // the stacks hold the ancestors still to be visited on the way to
+        // the first and last key inside the range, and the nodes passed over
+        // on the way are counted to know how many lie inside it
+        let mut front = Vec::new();
+        let mut before = 0;
+        let mut node = root;
+        while let Some(n) = node {
+            if after_start(n.entry().0.borrow(), &range) {
+                front.push(n);
+                node = n.left();
+            } else {
+                before += n.left().map_or(0, NodeHandle::size) + 1;
+                node = n.right();
+            }
+        }
+
+        let mut back = Vec::new();
+        let mut after = 0;
+        let mut node = root;
+        while let Some(n) = node {
+            if before_end(n.entry().0.borrow(), &range) {
+                back.push(n);
+                node = n.right();
+            } else {
+                after += n.right().map_or(0, NodeHandle::size) + 1;
+                node = n.left();
+            }
+        }
+
+        // counting rather than comparing the keys at both ends tells when they
+        // meet, which equal keys in a tree with duplicates would hide
+        let total = root.map_or(0, NodeHandle::size);
+        let len = total.saturating_sub(before + after);
+        Self { front, back, len }
+    }
+}
+
+impl<'a, N, K> Iterator for Range<N>
+where
+    N: NodeHandle<Key = &'a K>,
+    K: Ord + 'a,
+{
+    type Item = N;
+
+    fn next(&mut self) -> Option<N> {
+        if self.len == 0 {
+            return None;
+        }
+        let node = self.front.pop()?;
+        push_left(&mut self.front, node.right());
+        self.len -= 1;
+        Some(node)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, N, K> DoubleEndedIterator for Range<N>
+where
+    N: NodeHandle<Key = &'a K>,
+    K: Ord + 'a,
+{
+    fn next_back(&mut self) -> Option<N> {
+        if self.len == 0 {
+            return None;
+        }
+        let node = self.back.pop()?;
+        push_right(&mut self.back, node.left());
+        self.len -= 1;
+        Some(node)
+    }
+}
+
+impl<'a, N, K> ExactSizeIterator for Range<N>
+where
+    N: NodeHandle<Key = &'a K>,
+    K: Ord + 'a,
+{
+}
+
+impl<'a, N, K> FusedIterator for Range<N>
+where
+    N: NodeHandle<Key = &'a K>,
+    K: Ord + 'a,
+{
+}
+
+/// Yields the key and value of each node a traversal visits
+#[derive(Clone, Debug)]
+pub struct Entries<O> {
+    order: O,
+}
+
+impl<O> Entries<O> {
+    pub fn new(order: O) -> Self {
+        Self { order }
+    }
+}
+
+impl<O> Iterator for Entries<O>
+where
+    O: Iterator,
+    O::Item: NodeHandle,
+{
+    type Item = (<O::Item as NodeHandle>::Key, <O::Item as NodeHandle>::Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.order.next().map(NodeHandle::entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.order.size_hint()
+    }
+}
+
+impl<O> DoubleEndedIterator for Entries<O>
+where
+    O: DoubleEndedIterator,
+    O::Item: NodeHandle,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.order.next_back().map(NodeHandle::entry)
+    }
+}
+
+impl<O> ExactSizeIterator for Entries<O>
+where
+    O: ExactSizeIterator,
+    O::Item: NodeHandle,
+{
+}
+
+impl<O> FusedIterator for Entries<O>
+where
+    O: FusedIterator,
+    O::Item: NodeHandle,
+{
+}
+
+/// Yields the key of each node a traversal visits
+#[derive(Clone, Debug)]
+pub struct Keys<O> {
+    order: O,
+}
+
+impl<O> Keys<O> {
+    pub fn new(order: O) -> Self {
+        Self { order }
+    }
+}
+
+impl<O> Iterator for Keys<O>
+where
+    O: Iterator,
+    O::Item: NodeHandle,
+{
+    type Item = <O::Item as NodeHandle>::Key;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.order.next().map(|n| n.entry().0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.order.size_hint()
+    }
+}
+
+impl<O> DoubleEndedIterator for Keys<O>
+where
+    O: DoubleEndedIterator,
+    O::Item: NodeHandle,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.order.next_back().map(|n| n.entry().0)
+    }
+}
+
+impl<O> ExactSizeIterator for Keys<O>
+where
+    O: ExactSizeIterator,
+    O::Item: NodeHandle,
+{
+}
+
+impl<O> FusedIterator for Keys<O>
+where
+    O: FusedIterator,
+    O::Item: NodeHandle,
+{
+}
@@ -0,0 +1,141 @@
+use std::cmp::Ordering;
+use std::fmt::{self, Display};
+
+use crate::{Comparator, DuplicatePolicy};
+
+/// Which child a step down the tree goes to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// An invariant a node breaks
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The key is not between the keys of the ancestors it hangs under, or
+    /// repeats one in a tree that does not keep duplicates
+    OutOfOrder,
+    /// The stored number of nodes in the subtree is not the counted one
+    WrongSize { stored: usize, actual: usize },
+    /// The stored aggregate is not the one combined from the entry and the
+    /// aggregates stored on the children
+    WrongAggregate,
+    /// AVL: the stored height is not the height of the subtree
+    WrongHeight { stored: i32, actual: i32 },
+    /// AVL: the heights of the left and right subtree differ by more than one,
+    /// `balance` is left minus right
+    Unbalanced { balance: i32 },
+    /// Red-black: the root is red
+    RedRoot,
+    /// Red-black: the node and its parent are both red
+    RedUnderRed,
+    /// Red-black: the paths through the left and the right subtree pass
+    /// different numbers of black nodes
+    BlackHeightMismatch { left: usize, right: usize },
+    /// Red-black: the parent pointer does not lead to the node above it
+    WrongParent,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::OutOfOrder => write!(f, "key is out of order"),
+            Violation::WrongSize { stored, actual } => {
+                write!(f, "stored size {} but the subtree has {} nodes", stored, actual)
+            }
+            Violation::WrongAggregate => write!(f, "stored aggregate does not match the subtree"),
+            Violation::WrongHeight { stored, actual } => {
+                write!(f, "stored height {} but the subtree is {} high", stored, actual)
+            }
+            Violation::Unbalanced { balance } => write!(f, "balance factor {}", balance),
+            Violation::RedRoot => write!(f, "root is red"),
+            Violation::RedUnderRed => write!(f, "red node has a red parent"),
+            Violation::BlackHeightMismatch { left, right } => write!(
+                f,
+                "black height {} on the left but {} on the right",
+                left, right
+            ),
+            Violation::WrongParent => write!(f, "parent pointer is wrong"),
+        }
+    }
+}
+
+/// One broken invariant, with the key of the node that breaks it and the
+/// steps from the root down to that node
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem<'a, K> {
+    pub key: &'a K,
+    pub path: Vec<Side>,
+    pub violation: Violation,
+}
+
+impl<'a, K: Display> Display for Problem<'a, K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "key {} at root", self.key)?;
+        for side in &self.path {
+            match side {
+                Side::Left => write!(f, ".left")?,
+                Side::Right => write!(f, ".right")?,
+            }
+        }
+        write!(f, ": {}", self.violation)
+    }
+}
+
+/// What `validate` found in a tree. The tree is healthy when there are no
+/// problems and it holds as many nodes as its length says.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report<'a, K> {
+    /// The length the tree keeps
+    pub len: usize,
+    /// The nodes actually reachable from the root
+    pub nodes: usize,
+    /// Every broken invariant, parents before their children
+    pub problems: Vec<Problem<'a, K>>,
+}
+
+impl<'a, K> Report<'a, K> {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty() && self.len == self.nodes
+    }
+}
+
+impl<'a, K: Display> Display for Report<'a, K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_valid() {
+            return write!(f, "valid tree of {} nodes", self.len);
+        }
+        write!(f, "invalid tree")?;
+        if self.len != self.nodes {
+            write!(
+                f,
+                "\nlength is {} but {} nodes are reachable",
+                self.len, self.nodes
+            )?;
+        }
+        for problem in &self.problems {
+            write!(f, "\n{}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// True if `key` lies between the bounds a node inherits from its ancestors in
+/// the order of `cmp`, `None` being unbounded. Keys equal to a bound are only in
+/// bounds for trees that keep duplicates, which can rotate an equal key to
+/// either side.
+pub fn in_bounds<K, C: Comparator<K>>(
+    key: &K,
+    lower: Option<&K>,
+    upper: Option<&K>,
+    duplicates: DuplicatePolicy,
+    cmp: &C,
+) -> bool {
+    let fits = |ord: Ordering| match duplicates {
+        DuplicatePolicy::Count => ord != Ordering::Greater,
+        _ => ord == Ordering::Less,
+    };
+    lower.is_none_or(|l| fits(cmp.compare(l, key)))
+        && upper.is_none_or(|u| fits(cmp.compare(key, u)))
+}
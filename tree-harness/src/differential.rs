@@ -0,0 +1,129 @@
+use crate::Op;
+use avl::AVL;
+use rbt::RBTree;
+use std::fmt;
+
+/// The first step at which the AVL and the RBTree stopped agreeing, with both
+/// trees as they were right after it
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    pub step: usize,
+    pub op: Op,
+    pub reason: String,
+    pub avl: String,
+    pub rbt: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "trees diverge at step {} {:?}: {}\nAVL:\n{}\nRBTree:\n{}",
+            self.step, self.op, self.reason, self.avl, self.rbt
+        )
+    }
+}
+
+/// Replays `ops` into an AVL and an RBTree and checks after every step that
+/// inserts, deletes and finds give the same answer, both hold the same keys in the same order, the
+/// leaf counts are possible for that many keys and each height is within the
+/// bound of its balancing scheme. Returns the first step where one of those
+/// fails.
+pub fn compare(ops: &[Op]) -> Result<(), Divergence> {
+    let mut avl = AVL::new();
+    let mut rbt = RBTree::new();
+    for (step, &op) in ops.iter().enumerate() {
+        let mut reason = None;
+        match op {
+            Op::Insert(key) => {
+                let (from_avl, from_rbt) = (avl.insert(key), rbt.insert(key));
+                if from_avl != from_rbt {
+                    reason = Some(format!(
+                        "insert {} returns {} from the AVL but {} from the RBTree",
+                        key, from_avl, from_rbt
+                    ));
+                }
+            }
+            Op::Delete(key) => {
+                let (from_avl, from_rbt) = (avl.delete(&key), rbt.delete(&key));
+                if from_avl != from_rbt {
+                    reason = Some(format!(
+                        "delete {} returns {:?} from the AVL but {:?} from the RBTree",
+                        key, from_avl, from_rbt
+                    ));
+                }
+            }
+            Op::Find(key) => {
+                let (in_avl, in_rbt) = (avl.search(&key), rbt.find(&key).is_some());
+                if in_avl != in_rbt {
+                    reason = Some(format!(
+                        "find {} is {} in the AVL but {} in the RBTree",
+                        key, in_avl, in_rbt
+                    ));
+                }
+            }
+            Op::Clear => {
+                avl = AVL::new();
+                rbt = RBTree::new();
+            }
+        }
+
+        if let Some(reason) = reason.or_else(|| disagreement(&avl, &rbt)) {
+            return Err(Divergence {
+                step,
+                op,
+                reason,
+                avl: avl.print(),
+                rbt: rbt.pretty_print(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// the first way the two trees disagree or break a shape bound, if any
+fn disagreement(avl: &AVL<u8>, rbt: &RBTree<u8>) -> Option<String> {
+    if !avl.iter().eq(rbt.iter()) {
+        return Some(format!(
+            "AVL holds {:?} but RBTree holds {:?}",
+            avl.inorder(),
+            rbt.inorder_traversal()
+        ));
+    }
+    let n = avl.count() as usize;
+    if rbt.count() as usize != n {
+        return Some(format!(
+            "AVL counts {} keys but RBTree counts {}",
+            n,
+            rbt.count()
+        ));
+    }
+
+    // a binary tree of n > 0 nodes has between 1 and ceil(n / 2) leaves
+    let max_leaves = n.div_ceil(2);
+    for (name, leaves) in [("AVL", avl.count_leaves()), ("RBTree", rbt.count_leaves())].iter() {
+        let leaves = *leaves as usize;
+        if (n == 0 && leaves != 0) || (n > 0 && (leaves == 0 || leaves > max_leaves)) {
+            return Some(format!("{} has {} leaves for {} keys", name, leaves, n));
+        }
+    }
+
+    // no binary tree is lower than log2(n + 1), an AVL tree is at most
+    // 1.44 log2(n + 2) high and a red-black tree at most 2 log2(n + 1)
+    let log = |x: usize| (x as f64).log2();
+    let min_height = log(n + 1).ceil();
+    let bounds = [
+        ("AVL", avl.height(), 1.4405 * log(n + 2)),
+        ("RBTree", rbt.get_height(), 2.0 * log(n + 1)),
+    ];
+    for (name, height, max_height) in bounds.iter() {
+        let height = f64::from(*height);
+        if height < min_height || height > *max_height {
+            return Some(format!(
+                "{} is {} high for {} keys, outside {}..={:.2}",
+                name, height, n, min_height, max_height
+            ));
+        }
+    }
+    None
+}
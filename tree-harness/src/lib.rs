@@ -0,0 +1,155 @@
+use arbitrary::{Arbitrary, Unstructured};
+use avl::AVL;
+use rbt::RBTree;
+use std::collections::BTreeSet;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+mod differential;
+#[cfg(feature = "model")]
+pub mod model;
+
+pub use differential::{compare, Divergence};
+
+/// Largest key an operation uses. Keeping keys this few makes inserts of
+/// present keys and deletes of missing ones common, and builds trees deep
+/// enough for every rotation and recoloring case of delete.
+pub const MAX_KEY: u8 = 63;
+
+/// One step of a workload
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Insert(u8),
+    Delete(u8),
+    Find(u8),
+    /// Replaces every tree with an empty one
+    Clear,
+}
+
+impl<'a> Arbitrary<'a> for Op {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let kind = u.int_in_range(0..=31u8)?;
+        let key = u.int_in_range(0..=MAX_KEY)?;
+        // clear rarely, so the trees get big between clears
+        Ok(match kind {
+            0..=13 => Op::Insert(key),
+            14..=26 => Op::Delete(key),
+            27..=30 => Op::Find(key),
+            _ => Op::Clear,
+        })
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (2, Some(2))
+    }
+}
+
+/// Turns raw bytes into operations, two bytes each. Any input decodes, so
+/// every fuzzer input and every saved seed replays the same way.
+pub fn decode(bytes: &[u8]) -> Vec<Op> {
+    Vec::arbitrary_take_rest(Unstructured::new(bytes)).unwrap_or_default()
+}
+
+/// Decodes every input saved under seeds/ in file name order and hands it to
+/// `run`. If `run` panics on an input, the panic is raised again with the path
+/// of the file in front of its message. Panics if the directory is missing or
+/// holds no inputs.
+pub fn replay_seeds<F: FnMut(&[Op])>(mut run: F) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("seeds");
+    let mut paths: Vec<_> = fs::read_dir(&dir)
+        .expect("seeds directory is missing")
+        .map(|entry| entry.expect("unreadable seeds entry").path())
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no inputs in {}", dir.display());
+
+    for path in paths {
+        let ops = decode(&fs::read(&path).expect("unreadable seed input"));
+        if let Err(cause) = panic::catch_unwind(AssertUnwindSafe(|| run(&ops))) {
+            let message = match cause.downcast_ref::<String>() {
+                Some(message) => message.as_str(),
+                None => cause
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .unwrap_or("no message"),
+            };
+            panic!("{}: {}", path.display(), message);
+        }
+    }
+}
+
+/// Runs `ops` on an AVL and an RBTree next to a BTreeSet and panics at the
+/// first step where a find disagrees with the set, a tree holds other keys than
+/// the set or a tree fails `validate`. The panic names the step and prints the
+/// broken tree.
+pub fn check(ops: &[Op]) {
+    let mut avl = AVL::new();
+    let mut rbt = RBTree::new();
+    let mut model = BTreeSet::new();
+    for (step, op) in ops.iter().enumerate() {
+        match *op {
+            Op::Insert(key) => {
+                avl.insert(key);
+                rbt.insert(key);
+                model.insert(key);
+            }
+            Op::Delete(key) => {
+                avl.delete(&key);
+                rbt.delete(&key);
+                model.remove(&key);
+            }
+            Op::Find(key) => {
+                let expected = model.contains(&key);
+                assert_eq!(avl.search(&key), expected, "step {} {:?}: AVL", step, op);
+                assert_eq!(
+                    rbt.find(&key).is_some(),
+                    expected,
+                    "step {} {:?}: RBTree",
+                    step,
+                    op
+                );
+            }
+            Op::Clear => {
+                avl = AVL::new();
+                rbt = RBTree::new();
+                model.clear();
+            }
+        }
+
+        let report = avl.validate();
+        assert!(
+            report.is_valid(),
+            "step {} {:?}: AVL {}\n{}",
+            step,
+            op,
+            report,
+            avl.print()
+        );
+        let report = rbt.validate();
+        assert!(
+            report.is_valid(),
+            "step {} {:?}: RBTree {}\n{}",
+            step,
+            op,
+            report,
+            rbt.pretty_print()
+        );
+        assert!(
+            avl.iter().eq(model.iter()),
+            "step {} {:?}: AVL holds {:?}, expected {:?}",
+            step,
+            op,
+            avl.inorder(),
+            model
+        );
+        assert!(
+            rbt.iter().eq(model.iter()),
+            "step {} {:?}: RBTree holds {:?}, expected {:?}",
+            step,
+            op,
+            rbt.inorder_traversal(),
+            model
+        );
+    }
+}
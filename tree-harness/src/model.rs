@@ -0,0 +1,382 @@
+//! Model-based tests shared by the tree crates. Random operation sequences are
+//! applied to a tree and to a `BTreeSet` (or a `BTreeMap` counting copies for
+//! trees that keep duplicates) side by side, and after every step the two must
+//! agree and the tree must pass its own invariant check. proptest shrinks a
+//! failing sequence down to the shortest one that still fails.
+
+use avl::AVL;
+use proptest::prelude::*;
+use rbt::RBTree;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+use std::ops::Range;
+use tree_core::SortedTree;
+
+/// One step of a model test
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Insert(i32),
+    Delete(i32),
+    /// Looks the key and its neighbors up without changing the tree
+    Search(i32),
+    /// Deletes every copy of the key
+    DeleteAll(i32),
+    PopFirst,
+    PopLast,
+}
+
+/// Inserts, deletes, searches and pops on keys from `keys`. A small range makes
+/// inserts of present keys and deletes of missing ones common.
+pub fn op(keys: Range<i32>) -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => keys.clone().prop_map(Op::Insert),
+        2 => keys.clone().prop_map(Op::Delete),
+        1 => keys.prop_map(Op::Search),
+        1 => Just(Op::PopFirst),
+        1 => Just(Op::PopLast),
+    ]
+}
+
+/// The ops of `op` and deletes of every copy of a key, for trees with duplicates
+pub fn multiset_op(keys: Range<i32>) -> impl Strategy<Value = Op> {
+    prop_oneof![
+        5 => op(keys.clone()),
+        1 => keys.prop_map(Op::DeleteAll),
+    ]
+}
+
+/// Inserts of the keys `0..n` in ascending or descending order, the worst case
+/// for a tree that does not rebalance
+pub fn sorted_inserts(n: i32, descending: bool) -> Vec<Op> {
+    match descending {
+        true => (0..n).rev().map(Op::Insert).collect(),
+        false => (0..n).map(Op::Insert).collect(),
+    }
+}
+
+/// What the model tests need from a tree beyond `SortedTree`. Both trees have
+/// these methods under the same names, the trait only lets the tests be
+/// written once.
+pub trait ModelTree: SortedTree<i32> {
+    /// An empty tree that keeps every copy of a key inserted more than once
+    fn multiset() -> Self;
+
+    /// The largest height a valid tree of `len` keys can have
+    fn max_height(len: usize) -> f64;
+
+    /// The report of `validate` if the tree breaks an invariant
+    fn problems(&self) -> Option<String>;
+
+    fn keys(&self) -> Vec<i32>;
+    fn remove_all(&mut self, key: &i32) -> usize;
+    fn pop_first(&mut self) -> Option<i32>;
+    fn pop_last(&mut self) -> Option<i32>;
+    fn first(&self) -> Option<&i32>;
+    fn last(&self) -> Option<&i32>;
+    fn floor(&self, key: &i32) -> Option<&i32>;
+    fn ceiling(&self, key: &i32) -> Option<&i32>;
+    fn predecessor(&self, key: &i32) -> Option<&i32>;
+    fn successor(&self, key: &i32) -> Option<&i32>;
+    fn count_of(&self, key: &i32) -> usize;
+    fn count_range(&self, range: (Bound<i32>, Bound<i32>)) -> usize;
+
+    /// The keys in `range` from the largest down
+    fn range_rev(&self, range: (Bound<i32>, Bound<i32>)) -> Vec<i32>;
+}
+
+impl ModelTree for AVL<i32> {
+    fn multiset() -> Self {
+        AVL::multiset()
+    }
+
+    /// The height of an AVL tree of n nodes is below 1.44 log2(n + 2)
+    fn max_height(len: usize) -> f64 {
+        1.4405 * ((len + 2) as f64).log2()
+    }
+
+    fn problems(&self) -> Option<String> {
+        let report = self.validate();
+        match report.is_valid() {
+            true => None,
+            false => Some(format!("{}\n{}", report, self.print())),
+        }
+    }
+
+    fn keys(&self) -> Vec<i32> {
+        self.iter().copied().collect()
+    }
+
+    fn remove_all(&mut self, key: &i32) -> usize {
+        AVL::remove_all(self, key)
+    }
+
+    fn pop_first(&mut self) -> Option<i32> {
+        AVL::pop_first(self)
+    }
+
+    fn pop_last(&mut self) -> Option<i32> {
+        AVL::pop_last(self)
+    }
+
+    fn first(&self) -> Option<&i32> {
+        AVL::first(self)
+    }
+
+    fn last(&self) -> Option<&i32> {
+        AVL::last(self)
+    }
+
+    fn floor(&self, key: &i32) -> Option<&i32> {
+        AVL::floor(self, key)
+    }
+
+    fn ceiling(&self, key: &i32) -> Option<&i32> {
+        AVL::ceiling(self, key)
+    }
+
+    fn predecessor(&self, key: &i32) -> Option<&i32> {
+        AVL::predecessor(self, key)
+    }
+
+    fn successor(&self, key: &i32) -> Option<&i32> {
+        AVL::successor(self, key)
+    }
+
+    fn count_of(&self, key: &i32) -> usize {
+        AVL::count_of(self, key)
+    }
+
+    fn count_range(&self, range: (Bound<i32>, Bound<i32>)) -> usize {
+        AVL::count_range(self, range)
+    }
+
+    fn range_rev(&self, range: (Bound<i32>, Bound<i32>)) -> Vec<i32> {
+        self.range(range).rev().copied().collect()
+    }
+}
+
+impl ModelTree for RBTree<i32> {
+    fn multiset() -> Self {
+        RBTree::multiset()
+    }
+
+    /// The height of a red-black tree of n nodes is at most 2 log2(n + 1)
+    fn max_height(len: usize) -> f64 {
+        2.0 * ((len + 1) as f64).log2()
+    }
+
+    fn problems(&self) -> Option<String> {
+        let report = self.validate();
+        match report.is_valid() {
+            true => None,
+            false => Some(format!("{}\n{}", report, self.pretty_print())),
+        }
+    }
+
+    fn keys(&self) -> Vec<i32> {
+        self.iter().copied().collect()
+    }
+
+    fn remove_all(&mut self, key: &i32) -> usize {
+        RBTree::remove_all(self, key)
+    }
+
+    fn pop_first(&mut self) -> Option<i32> {
+        RBTree::pop_first(self)
+    }
+
+    fn pop_last(&mut self) -> Option<i32> {
+        RBTree::pop_last(self)
+    }
+
+    fn first(&self) -> Option<&i32> {
+        RBTree::first(self)
+    }
+
+    fn last(&self) -> Option<&i32> {
+        RBTree::last(self)
+    }
+
+    fn floor(&self, key: &i32) -> Option<&i32> {
+        RBTree::floor(self, key)
+    }
+
+    fn ceiling(&self, key: &i32) -> Option<&i32> {
+        RBTree::ceiling(self, key)
+    }
+
+    fn predecessor(&self, key: &i32) -> Option<&i32> {
+        RBTree::predecessor(self, key)
+    }
+
+    fn successor(&self, key: &i32) -> Option<&i32> {
+        RBTree::successor(self, key)
+    }
+
+    fn count_of(&self, key: &i32) -> usize {
+        RBTree::count_of(self, key)
+    }
+
+    fn count_range(&self, range: (Bound<i32>, Bound<i32>)) -> usize {
+        RBTree::count_range(self, range)
+    }
+
+    fn range_rev(&self, range: (Bound<i32>, Bound<i32>)) -> Vec<i32> {
+        self.range(range).rev().copied().collect()
+    }
+}
+
+/// Applies `ops` to an empty tree and to a `BTreeSet`. After every step the
+/// tree must hold the keys of the set, pass its invariant check and stay within
+/// its height bound, and every lookup must answer like the set.
+pub fn run<S: ModelTree>(ops: &[Op]) -> Result<(), TestCaseError> {
+    let mut tree = S::default();
+    let mut model = BTreeSet::new();
+    for (step, op) in ops.iter().enumerate() {
+        match *op {
+            Op::Insert(key) => {
+                prop_assert_eq!(
+                    tree.insert(key),
+                    model.insert(key),
+                    "step {}: {:?}",
+                    step,
+                    op
+                );
+            }
+            Op::Delete(key) => {
+                prop_assert_eq!(
+                    tree.remove(&key),
+                    model.take(&key),
+                    "step {}: {:?}",
+                    step,
+                    op
+                );
+            }
+            Op::DeleteAll(key) => {
+                let expected = usize::from(model.remove(&key));
+                prop_assert_eq!(tree.remove_all(&key), expected, "step {}: {:?}", step, op);
+            }
+            Op::Search(key) => {
+                prop_assert_eq!(
+                    tree.contains(&key),
+                    model.contains(&key),
+                    "step {}: {:?}",
+                    step,
+                    op
+                );
+                prop_assert_eq!(tree.floor(&key), model.range(..=key).next_back());
+                prop_assert_eq!(tree.ceiling(&key), model.range(key..).next());
+                prop_assert_eq!(tree.predecessor(&key), model.range(..key).next_back());
+                let above = (Excluded(key), Unbounded);
+                prop_assert_eq!(tree.successor(&key), model.range(above).next());
+                let window = (Included(key), Excluded(key + 8));
+                prop_assert_eq!(tree.count_range(window), model.range(window).count());
+                let below = (Unbounded, Included(key));
+                prop_assert_eq!(tree.count_range(below), model.range(below).count());
+                prop_assert_eq!(tree.count_range(above), model.range(above).count());
+            }
+            Op::PopFirst => {
+                prop_assert_eq!(
+                    tree.pop_first(),
+                    model.pop_first(),
+                    "step {}: {:?}",
+                    step,
+                    op
+                );
+            }
+            Op::PopLast => {
+                prop_assert_eq!(tree.pop_last(), model.pop_last(), "step {}: {:?}", step, op);
+            }
+        }
+        if let Some(problems) = tree.problems() {
+            prop_assert!(false, "step {}: {:?}\n{}", step, op, problems);
+        }
+        prop_assert!(
+            tree.keys().iter().eq(model.iter()),
+            "step {}: {:?}\n{}",
+            step,
+            op,
+            tree.render()
+        );
+        prop_assert_eq!(tree.len(), model.len());
+        prop_assert_eq!(tree.is_empty(), model.is_empty());
+        prop_assert_eq!(tree.first(), model.first());
+        prop_assert_eq!(tree.last(), model.last());
+        prop_assert!(tree.height() as f64 <= S::max_height(model.len()));
+    }
+    Ok(())
+}
+
+/// Takes one copy of key out of a model counting the copies of each key
+fn take_copy(model: &mut BTreeMap<i32, usize>, key: i32) -> Option<i32> {
+    let copies = model.get_mut(&key)?;
+    *copies -= 1;
+    if *copies == 0 {
+        model.remove(&key);
+    }
+    Some(key)
+}
+
+/// Applies `ops` to an empty multiset and to a `BTreeMap` counting the copies
+/// of each key, checking the copies and the tree after every step like `run`
+pub fn run_counted<S: ModelTree>(ops: &[Op]) -> Result<(), TestCaseError> {
+    let mut tree = S::multiset();
+    let mut model: BTreeMap<i32, usize> = BTreeMap::new();
+    for (step, op) in ops.iter().enumerate() {
+        match *op {
+            Op::Insert(key) => {
+                prop_assert!(tree.insert(key), "step {}: {:?}", step, op);
+                *model.entry(key).or_insert(0) += 1;
+            }
+            Op::Delete(key) => {
+                let expected = take_copy(&mut model, key);
+                prop_assert_eq!(tree.remove(&key), expected, "step {}: {:?}", step, op);
+            }
+            Op::Search(key) => {
+                prop_assert_eq!(tree.contains(&key), model.contains_key(&key));
+            }
+            Op::DeleteAll(key) => {
+                let expected = model.remove(&key).unwrap_or(0);
+                prop_assert_eq!(tree.remove_all(&key), expected, "step {}: {:?}", step, op);
+            }
+            Op::PopFirst => {
+                let expected = model.keys().next().copied();
+                let expected = expected.and_then(|key| take_copy(&mut model, key));
+                prop_assert_eq!(tree.pop_first(), expected, "step {}: {:?}", step, op);
+            }
+            Op::PopLast => {
+                let expected = model.keys().next_back().copied();
+                let expected = expected.and_then(|key| take_copy(&mut model, key));
+                prop_assert_eq!(tree.pop_last(), expected, "step {}: {:?}", step, op);
+            }
+        }
+        if let Op::Insert(key) | Op::Delete(key) | Op::Search(key) | Op::DeleteAll(key) = *op {
+            let copies = model.get(&key).copied().unwrap_or(0);
+            prop_assert_eq!(tree.count_of(&key), copies, "step {}: {:?}", step, op);
+            let exactly = (Included(key), Included(key));
+            prop_assert_eq!(tree.range_rev(exactly).len(), copies);
+            prop_assert_eq!(tree.count_range(exactly), copies);
+            let below: usize = model.range(..key).map(|(_, copies)| copies).sum();
+            prop_assert_eq!(tree.count_range((Unbounded, Excluded(key))), below);
+            let below = model.range(..=key).next_back().map(|(key, _)| key);
+            prop_assert_eq!(tree.floor(&key), below);
+            let above = model.range((Excluded(key), Unbounded)).next();
+            prop_assert_eq!(tree.successor(&key), above.map(|(key, _)| key));
+        }
+        if let Some(problems) = tree.problems() {
+            prop_assert!(false, "step {}: {:?}\n{}", step, op, problems);
+        }
+        let expected = model
+            .iter()
+            .flat_map(|(key, copies)| std::iter::repeat_n(key, *copies));
+        prop_assert!(
+            tree.keys().iter().eq(expected),
+            "step {}: {:?}\n{}",
+            step,
+            op,
+            tree.render()
+        );
+        prop_assert_eq!(tree.len(), model.values().sum::<usize>());
+    }
+    Ok(())
+}
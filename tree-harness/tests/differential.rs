@@ -0,0 +1,80 @@
+// Replays the same workloads into AVL and RBTree and expects them to agree at
+// every step, see tree_harness::compare
+
+use proptest::prelude::*;
+use tree_harness::{compare, replay_seeds, Op, MAX_KEY};
+
+fn agree(ops: &[Op]) {
+    if let Err(divergence) = compare(ops) {
+        panic!("{}", divergence);
+    }
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let key = 0..=MAX_KEY;
+    prop_oneof![
+        6 => key.clone().prop_map(Op::Insert),
+        5 => key.clone().prop_map(Op::Delete),
+        2 => key.prop_map(Op::Find),
+        1 => Just(Op::Clear),
+    ]
+}
+
+#[test]
+fn ascending_then_descending_deletes() {
+    let mut ops: Vec<Op> = (0..=MAX_KEY).map(Op::Insert).collect();
+    ops.extend((0..=MAX_KEY).rev().map(Op::Delete));
+    agree(&ops);
+}
+
+#[test]
+fn descending_then_ascending_deletes() {
+    let mut ops: Vec<Op> = (0..=MAX_KEY).rev().map(Op::Insert).collect();
+    ops.extend((0..=MAX_KEY).map(Op::Delete));
+    agree(&ops);
+}
+
+#[test]
+fn zigzag_inserts_and_deletes_from_the_middle() {
+    let mut ops = Vec::new();
+    for i in 0..=MAX_KEY / 2 {
+        ops.push(Op::Insert(i));
+        ops.push(Op::Insert(MAX_KEY - i));
+    }
+    for i in 0..=MAX_KEY / 2 {
+        ops.push(Op::Delete(MAX_KEY / 2 - i));
+        ops.push(Op::Find(MAX_KEY / 2 + i));
+        ops.push(Op::Delete(MAX_KEY / 2 + 1 + i));
+    }
+    agree(&ops);
+}
+
+#[test]
+fn repeated_and_missing_keys() {
+    let ops = [
+        Op::Delete(3),
+        Op::Insert(3),
+        Op::Insert(3),
+        Op::Find(3),
+        Op::Delete(3),
+        Op::Delete(3),
+        Op::Find(3),
+        Op::Clear,
+        Op::Find(3),
+    ];
+    agree(&ops);
+}
+
+#[test]
+fn saved_fuzz_inputs_agree() {
+    replay_seeds(agree);
+}
+
+proptest! {
+    #[test]
+    fn random_workloads_agree(ops in prop::collection::vec(op(), 0..500)) {
+        if let Err(divergence) = compare(&ops) {
+            return Err(TestCaseError::fail(divergence.to_string()));
+        }
+    }
+}
@@ -0,0 +1,14 @@
+// Replays every input saved under seeds/ through the same check the fuzz
+// target runs. A crash found by `cargo fuzz run ops` is kept by copying its
+// file from fuzz/artifacts/ops/ into seeds/.
+
+#[test]
+fn saved_inputs_pass() {
+    tree_harness::replay_seeds(tree_harness::check);
+}
+
+#[test]
+#[should_panic(expected = "seed-21808d2938b92e4c8904527e4237b432adcaf271: step 3 failed")]
+fn a_failing_input_is_reported_with_its_file() {
+    tree_harness::replay_seeds(|_| panic!("step {} failed", 3));
+}